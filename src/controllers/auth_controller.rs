@@ -11,6 +11,7 @@ use serde::{Deserialize, Serialize};
 use sha_crypt::{Sha512Params, sha512_simple, sha512_check};
 use tower_sessions::{Session, SessionStore};
 use crate::db_manager::entities::user::User;
+use crate::db_manager::{DbError, DbPool};
 
 #[derive(Deserialize)]
 pub struct LoginUser {
@@ -48,7 +49,7 @@ fn generate_jwt(username: &str) -> String {
         .expect("Failed to encode JWT")
 }
 
-pub async fn register(Json(payload): Json<CreateUser>) -> Json<Value> {
+pub async fn register(State(pool): State<DbPool>, Json(payload): Json<CreateUser>) -> Result<Json<Value>, DbError> {
     let params = Sha512Params::new(10_000).expect("Erreur!");
 
     let hashed_password = sha512_simple(&*payload.password, &params)
@@ -56,50 +57,37 @@ pub async fn register(Json(payload): Json<CreateUser>) -> Json<Value> {
 
     let user = User::new(0, payload.username, payload.email, hashed_password);
 
-    let result = create_user(user);
+    let created_user = create_user(&pool, user).await?;
 
-    if result.is_err() {
-        return Json(json!({ "error": result.err().unwrap() }));
-    }
-
-    Json(json!({ "data": result.unwrap() }))
+    Ok(Json(json!({ "data": created_user })))
 
 }
 
-pub async fn login(Extension(session): Extension<Session>, Json(payload): Json<LoginUser>) -> impl IntoResponse {
-
-    let users = get_by_username(payload.username);
-
-    if users.is_err() {
-        return (
-            [(axum::http::header::SET_COOKIE, "".to_string())],
-            Json(json!({ "data": "Wrong username or password" }))
-        )
-    }
+pub async fn login(Extension(session): Extension<Session>, State(pool): State<DbPool>, Json(payload): Json<LoginUser>) -> Result<impl IntoResponse, DbError> {
 
-    let user = users.unwrap();
+    let user = get_by_username(&pool, payload.username).await?;
 
     if user.len() == 1 {
         let user_check = user.get(0).unwrap();
         if !sha512_check(&*payload.password, &*user_check.password_).is_ok() {
-           return (
+           return Ok((
                [(axum::http::header::SET_COOKIE, "".to_string())],
                Json(json!({ "data": "Wrong username or password" }))
-           )
+           ))
         }
 
         let jwt = generate_jwt(&user_check.username_);
         session.insert("jwt", jwt.clone()).await.unwrap();
         let cookie = format!("jwt={}; HttpOnly; Path=/", jwt);
-        (
+        Ok((
             [(axum::http::header::SET_COOKIE, cookie)],
             Json(json!({ "data": "Logged in" }))
-        )
+        ))
     } else {
-        (
+        Ok((
             [(axum::http::header::SET_COOKIE, "".to_string())],
             Json(json!({ "data": "Wrong username or password" }))
-        )
+        ))
     }
 
 }
\ No newline at end of file
@@ -0,0 +1,16 @@
+use axum::http::StatusCode;
+use axum::Json;
+use axum::response::{IntoResponse, Response};
+use serde_json::json;
+use crate::db_manager::DbError;
+
+impl IntoResponse for DbError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            DbError::NotFound(_) => StatusCode::NOT_FOUND,
+            DbError::Conflict(_) | DbError::ForeignKey(_) => StatusCode::CONFLICT,
+            DbError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR
+        };
+        (status, Json(json!({ "error": self.to_string() }))).into_response()
+    }
+}
\ No newline at end of file
@@ -1,5 +1,6 @@
 mod auth_controller;
 mod default_controller;
+mod db_error_response;
 
 pub use auth_controller::*;
 pub use default_controller::*;
\ No newline at end of file
@@ -0,0 +1,321 @@
+use chrono::{Datelike, NaiveDate};
+use diesel::{sqlite::Sqlite, BoolExpressionMethods, EscapeExpressionMethods, ExpressionMethods, QueryDsl, RunQueryDsl, SqliteConnection, TextExpressionMethods};
+use crate::db_manager::{entities::{schema::document::dsl::*, Document}, DbError};
+
+/// Whether a document needs one or all of the requested tags or genres.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub enum MatchMode {
+    #[default]
+    Any,
+    All
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub enum DocumentSort {
+    #[default]
+    Id,
+    Name,
+    Date
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub enum SortDirection {
+    #[default]
+    Ascending,
+    Descending
+}
+
+/// Position after the last document of a page, used to fetch the next one.
+/// The key is None for documents without date.
+#[derive(PartialEq, Debug, Clone)]
+pub struct DocumentCursor {
+    pub id_ : i32,
+    pub key_ : Option<String>
+}
+
+/// Criteria for `get_documents_matching`, unset criteria don't filter anything.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct DocumentQuery {
+    pub author_ : Option<i32>,
+    pub series_ : Option<i32>,
+    pub category_ : Option<i32>,
+    pub tags_ : Vec<i32>,
+    pub tag_match_ : MatchMode,
+    pub genres_ : Vec<i32>,
+    pub genre_match_ : MatchMode,
+    pub date_from_ : Option<NaiveDate>,
+    pub date_to_ : Option<NaiveDate>,
+    pub title_ : Option<String>,
+    pub sort_ : DocumentSort,
+    pub direction_ : SortDirection,
+    pub limit_ : Option<i64>,
+    pub offset_ : Option<i64>,
+    pub after_ : Option<DocumentCursor>
+}
+
+impl DocumentQuery {
+    pub fn new() -> DocumentQuery {
+        return DocumentQuery::default();
+    }
+
+    /// Documents the author contributed to, whatever their role.
+    pub fn author(mut self, author_id : &i32) -> DocumentQuery {
+        self.author_ = Some(*author_id);
+        return self;
+    }
+
+    pub fn series(mut self, series_id : &i32) -> DocumentQuery {
+        self.series_ = Some(*series_id);
+        return self;
+    }
+
+    /// Documents stored in the category or linked to it.
+    pub fn category(mut self, category_id : &i32) -> DocumentQuery {
+        self.category_ = Some(*category_id);
+        return self;
+    }
+
+    pub fn tags(mut self, tag_ids : &[i32], mode : &MatchMode) -> DocumentQuery {
+        self.tags_ = tag_ids.to_vec();
+        self.tag_match_ = *mode;
+        return self;
+    }
+
+    pub fn genres(mut self, genre_ids : &[i32], mode : &MatchMode) -> DocumentQuery {
+        self.genres_ = genre_ids.to_vec();
+        self.genre_match_ = *mode;
+        return self;
+    }
+
+    /// Both bounds are inclusive. A partial date matches when its year or month overlaps the range,
+    /// documents without date never match.
+    pub fn dated_between(mut self, from : &Option<NaiveDate>, to : &Option<NaiveDate>) -> DocumentQuery {
+        self.date_from_ = *from;
+        self.date_to_ = *to;
+        return self;
+    }
+
+    /// Documents whose name contains the text, ignoring ASCII case.
+    pub fn title(mut self, text : &String) -> DocumentQuery {
+        self.title_ = Some(text.clone());
+        return self;
+    }
+
+    pub fn sort_by(mut self, sort : &DocumentSort, direction : &SortDirection) -> DocumentQuery {
+        self.sort_ = *sort;
+        self.direction_ = *direction;
+        return self;
+    }
+
+    pub fn limit(mut self, count : &i64) -> DocumentQuery {
+        self.limit_ = Some(*count);
+        return self;
+    }
+
+    pub fn offset(mut self, count : &i64) -> DocumentQuery {
+        self.offset_ = Some(*count);
+        return self;
+    }
+
+    /// Starts after the given document, which should be the last one of the previous page with the same sort.
+    pub fn after(mut self, last_document : &Document) -> DocumentQuery {
+        let key = match self.sort_ {
+            DocumentSort::Id => Some(last_document.id_.to_string()),
+            DocumentSort::Name => Some(last_document.name_.clone()),
+            DocumentSort::Date => last_document.date_.map(|document_date| document_date.to_string())
+        };
+        self.after_ = Some(DocumentCursor { id_ : last_document.id_, key_ : key });
+        return self;
+    }
+}
+
+fn escape_like(text : &str) -> String {
+    return text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+}
+
+pub fn get_documents_matching(connection : &mut SqliteConnection, query : &DocumentQuery) -> Result<Vec<Document>, DbError> {
+    use crate::db_manager::entities::schema::document_contributor::dsl::{document_contributor, document as dc_document, author as dc_author};
+    use crate::db_manager::entities::schema::document_category::dsl::{document_category, document as dca_document, category as dca_category};
+    use crate::db_manager::entities::schema::document_tag::dsl::{document_tag, document as dt_document, tag as dt_tag};
+    use crate::db_manager::entities::schema::document_genre::dsl::{document_genre, document as dg_document, genre as dg_genre};
+    let mut documents = document.filter(deleted_at.is_null()).into_boxed::<Sqlite>();
+    if let Some(author_id) = query.author_ {
+        documents = documents.filter(id.eq_any(document_contributor.filter(dc_author.eq(author_id)).select(dc_document)));
+    }
+    if let Some(series_id) = query.series_ {
+        documents = documents.filter(series.eq(series_id));
+    }
+    if let Some(category_id) = query.category_ {
+        documents = documents.filter(category.eq(category_id).or(id.eq_any(document_category.filter(dca_category.eq(category_id)).select(dca_document))));
+    }
+    if !query.tags_.is_empty() {
+        documents = match query.tag_match_ {
+            MatchMode::Any => documents.filter(id.eq_any(document_tag.filter(dt_tag.eq_any(query.tags_.clone())).select(dt_document))),
+            MatchMode::All => query.tags_.iter().fold(documents, |documents, tag_id| documents.filter(id.eq_any(document_tag.filter(dt_tag.eq(*tag_id)).select(dt_document))))
+        };
+    }
+    if !query.genres_.is_empty() {
+        documents = match query.genre_match_ {
+            MatchMode::Any => documents.filter(id.eq_any(document_genre.filter(dg_genre.eq_any(query.genres_.clone())).select(dg_document))),
+            MatchMode::All => query.genres_.iter().fold(documents, |documents, genre_id| documents.filter(id.eq_any(document_genre.filter(dg_genre.eq(*genre_id)).select(dg_document))))
+        };
+    }
+    // partial dates sort before the days they contain, so "1954" <= "1954-06-29" as text
+    if let Some(from) = query.date_from_ {
+        let from_month = format!("{:04}-{:02}", from.year(), from.month());
+        let from_year = format!("{:04}", from.year());
+        documents = documents.filter(date.ge(from.to_string()).or(date.eq(from_month)).or(date.eq(from_year)));
+    }
+    if let Some(to) = query.date_to_ {
+        documents = documents.filter(date.le(to.to_string()));
+    }
+    if let Some(text) = &query.title_ {
+        documents = documents.filter(name.like(format!("%{}%", escape_like(text))).escape('\\'));
+    }
+    // ties on the sort key are broken by id, which the cursor relies on.
+    // Documents without date come first in ascending order and last in descending order.
+    if let Some(cursor) = &query.after_ {
+        let key = cursor.key_.clone().unwrap_or_default();
+        documents = match (query.sort_, query.direction_, &cursor.key_) {
+            (DocumentSort::Id, SortDirection::Ascending, _) => documents.filter(id.gt(cursor.id_)),
+            (DocumentSort::Id, SortDirection::Descending, _) => documents.filter(id.lt(cursor.id_)),
+            (DocumentSort::Name, SortDirection::Ascending, _) => documents.filter(name.gt(key.clone()).or(name.eq(key).and(id.gt(cursor.id_)))),
+            (DocumentSort::Name, SortDirection::Descending, _) => documents.filter(name.lt(key.clone()).or(name.eq(key).and(id.lt(cursor.id_)))),
+            (DocumentSort::Date, SortDirection::Ascending, Some(_)) => documents.filter(date.gt(key.clone()).or(date.eq(key).and(id.gt(cursor.id_)))),
+            (DocumentSort::Date, SortDirection::Ascending, None) => documents.filter(date.is_null().and(id.gt(cursor.id_)).or(date.is_not_null())),
+            (DocumentSort::Date, SortDirection::Descending, Some(_)) => documents.filter(date.lt(key.clone()).or(date.eq(key).and(id.lt(cursor.id_))).or(date.is_null())),
+            (DocumentSort::Date, SortDirection::Descending, None) => documents.filter(date.is_null().and(id.lt(cursor.id_)))
+        };
+    }
+    documents = match (query.sort_, query.direction_) {
+        (DocumentSort::Id, SortDirection::Ascending) => documents.order(id.asc()),
+        (DocumentSort::Id, SortDirection::Descending) => documents.order(id.desc()),
+        (DocumentSort::Name, SortDirection::Ascending) => documents.order((name.asc(), id.asc())),
+        (DocumentSort::Name, SortDirection::Descending) => documents.order((name.desc(), id.desc())),
+        (DocumentSort::Date, SortDirection::Ascending) => documents.order((date.asc(), id.asc())),
+        (DocumentSort::Date, SortDirection::Descending) => documents.order((date.desc(), id.desc()))
+    };
+    if let Some(count) = query.limit_ {
+        documents = documents.limit(count);
+    }
+    if let Some(count) = query.offset_ {
+        // SQLite needs a limit to accept an offset
+        if query.limit_.is_none() {
+            documents = documents.limit(-1);
+        }
+        documents = documents.offset(count);
+    }
+    return documents.load::<Document>(connection)
+        .map_err(|e| DbError::from_diesel("An error occured while querying documents".to_string(), e));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use chrono::NaiveDate;
+
+    use crate::db_manager::{create_database, delete_database, entities::{add_author, add_category, add_document, add_genre, add_series, add_tag, link_category_to_document, link_contributor_to_document, link_genre_to_document, link_tag_to_document, ContributorRole, Document}, get_connection, PartialDate};
+
+    use super::{get_documents_matching, DocumentQuery, DocumentSort, MatchMode, SortDirection};
+
+    #[test]
+    fn querying_documents_should_combine_filters() {
+        let test_db_path = Path::new("./querying_documents_should_combine_filters.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let books = add_category(&mut connection, &"Books".to_string(), &"~/Documents/Books".to_string()).unwrap();
+        let favourites = add_category(&mut connection, &"Favourites".to_string(), &"~/Documents/Favourites".to_string()).unwrap();
+        let jrr_tolkien = add_author(&mut connection, &"J.R.R Tolkien".to_string()).unwrap();
+        let alan_lee = add_author(&mut connection, &"Alan Lee".to_string()).unwrap();
+        let the_lord_of_the_rings = add_series(&mut connection, &"The Lord Of The Rings".to_string(), &jrr_tolkien.id_).unwrap();
+        let heroic_fantasy = add_genre(&mut connection, &"Heroic Fantasy".to_string()).unwrap();
+        let favorites = add_tag(&mut connection, &"favorites".to_string()).unwrap();
+        let to_read = add_tag(&mut connection, &"to read".to_string()).unwrap();
+        let the_hobbit = add_document(&mut connection, &"The Hobbit".to_string(), &books.id_, &Some(jrr_tolkien.id_), &None, &Some(NaiveDate::from_ymd_opt(1937, 9, 21).unwrap().into()), &"Tolkien/the_hobbit.epub".to_string()).unwrap();
+        let the_fellowship_of_the_ring = add_document(&mut connection, &"The Fellowship Of The Ring".to_string(), &books.id_, &Some(jrr_tolkien.id_), &Some(the_lord_of_the_rings.id_), &Some(NaiveDate::from_ymd_opt(1954, 6, 29).unwrap().into()), &"Tolkien/the_fellowship_of_the_ring.epub".to_string()).unwrap();
+        let the_two_towers = add_document(&mut connection, &"The Two Towers".to_string(), &books.id_, &Some(jrr_tolkien.id_), &Some(the_lord_of_the_rings.id_), &Some(NaiveDate::from_ymd_opt(1954, 11, 11).unwrap().into()), &"Tolkien/the_two_towers.epub".to_string()).unwrap();
+        let a_game_of_thrones = add_document(&mut connection, &"A Game Of Thrones".to_string(), &favourites.id_, &None, &None, &Some(NaiveDate::from_ymd_opt(1996, 8, 6).unwrap().into()), &"Martin/A_game_of_thrones.epub".to_string()).unwrap();
+        link_contributor_to_document(&mut connection, &the_two_towers.id_, &alan_lee.id_, &ContributorRole::Illustrator).unwrap();
+        link_category_to_document(&mut connection, &the_hobbit.id_, &favourites.id_).unwrap();
+        for tolkien_document in [&the_hobbit, &the_fellowship_of_the_ring, &the_two_towers] {
+            link_genre_to_document(&mut connection, &tolkien_document.id_, &heroic_fantasy.id_).unwrap();
+        }
+        link_tag_to_document(&mut connection, &the_hobbit.id_, &favorites.id_).unwrap();
+        link_tag_to_document(&mut connection, &the_hobbit.id_, &to_read.id_).unwrap();
+        link_tag_to_document(&mut connection, &the_two_towers.id_, &to_read.id_).unwrap();
+        link_tag_to_document(&mut connection, &a_game_of_thrones.id_, &favorites.id_).unwrap();
+
+        let query = DocumentQuery::new().author(&alan_lee.id_);
+        assert_eq!(vec![the_two_towers.clone()], get_documents_matching(&mut connection, &query).unwrap());
+        let query = DocumentQuery::new().series(&the_lord_of_the_rings.id_).dated_between(&NaiveDate::from_ymd_opt(1954, 7, 1), &None);
+        assert_eq!(vec![the_two_towers.clone()], get_documents_matching(&mut connection, &query).unwrap());
+        let query = DocumentQuery::new().category(&favourites.id_).sort_by(&DocumentSort::Name, &SortDirection::Ascending);
+        assert_eq!(vec![a_game_of_thrones.clone(), the_hobbit.clone()], get_documents_matching(&mut connection, &query).unwrap());
+        let query = DocumentQuery::new().tags(&[favorites.id_, to_read.id_], &MatchMode::Any);
+        assert_eq!(vec![the_hobbit.clone(), the_two_towers.clone(), a_game_of_thrones.clone()], get_documents_matching(&mut connection, &query).unwrap());
+        let query = DocumentQuery::new().tags(&[favorites.id_, to_read.id_], &MatchMode::All);
+        assert_eq!(vec![the_hobbit.clone()], get_documents_matching(&mut connection, &query).unwrap());
+        let query = DocumentQuery::new().genres(&[heroic_fantasy.id_], &MatchMode::All).tags(&[to_read.id_], &MatchMode::Any).title(&"towers".to_string());
+        assert_eq!(vec![the_two_towers.clone()], get_documents_matching(&mut connection, &query).unwrap());
+        let query = DocumentQuery::new().title(&"%".to_string());
+        assert_eq!(Vec::<Document>::new(), get_documents_matching(&mut connection, &query).unwrap());
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn querying_documents_should_paginate_with_offset_or_cursor() {
+        let test_db_path = Path::new("./querying_documents_should_paginate_with_offset_or_cursor.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let mangas = add_category(&mut connection, &"Mangas".to_string(), &"~/Documents/Mangas".to_string()).unwrap();
+        let mut chapters = Vec::new();
+        for (chapter, release_date) in [(1003, NaiveDate::from_ymd_opt(2021, 2, 7)), (1001, NaiveDate::from_ymd_opt(2021, 1, 18)), (1002, NaiveDate::from_ymd_opt(2021, 1, 24)), (1000, NaiveDate::from_ymd_opt(2021, 1, 4))] {
+            chapters.push(add_document(&mut connection, &format!("Chapter {}", chapter), &mangas.id_, &None, &None, &Some(release_date.unwrap().into()), &format!("Oda/One piece/chapter-{}.cbz", chapter)).unwrap());
+        }
+        let by_date = DocumentQuery::new().sort_by(&DocumentSort::Date, &SortDirection::Descending).limit(&2);
+        let first_page = get_documents_matching(&mut connection, &by_date).unwrap();
+        assert_eq!(vec![chapters[0].clone(), chapters[2].clone()], first_page);
+        let second_page = get_documents_matching(&mut connection, &by_date.clone().after(first_page.last().unwrap())).unwrap();
+        assert_eq!(vec![chapters[1].clone(), chapters[3].clone()], second_page);
+        assert_eq!(second_page, get_documents_matching(&mut connection, &by_date.clone().offset(&2)).unwrap());
+        let by_name = DocumentQuery::new().sort_by(&DocumentSort::Name, &SortDirection::Ascending).offset(&3);
+        assert_eq!(vec![chapters[0].clone()], get_documents_matching(&mut connection, &by_name).unwrap());
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn querying_documents_should_filter_and_sort_partial_dates() {
+        let test_db_path = Path::new("./querying_documents_should_filter_and_sort_partial_dates.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let books = add_category(&mut connection, &"Books".to_string(), &"~/Documents/Books".to_string()).unwrap();
+        let the_hobbit = add_document(&mut connection, &"The Hobbit".to_string(), &books.id_, &None, &None, &Some(NaiveDate::from_ymd_opt(1937, 9, 21).unwrap().into()), &"Tolkien/the_hobbit.epub".to_string()).unwrap();
+        let the_fellowship_of_the_ring = add_document(&mut connection, &"The Fellowship Of The Ring".to_string(), &books.id_, &None, &None, &Some(PartialDate::year_month(1954, 6).unwrap()), &"Tolkien/the_fellowship_of_the_ring.epub".to_string()).unwrap();
+        let the_two_towers = add_document(&mut connection, &"The Two Towers".to_string(), &books.id_, &None, &None, &Some(PartialDate::year(1954).unwrap()), &"Tolkien/the_two_towers.epub".to_string()).unwrap();
+        let personal_notes = add_document(&mut connection, &"Notes".to_string(), &books.id_, &None, &None, &None, &"notes.pdf".to_string()).unwrap();
+        assert_eq!(Some(PartialDate::year_month(1954, 6).unwrap()), the_fellowship_of_the_ring.date_);
+
+        let june_1954 = DocumentQuery::new().dated_between(&NaiveDate::from_ymd_opt(1954, 6, 15), &NaiveDate::from_ymd_opt(1954, 6, 20));
+        assert_eq!(vec![the_fellowship_of_the_ring.clone(), the_two_towers.clone()], get_documents_matching(&mut connection, &june_1954).unwrap());
+        let after_june_1954 = DocumentQuery::new().dated_between(&NaiveDate::from_ymd_opt(1954, 7, 1), &None);
+        assert_eq!(vec![the_two_towers.clone()], get_documents_matching(&mut connection, &after_june_1954).unwrap());
+        let before_1954 = DocumentQuery::new().dated_between(&None, &NaiveDate::from_ymd_opt(1953, 12, 31));
+        assert_eq!(vec![the_hobbit.clone()], get_documents_matching(&mut connection, &before_1954).unwrap());
+
+        for direction in [SortDirection::Ascending, SortDirection::Descending] {
+            let by_date = DocumentQuery::new().sort_by(&DocumentSort::Date, &direction).limit(&1);
+            let mut pages = Vec::new();
+            let mut page = get_documents_matching(&mut connection, &by_date).unwrap();
+            while let Some(last_document) = page.last().cloned() {
+                pages.push(last_document.clone());
+                page = get_documents_matching(&mut connection, &by_date.clone().after(&last_document)).unwrap();
+            }
+            let mut expected = vec![personal_notes.clone(), the_hobbit.clone(), the_two_towers.clone(), the_fellowship_of_the_ring.clone()];
+            if direction == SortDirection::Descending {
+                expected.reverse();
+            }
+            assert_eq!(expected, pages);
+        }
+        delete_database(test_db_path).unwrap();
+    }
+}
@@ -1,6 +1,6 @@
 use derive_new::new;
-use diesel::{dsl::delete, insert_into, prelude::Queryable, query_dsl::methods::FilterDsl, ExpressionMethods, RunQueryDsl, SqliteConnection};
-use crate::db_manager::entities::schema::author::dsl::*;
+use diesel::{dsl::{delete, insert_or_ignore_into, update}, insert_into, prelude::Queryable, Connection, ExpressionMethods, QueryDsl, RunQueryDsl, SqliteConnection};
+use crate::db_manager::{entities::{schema::author::dsl::*, ContributorRole}, DbError};
 
 #[derive(Queryable, PartialEq, Debug, new, Clone)]
 pub struct Author {
@@ -8,51 +8,84 @@ pub struct Author {
     pub name_ : String
 }
 
-pub fn add_author(connection : &mut SqliteConnection, author_name : &String) -> Result<Author, String> {
-    let added_rows = insert_into(author)
+pub fn add_author(connection : &mut SqliteConnection, author_name : &String) -> Result<Author, DbError> {
+    return insert_into(author)
         .values(name.eq(author_name))
-        .execute(connection)
-        .map_err(|e| format!("Could not add author {} to database : {}", author_name, e))?;
-    match added_rows {
-        1 => get_author_by_name(connection, author_name)
-                .ok_or(format!("Could not find newly created author {} in database", author_name)),
-        _ => Err(format!("Something wrong happened while adding author {} in database", author_name))
-    }
+        .get_result::<Author>(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not add author {} to database", author_name), e));
+}
+
+/// Gives the author with the given name, creating it if it does not exist yet.
+pub fn get_or_create_author(connection : &mut SqliteConnection, author_name : &String) -> Result<Author, DbError> {
+    return match add_author(connection, author_name) {
+        Err(DbError::Conflict(_)) => get_author_by_name(connection, author_name),
+        added => added
+    };
 }
 
-pub fn remove_author(connection : &mut SqliteConnection, author_id : &i32) -> Result<(), String> {
+pub fn remove_author(connection : &mut SqliteConnection, author_id : &i32) -> Result<(), DbError> {
     let deleted_rows = delete(author).filter(id.eq(&author_id)).execute(connection)
-        .map_err(|e| format!("Could not delete author {} from database : {}", &author_id, e))?;
+        .map_err(|e| DbError::from_diesel(format!("Could not delete author {} from database", &author_id), e))?;
     match deleted_rows {
         1 => Ok(()),
-        _ => Err(format!("Something wrong happened while deleting author {} from database", &author_id))
+        _ => Err(DbError::NotFound(format!("Could not find author {} to delete from database", &author_id)))
     }
 }
 
-pub fn get_authors(connection : &mut SqliteConnection) -> Result<Vec<Author>, String> {
+pub fn get_authors(connection : &mut SqliteConnection) -> Result<Vec<Author>, DbError> {
     return author.load::<Author>(connection)
-    .map_err(|e| format!("An errror occured while getting all authors : {}", e));
+    .map_err(|e| DbError::from_diesel("An errror occured while getting all authors".to_string(), e));
 }
 
-pub fn get_author_by_id(connection : &mut SqliteConnection, author_id : &i32) -> Option<Author> {
-    match author.filter(id.eq(author_id)).first::<Author>(connection) {
-        Ok(a) => Some(a),
-        Err(_) => None
-    }
+pub fn get_author_by_id(connection : &mut SqliteConnection, author_id : &i32) -> Result<Author, DbError> {
+    return author.filter(id.eq(author_id)).first::<Author>(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not get author {}", author_id), e));
+}
+
+pub fn get_author_by_name(connection : &mut SqliteConnection, author_name : &String) -> Result<Author, DbError> {
+    return author.filter(name.eq(author_name)).first::<Author>(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not get author {}", author_name), e));
 }
 
-pub fn get_author_by_name(connection : &mut SqliteConnection, author_name : &String) -> Option<Author> {
-    match author.filter(name.eq(author_name)).first::<Author>(connection) {
-        Ok(a) => Some(a),
-        Err(_) => None
+pub fn rename_author(connection : &mut SqliteConnection, author_id : &i32, new_name : &String) -> Result<Author, DbError> {
+    return update(author.filter(id.eq(author_id)))
+        .set(name.eq(new_name))
+        .get_result::<Author>(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not rename author {} to {}", author_id, new_name), e));
+}
+
+/// Gives the documents, contributions and series of the source author to the target one, then deletes the source.
+pub fn merge_authors(connection : &mut SqliteConnection, source_id : &i32, target_id : &i32) -> Result<Author, DbError> {
+    use crate::db_manager::entities::schema::document_contributor::dsl::{document_contributor, document as dc_document, author as dc_author, role as dc_role};
+    use crate::db_manager::entities::schema::document::dsl::{document, author as document_author};
+    use crate::db_manager::entities::schema::series::dsl::{series, author as series_author};
+    if source_id == target_id {
+        return Err(DbError::Conflict(format!("Could not merge author {} into itself", source_id)));
     }
+    return connection.transaction::<Author, DbError, _>(|connection| {
+        get_author_by_id(connection, source_id)?;
+        get_author_by_id(connection, target_id)?;
+        let contributions = document_contributor.filter(dc_author.eq(source_id)).select((dc_document, dc_role)).load::<(i32, ContributorRole)>(connection)
+            .map_err(|e| DbError::from_diesel(format!("Could not get contributions of author {}", source_id), e))?;
+        insert_or_ignore_into(document_contributor)
+            .values(contributions.iter().map(|(document_id, contributor_role)| (dc_document.eq(document_id), dc_author.eq(target_id), dc_role.eq(contributor_role))).collect::<Vec<_>>())
+            .execute(connection)
+            .map_err(|e| DbError::from_diesel(format!("Could not move contributions of author {} to author {}", source_id, target_id), e))?;
+        update(document.filter(document_author.eq(source_id))).set(document_author.eq(target_id)).execute(connection)
+            .map_err(|e| DbError::from_diesel(format!("Could not move documents of author {} to author {}", source_id, target_id), e))?;
+        update(series.filter(series_author.eq(source_id))).set(series_author.eq(target_id)).execute(connection)
+            .map_err(|e| DbError::from_diesel(format!("Could not move series of author {} to author {}", source_id, target_id), e))?;
+        remove_author(connection, source_id)?;
+        get_author_by_id(connection, target_id)
+    });
 }
 
 #[cfg(test)]
 mod tests {
     use std::path::Path;
+    use chrono::NaiveDate;
 
-    use crate::db_manager::{create_database, delete_database, entities::{add_author, get_author_by_id, get_authors, remove_author, Author}, get_connection};
+    use crate::db_manager::{create_database, delete_database, entities::{add_author, add_category, add_document, add_series, get_author_by_id, get_authors, get_document_by_id, get_document_contributors, get_or_create_author, get_series_by_id, link_contributor_to_document, merge_authors, remove_author, rename_author, Author, ContributorRole, DocumentContributor}, get_connection, DbError};
 
     #[test]
     fn adding_author_should_give_newly_created_author() {
@@ -96,7 +129,86 @@ mod tests {
         let mut connection = get_connection(test_db_path).unwrap();
         let rr_martin = add_author(&mut connection, &"George R.R Martin".to_string()).unwrap();
         remove_author(&mut connection, &rr_martin.id_).unwrap();
-        assert!(get_author_by_id(&mut connection, &rr_martin.id_).is_none());
+        assert!(matches!(get_author_by_id(&mut connection, &rr_martin.id_), Err(DbError::NotFound(_))));
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn remove_author_should_unset_it_on_documents() {
+        let test_db_path = Path::new("./remove_author_should_unset_it_on_documents.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let books = add_category(&mut connection, &"Books".to_string(), &"~/Documents/Books".to_string()).unwrap();
+        let jrr_tolkien = add_author(&mut connection, &"J.R.R Tolkien".to_string()).unwrap();
+        let the_hobbit = add_document(&mut connection, &"The Hobbit".to_string(), &books.id_, &Some(jrr_tolkien.id_), &None, &Some(NaiveDate::from_ymd_opt(1937, 9, 21).unwrap().into()), &"Tolkien/the_hobbit.epub".to_string()).unwrap();
+        remove_author(&mut connection, &jrr_tolkien.id_).unwrap();
+        assert_eq!(None, get_document_by_id(&mut connection, &the_hobbit.id_).unwrap().author_);
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn remove_author_with_series_should_be_refused() {
+        let test_db_path = Path::new("./remove_author_with_series_should_be_refused.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let jrr_tolkien = add_author(&mut connection, &"J.R.R Tolkien".to_string()).unwrap();
+        add_series(&mut connection, &"The Lord Of The Rings".to_string(), &jrr_tolkien.id_).unwrap();
+        assert!(matches!(remove_author(&mut connection, &jrr_tolkien.id_), Err(DbError::ForeignKey(_))));
+        assert_eq!(jrr_tolkien, get_author_by_id(&mut connection, &jrr_tolkien.id_).unwrap());
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn adding_author_twice_should_be_refused() {
+        let test_db_path = Path::new("./adding_author_twice_should_be_refused.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        add_author(&mut connection, &"J.R.R Tolkien".to_string()).unwrap();
+        assert!(matches!(add_author(&mut connection, &"J.R.R Tolkien".to_string()), Err(DbError::Conflict(_))));
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn get_or_create_author_should_reuse_existing_author() {
+        let test_db_path = Path::new("./get_or_create_author_should_reuse_existing_author.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let jrr_tolkien = add_author(&mut connection, &"J.R.R Tolkien".to_string()).unwrap();
+        let rr_martin = get_or_create_author(&mut connection, &"George R.R Martin".to_string()).unwrap();
+        assert_eq!(jrr_tolkien, get_or_create_author(&mut connection, &"J.R.R Tolkien".to_string()).unwrap());
+        assert_eq!(vec![jrr_tolkien, rr_martin], get_authors(&mut connection).unwrap());
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn merging_authors_should_move_documents_series_and_contributions() {
+        let test_db_path = Path::new("./merging_authors_should_move_documents_series_and_contributions.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let books = add_category(&mut connection, &"Books".to_string(), &"~/Documents/Books".to_string()).unwrap();
+        let jrr_tolkien = add_author(&mut connection, &"J.R.R Tolkien".to_string()).unwrap();
+        let jrr_tolkien_typo = add_author(&mut connection, &"JRR Tolkien".to_string()).unwrap();
+        let the_lord_of_the_rings = add_series(&mut connection, &"The Lord Of The Rings".to_string(), &jrr_tolkien_typo.id_).unwrap();
+        let the_hobbit = add_document(&mut connection, &"The Hobbit".to_string(), &books.id_, &Some(jrr_tolkien_typo.id_), &None, &Some(NaiveDate::from_ymd_opt(1937, 9, 21).unwrap().into()), &"Tolkien/the_hobbit.epub".to_string()).unwrap();
+        link_contributor_to_document(&mut connection, &the_hobbit.id_, &jrr_tolkien.id_, &ContributorRole::Author).unwrap();
+        link_contributor_to_document(&mut connection, &the_hobbit.id_, &jrr_tolkien_typo.id_, &ContributorRole::Illustrator).unwrap();
+        merge_authors(&mut connection, &jrr_tolkien_typo.id_, &jrr_tolkien.id_).unwrap();
+        assert_eq!(Some(jrr_tolkien.id_), get_document_by_id(&mut connection, &the_hobbit.id_).unwrap().author_);
+        assert_eq!(Some(jrr_tolkien.id_), get_series_by_id(&mut connection, &the_lord_of_the_rings.id_).unwrap().author_);
+        assert_eq!(vec![DocumentContributor::new(the_hobbit.id_, jrr_tolkien.id_, ContributorRole::Author), DocumentContributor::new(the_hobbit.id_, jrr_tolkien.id_, ContributorRole::Illustrator)], get_document_contributors(&mut connection, &the_hobbit.id_).unwrap());
+        assert_eq!(vec![jrr_tolkien], get_authors(&mut connection).unwrap());
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn renaming_author_should_give_renamed_author() {
+        let test_db_path = Path::new("./renaming_author_should_give_renamed_author.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let jrr_tolkien = add_author(&mut connection, &"JRR Tolkien".to_string()).unwrap();
+        let renamed_author = rename_author(&mut connection, &jrr_tolkien.id_, &"J.R.R Tolkien".to_string()).unwrap();
+        assert_eq!(Author::new(jrr_tolkien.id_, "J.R.R Tolkien".to_string()), renamed_author);
+        assert!(matches!(rename_author(&mut connection, &(jrr_tolkien.id_ + 1), &"R.R Martin".to_string()), Err(DbError::NotFound(_))));
         delete_database(test_db_path).unwrap();
     }
 }
\ No newline at end of file
@@ -1,59 +1,84 @@
+use std::{env, path::PathBuf};
 use derive_new::new;
-use diesel::{dsl::delete, insert_into, prelude::Queryable, query_dsl::methods::FilterDsl, ExpressionMethods, RunQueryDsl, SqliteConnection};
-use crate::db_manager::entities::schema::category::dsl::*;
+use diesel::{dsl::{delete, update}, insert_into, prelude::Queryable, query_dsl::methods::FilterDsl, ExpressionMethods, RunQueryDsl, SqliteConnection};
+use crate::db_manager::{entities::schema::category::dsl::*, DbError};
 
 #[derive(Queryable, PartialEq, Debug, new, Clone)]
 pub struct Category {
     pub id_ : i32,
     pub name_ : String,
-    pub path_ : String
+    pub path_ : String,
+    /// How document files are laid out under the root, see `PathTemplate`
+    pub path_template_ : Option<String>
 }
 
-pub fn add_category(connection : &mut SqliteConnection, category_name : &String, category_path : &String) -> Result<Category, String> {
-    let added_rows = insert_into(category)
-        .values((name.eq(category_name), path.eq(category_path)))
-        .execute(connection)
-        .map_err(|e| format!("Could not add category {} to database : {}", category_name, e))?;
-    match added_rows {
-        1 => get_category_by_name(connection, category_name)
-                .ok_or(format!("Could not find newly created author {} in database", category_name)),
-        _ => Err(format!("Something wrong happened while adding author {} in database", category_name))
+impl Category {
+    /// Folder the category documents are stored in, with a leading "~" replaced by the home folder.
+    /// The path is canonicalized when it exists, it is only expanded otherwise.
+    pub fn root_path(&self) -> PathBuf {
+        let expanded_path = expand_home(&self.path_);
+        return expanded_path.canonicalize().unwrap_or(expanded_path);
     }
 }
 
-pub fn remove_category(connection : &mut SqliteConnection, category_id : &i32) -> Result<(), String> {
+fn expand_home(category_path : &str) -> PathBuf {
+    let home = env::var_os("HOME");
+    return match (category_path.strip_prefix('~'), home) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => PathBuf::from(home).join(rest.trim_start_matches('/')),
+        _ => PathBuf::from(category_path)
+    };
+}
+
+pub fn add_category(connection : &mut SqliteConnection, category_name : &String, category_path : &String) -> Result<Category, DbError> {
+    return insert_into(category)
+        .values((name.eq(category_name), path.eq(category_path)))
+        .get_result::<Category>(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not add category {} to database", category_name), e));
+}
+
+pub fn remove_category(connection : &mut SqliteConnection, category_id : &i32) -> Result<(), DbError> {
     let deleted_rows = delete(category).filter(id.eq(category_id)).execute(connection)
-        .map_err(|e| format!("Could not delete author {} from database : {}", category_id, e))?;
+        .map_err(|e| DbError::from_diesel(format!("Could not delete category {} from database", category_id), e))?;
     match deleted_rows {
         1 => Ok(()),
-        _ => Err(format!("Something wrong happened while deleting author {} from database", category_id))
+        _ => Err(DbError::NotFound(format!("Could not find category {} to delete from database", category_id)))
     }
 }
 
-pub fn get_categories(connection : &mut SqliteConnection) -> Result<Vec<Category>, String> {
+/// Moves the category root, documents follow since their paths are relative to it.
+pub fn update_category_path(connection : &mut SqliteConnection, category_id : &i32, category_path : &String) -> Result<Category, DbError> {
+    return update(category.filter(id.eq(category_id))).set(path.eq(category_path))
+        .get_result::<Category>(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not move category {} to {}", category_id, category_path), e));
+}
+
+pub fn set_category_path_template(connection : &mut SqliteConnection, category_id : &i32, category_path_template : &Option<String>) -> Result<Category, DbError> {
+    return update(category.filter(id.eq(category_id))).set(path_template.eq(category_path_template))
+        .get_result::<Category>(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not set path template of category {}", category_id), e));
+}
+
+pub fn get_categories(connection : &mut SqliteConnection) -> Result<Vec<Category>, DbError> {
     return category.load::<Category>(connection)
-    .map_err(|e| format!("An errror occured while getting all categories : {}", e));
+    .map_err(|e| DbError::from_diesel("An errror occured while getting all categories".to_string(), e));
 }
 
-pub fn get_category_by_name(connection : &mut SqliteConnection, category_name : &String) -> Option<Category> {
-    match category.filter(name.eq(category_name)).first::<Category>(connection) {
-        Ok(c) => Some(c),
-        Err(_) => None
-    }
+pub fn get_category_by_name(connection : &mut SqliteConnection, category_name : &String) -> Result<Category, DbError> {
+    return category.filter(name.eq(category_name)).first::<Category>(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not get category {}", category_name), e));
 }
 
-pub fn get_category_by_id(connection : &mut SqliteConnection, category_id : &i32) -> Option<Category> {
-    match category.filter(id.eq(category_id)).first::<Category>(connection) {
-        Ok(c) => Some(c),
-        Err(_) => None
-    }
+pub fn get_category_by_id(connection : &mut SqliteConnection, category_id : &i32) -> Result<Category, DbError> {
+    return category.filter(id.eq(category_id)).first::<Category>(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not get category {}", category_id), e));
 }
 
 #[cfg(test)]
 mod tests {
-    use std::path::Path;
+    use std::{env, fs::{create_dir, remove_dir}, path::{Path, PathBuf}};
+    use chrono::NaiveDate;
 
-    use crate::db_manager::{create_database, delete_database, entities::{add_category, get_categories, get_category_by_id, remove_category, Category}, get_connection};
+    use crate::db_manager::{create_database, delete_database, entities::{add_category, add_document, get_categories, get_category_by_id, remove_category, update_category_path, Category}, get_connection, DbError};
 
     #[test]
     fn adding_category_should_give_newly_created_category() {
@@ -61,7 +86,7 @@ mod tests {
         create_database(test_db_path).unwrap();
         let mut connection = get_connection(test_db_path).unwrap();
         let maybe_added_category = add_category(&mut connection, &"Books".to_string(), &"~/Documents/Books".to_string()).unwrap();
-        assert_eq!(Category::new(1, "Books".to_string(), "~/Documents/Books".to_string()), maybe_added_category);
+        assert_eq!(Category::new(1, "Books".to_string(), "~/Documents/Books".to_string(), None), maybe_added_category);
         delete_database(test_db_path).unwrap();
     }
 
@@ -97,7 +122,45 @@ mod tests {
         let mut connection = get_connection(test_db_path).unwrap();
         let books = add_category(&mut connection, &"Books".to_string(), &"~/Documents/Books".to_string()).unwrap();
         remove_category(&mut connection, &books.id_).unwrap();
-        assert!(get_category_by_id(&mut connection, &books.id_).is_none());
+        assert!(matches!(get_category_by_id(&mut connection, &books.id_), Err(DbError::NotFound(_))));
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn remove_category_with_documents_should_be_refused() {
+        let test_db_path = Path::new("./remove_category_with_documents_should_be_refused.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let books = add_category(&mut connection, &"Books".to_string(), &"~/Documents/Books".to_string()).unwrap();
+        add_document(&mut connection, &"The Hobbit".to_string(), &books.id_, &None, &None, &Some(NaiveDate::from_ymd_opt(1937, 9, 21).unwrap().into()), &"Tolkien/the_hobbit.epub".to_string()).unwrap();
+        assert!(matches!(remove_category(&mut connection, &books.id_), Err(DbError::ForeignKey(_))));
+        assert_eq!(books, get_category_by_id(&mut connection, &books.id_).unwrap());
         delete_database(test_db_path).unwrap();
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn category_root_path_should_expand_home_and_canonicalize() {
+        let home = PathBuf::from(env::var("HOME").unwrap());
+        assert_eq!(home.join("Documents/Reliure/Books"), Category::new(1, "Books".to_string(), "~/Documents/Reliure/Books".to_string(), None).root_path());
+        assert_eq!(PathBuf::from("~someone/Books"), Category::new(1, "Books".to_string(), "~someone/Books".to_string(), None).root_path());
+        let existing_root = Path::new("./category_root_path_should_expand_home_and_canonicalize");
+        create_dir(existing_root).unwrap();
+        let category = Category::new(1, "Books".to_string(), "./category_root_path_should_expand_home_and_canonicalize/../category_root_path_should_expand_home_and_canonicalize".to_string(), None);
+        assert_eq!(existing_root.canonicalize().unwrap(), category.root_path());
+        assert!(category.root_path().is_absolute());
+        remove_dir(existing_root).unwrap();
+    }
+
+    #[test]
+    fn updating_category_path_should_move_it() {
+        let test_db_path = Path::new("./updating_category_path_should_move_it.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let books = add_category(&mut connection, &"Books".to_string(), &"~/Documents/Books".to_string()).unwrap();
+        let moved_books = update_category_path(&mut connection, &books.id_, &"/mnt/library/Books".to_string()).unwrap();
+        assert_eq!(Category::new(books.id_, "Books".to_string(), "/mnt/library/Books".to_string(), None), moved_books);
+        assert_eq!(moved_books, get_category_by_id(&mut connection, &books.id_).unwrap());
+        assert!(matches!(update_category_path(&mut connection, &42, &"/mnt/library/Mangas".to_string()), Err(DbError::NotFound(_))));
+        delete_database(test_db_path).unwrap();
+    }
+}
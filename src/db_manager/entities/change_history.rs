@@ -0,0 +1,165 @@
+use std::{fmt, str::FromStr};
+use derive_new::new;
+use diesel::{backend::Backend, deserialize::{self, FromSql, FromSqlRow}, dsl::{delete, replace_into}, expression::AsExpression, prelude::Queryable, serialize::{self, IsNull, Output, ToSql}, sql_types::Text, sqlite::Sqlite, ExpressionMethods, QueryDsl, RunQueryDsl, SqliteConnection};
+use crate::db_manager::{entities::schema::change_history::dsl::*, DbError};
+
+/// Kind of row a change was recorded for.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Text)]
+pub enum ChangedEntity {
+    Document,
+    Author,
+    Series,
+    Tag,
+    Genre
+}
+
+impl ChangedEntity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChangedEntity::Document => "document",
+            ChangedEntity::Author => "author",
+            ChangedEntity::Series => "series",
+            ChangedEntity::Tag => "tag",
+            ChangedEntity::Genre => "genre"
+        }
+    }
+}
+
+impl fmt::Display for ChangedEntity {
+    fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for ChangedEntity {
+    type Err = String;
+
+    fn from_str(value : &str) -> Result<ChangedEntity, String> {
+        match value {
+            "document" => Ok(ChangedEntity::Document),
+            "author" => Ok(ChangedEntity::Author),
+            "series" => Ok(ChangedEntity::Series),
+            "tag" => Ok(ChangedEntity::Tag),
+            "genre" => Ok(ChangedEntity::Genre),
+            _ => Err(format!("Unknown changed entity {}", value))
+        }
+    }
+}
+
+impl ToSql<Text, Sqlite> for ChangedEntity {
+    fn to_sql<'b>(&'b self, out : &mut Output<'b, '_, Sqlite>) -> serialize::Result {
+        out.set_value(self.as_str());
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Text, Sqlite> for ChangedEntity {
+    fn from_sql(bytes : <Sqlite as Backend>::RawValue<'_>) -> deserialize::Result<ChangedEntity> {
+        let value = <String as FromSql<Text, Sqlite>>::from_sql(bytes)?;
+        return value.parse::<ChangedEntity>().map_err(|e| e.into());
+    }
+}
+
+/// One field change, written by the database triggers. Creation and deletion are recorded
+/// with the "created" and "deleted" fields, document links with the name of the linked table.
+#[derive(Queryable, PartialEq, Debug, new, Clone)]
+#[allow(clippy::too_many_arguments)]
+pub struct Change {
+    pub id_ : i32,
+    pub entity_ : ChangedEntity,
+    pub entity_id_ : i32,
+    pub field_ : String,
+    pub old_value_ : Option<String>,
+    pub new_value_ : Option<String>,
+    pub changed_by_ : Option<String>,
+    pub changed_at_ : String
+}
+
+/// Runs the work in a transaction where every recorded change is attributed to the user.
+/// Changes made outside of it are recorded without author.
+pub fn with_change_author<T, F>(connection : &mut SqliteConnection, user_name : &String, work : F) -> Result<T, DbError>
+where
+    F : FnOnce(&mut SqliteConnection) -> Result<T, DbError>
+{
+    use crate::db_manager::entities::schema::change_author::dsl::{change_author, id as author_row, username};
+    // immediate so no other connection can write while the author row is set
+    return connection.immediate_transaction::<T, DbError, _>(|connection| {
+        replace_into(change_author).values((author_row.eq(1), username.eq(user_name))).execute(connection)
+            .map_err(|e| DbError::from_diesel(format!("Could not set {} as change author", user_name), e))?;
+        let result = work(connection)?;
+        delete(change_author).execute(connection)
+            .map_err(|e| DbError::from_diesel(format!("Could not unset {} as change author", user_name), e))?;
+        Ok(result)
+    });
+}
+
+pub fn get_history(connection : &mut SqliteConnection, changed_entity : &ChangedEntity, changed_id : &i32) -> Result<Vec<Change>, DbError> {
+    return change_history.filter(entity.eq(changed_entity)).filter(entity_id.eq(changed_id)).order(id.asc()).load::<Change>(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not get history of {} {}", changed_entity, changed_id), e));
+}
+
+pub fn get_document_history(connection : &mut SqliteConnection, document_id : &i32) -> Result<Vec<Change>, DbError> {
+    return get_history(connection, &ChangedEntity::Document, document_id);
+}
+
+pub fn get_changes_by_user(connection : &mut SqliteConnection, user_name : &String) -> Result<Vec<Change>, DbError> {
+    return change_history.filter(changed_by.eq(user_name)).order(id.asc()).load::<Change>(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not get changes made by {}", user_name), e));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use chrono::NaiveDate;
+
+    use crate::db_manager::{create_database, delete_database, entities::{add_category, add_document, add_tag, get_changes_by_user, get_document_history, get_history, link_tag_to_document, rename_tag, unlink_tag_to_document, update_document_metadata, with_change_author, ChangedEntity, DocumentMetadata}, get_connection, set_document_fingerprint, DbError, FileFingerprint};
+
+    #[test]
+    fn document_changes_should_be_recorded_with_their_author() {
+        let test_db_path = Path::new("./document_changes_should_be_recorded_with_their_author.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let mangas = add_category(&mut connection, &"Mangas".to_string(), &"~/Documents/Mangas".to_string()).unwrap();
+        let one_piece_ch_1001 = add_document(&mut connection, &"Chapter 1001".to_string(), &mangas.id_, &None, &None, &Some(NaiveDate::from_ymd_opt(2021, 1, 18).unwrap().into()), &"Oda/One piece/chapter-1001.cbz".to_string()).unwrap();
+        let wano = add_tag(&mut connection, &"Wano".to_string()).unwrap();
+        with_change_author(&mut connection, &"nami".to_string(), |connection| {
+            link_tag_to_document(connection, &one_piece_ch_1001.id_, &wano.id_)?;
+            update_document_metadata(connection, &one_piece_ch_1001.id_, &DocumentMetadata { language_ : Some("ja".to_string()), right_to_left_ : Some(true), ..Default::default() })
+        }).unwrap();
+        unlink_tag_to_document(&mut connection, &one_piece_ch_1001.id_, &wano.id_).unwrap();
+        let fingerprint = FileFingerprint { hash_ : "9f86d081".to_string(), size_ : 1001, modified_at_ : "2021-01-18T00:00:00Z".to_string() };
+        set_document_fingerprint(&mut connection, &one_piece_ch_1001.id_, &fingerprint).unwrap();
+        let history = get_document_history(&mut connection, &one_piece_ch_1001.id_).unwrap()
+            .into_iter().map(|change| (change.field_, change.old_value_, change.new_value_, change.changed_by_)).collect::<Vec<_>>();
+        assert_eq!(vec![("created".to_string(), None, Some("Chapter 1001".to_string()), None),
+                        ("tag".to_string(), None, Some("Wano".to_string()), Some("nami".to_string())),
+                        ("language".to_string(), None, Some("ja".to_string()), Some("nami".to_string())),
+                        ("right_to_left".to_string(), None, Some("1".to_string()), Some("nami".to_string())),
+                        ("tag".to_string(), Some("Wano".to_string()), None, None),
+                        ("file_size".to_string(), None, Some("1001".to_string()), None),
+                        ("content_hash".to_string(), None, Some("9f86d081".to_string()), None),
+                        ("modified_at".to_string(), None, Some("2021-01-18T00:00:00Z".to_string()), None)], history);
+        assert_eq!(3, get_changes_by_user(&mut connection, &"nami".to_string()).unwrap().len());
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn failed_work_should_not_record_changes() {
+        let test_db_path = Path::new("./failed_work_should_not_record_changes.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let wano = add_tag(&mut connection, &"Wano".to_string()).unwrap();
+        add_tag(&mut connection, &"Egghead".to_string()).unwrap();
+        let renamed = with_change_author(&mut connection, &"nami".to_string(), |connection| {
+            rename_tag(connection, &wano.id_, &"Wano Country".to_string())?;
+            rename_tag(connection, &wano.id_, &"Egghead".to_string())
+        });
+        assert!(matches!(renamed, Err(DbError::Conflict(_))));
+        assert_eq!(1, get_history(&mut connection, &ChangedEntity::Tag, &wano.id_).unwrap().len());
+        rename_tag(&mut connection, &wano.id_, &"Wano Country".to_string()).unwrap();
+        let last_change = get_history(&mut connection, &ChangedEntity::Tag, &wano.id_).unwrap().pop().unwrap();
+        assert_eq!(("name".to_string(), Some("Wano".to_string()), Some("Wano Country".to_string()), None), (last_change.field_, last_change.old_value_, last_change.new_value_, last_change.changed_by_));
+        delete_database(test_db_path).unwrap();
+    }
+}
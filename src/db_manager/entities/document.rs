@@ -1,16 +1,109 @@
+use std::{fmt, path::{Path, PathBuf}, str::FromStr};
+use chrono::{Duration, SecondsFormat, Utc};
 use derive_new::new;
-use diesel::{dsl::delete, insert_into, prelude::Queryable, ExpressionMethods, JoinOnDsl, QueryDsl, RunQueryDsl, SqliteConnection, Table};
-use crate::db_manager::entities::{document_genre, schema::{self, document::dsl::*}};
+use diesel::{backend::Backend, deserialize::{self, FromSql, FromSqlRow}, dsl::{delete, update}, expression::AsExpression, insert_into, prelude::Queryable, serialize::{self, IsNull, Output, ToSql}, sql_types::Text, sqlite::Sqlite, BoolExpressionMethods, Connection, ExpressionMethods, QueryDsl, RunQueryDsl, SqliteConnection};
+use crate::db_manager::{entities::{get_category_by_id, get_genre_subtree, get_tag_subtree, link_contributor_to_document, schema::document::dsl::*, ContributorRole}, DbError, PartialDate};
 
 #[derive(Queryable, PartialEq, Debug, new, Clone)]
+#[allow(clippy::too_many_arguments)]
 pub struct Document {
     pub id_ : i32,
     pub name_ : String,
     pub category_ : i32,
     pub author_ : Option<i32>,
     pub series_ : Option<i32>,
-    pub date_ : String,
-    pub path_ : String
+    pub date_ : Option<PartialDate>,
+    pub path_ : String,
+    pub series_index_ : Option<f64>,
+    pub series_numbering_ : Option<SeriesNumbering>,
+    pub description_ : Option<String>,
+    pub language_ : Option<String>,
+    pub publisher_ : Option<String>,
+    pub page_count_ : Option<i32>,
+    pub file_size_ : Option<i64>,
+    pub mime_type_ : Option<String>,
+    pub sort_title_ : Option<String>,
+    pub deleted_at_ : Option<String>,
+    pub content_hash_ : Option<String>,
+    pub modified_at_ : Option<String>,
+    pub right_to_left_ : Option<bool>
+}
+
+impl Document {
+    pub fn metadata(&self) -> DocumentMetadata {
+        return DocumentMetadata::new(self.description_.clone(), 
+                                    self.language_.clone(), 
+                                    self.publisher_.clone(), 
+                                    self.page_count_, 
+                                    self.file_size_, 
+                                    self.mime_type_.clone(), 
+                                    self.sort_title_.clone(), 
+                                    self.right_to_left_);
+    }
+}
+
+/// Bibliographic fields of a document, all of them optional. File size is in bytes.
+#[derive(PartialEq, Debug, new, Clone, Default)]
+#[allow(clippy::too_many_arguments)]
+pub struct DocumentMetadata {
+    pub description_ : Option<String>,
+    pub language_ : Option<String>,
+    pub publisher_ : Option<String>,
+    pub page_count_ : Option<i32>,
+    pub file_size_ : Option<i64>,
+    pub mime_type_ : Option<String>,
+    pub sort_title_ : Option<String>,
+    /// Pages are read from right to left, like most manga
+    pub right_to_left_ : Option<bool>
+}
+
+/// Whether a series index counts volumes or chapters.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Text)]
+pub enum SeriesNumbering {
+    Volume,
+    Chapter
+}
+
+impl SeriesNumbering {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SeriesNumbering::Volume => "volume",
+            SeriesNumbering::Chapter => "chapter"
+        }
+    }
+}
+
+impl fmt::Display for SeriesNumbering {
+    fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for SeriesNumbering {
+    type Err = String;
+
+    fn from_str(value : &str) -> Result<SeriesNumbering, String> {
+        match value {
+            "volume" => Ok(SeriesNumbering::Volume),
+            "chapter" => Ok(SeriesNumbering::Chapter),
+            _ => Err(format!("Unknown series numbering {}", value))
+        }
+    }
+}
+
+impl ToSql<Text, Sqlite> for SeriesNumbering {
+    fn to_sql<'b>(&'b self, out : &mut Output<'b, '_, Sqlite>) -> serialize::Result {
+        out.set_value(self.as_str());
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Text, Sqlite> for SeriesNumbering {
+    fn from_sql(bytes : <Sqlite as Backend>::RawValue<'_>) -> deserialize::Result<SeriesNumbering> {
+        let value = <String as FromSql<Text, Sqlite>>::from_sql(bytes)?;
+        return value.parse::<SeriesNumbering>().map_err(|e| e.into());
+    }
 }
 
 pub fn add_document(connection : &mut SqliteConnection, 
@@ -18,99 +111,232 @@ pub fn add_document(connection : &mut SqliteConnection,
                     document_category : &i32, 
                     document_author : &Option<i32>, 
                     document_series : &Option<i32>,
-                    document_date : &String,
-                    document_path : &String) -> Result<Document, String> {
-    let added_rows = insert_into(document)
-        .values((name.eq(document_name), 
-                category.eq(document_category), 
-                author.eq(document_author), 
-                series.eq(document_series), 
-                date.eq(document_date), 
-                path.eq(document_path)))
+                    document_date : &Option<PartialDate>,
+                    document_path : &String) -> Result<Document, DbError> {
+    return add_document_with_metadata(connection, document_name, document_category, document_author, document_series, document_date, document_path, &DocumentMetadata::default());
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn add_document_with_metadata(connection : &mut SqliteConnection, 
+                                document_name : &String, 
+                                document_category : &i32, 
+                                document_author : &Option<i32>, 
+                                document_series : &Option<i32>,
+                                document_date : &Option<PartialDate>,
+                                document_path : &String,
+                                document_metadata : &DocumentMetadata) -> Result<Document, DbError> {
+    return connection.transaction::<Document, DbError, _>(|connection| {
+        let relative_path = relative_to_category(connection, document_category, document_path)?;
+        let added_document = insert_into(document)
+            .values((name.eq(document_name), 
+                    category.eq(document_category), 
+                    author.eq(document_author), 
+                    series.eq(document_series), 
+                    date.eq(document_date), 
+                    path.eq(relative_path), 
+                    description.eq(&document_metadata.description_), 
+                    language.eq(&document_metadata.language_), 
+                    publisher.eq(&document_metadata.publisher_), 
+                    page_count.eq(&document_metadata.page_count_), 
+                    file_size.eq(&document_metadata.file_size_), 
+                    mime_type.eq(&document_metadata.mime_type_), 
+                    sort_title.eq(&document_metadata.sort_title_), 
+                    right_to_left.eq(&document_metadata.right_to_left_)))
+            .get_result::<Document>(connection)
+            .map_err(|e| DbError::from_diesel(format!("Could not add document {} to database", document_name), e))?;
+        // the main author is also listed among contributors, which is what author queries look at
+        if let Some(author_id) = document_author {
+            link_contributor_to_document(connection, &added_document.id_, author_id, &ContributorRole::Author)?;
+        }
+        Ok(added_document)
+    });
+}
+
+/// Gives the document path relative to its category root, absolute paths must be inside of it.
+fn relative_to_category(connection : &mut SqliteConnection, document_category : &i32, document_path : &String) -> Result<String, DbError> {
+    let given_path = Path::new(document_path);
+    if !given_path.is_absolute() {
+        return Ok(document_path.clone());
+    }
+    let category_root = get_category_by_id(connection, document_category)?.root_path();
+    let given_path = given_path.canonicalize().unwrap_or(given_path.to_path_buf());
+    return given_path.strip_prefix(&category_root)
+        .map(|relative_path| relative_path.to_string_lossy().to_string())
+        .map_err(|_| DbError::Conflict(format!("Document path {} is outside of category {} root {}", document_path, document_category, category_root.display())));
+}
+
+/// Absolute path of the document file, built from the root of its category.
+pub fn resolve_document_path(connection : &mut SqliteConnection, document_id : &i32) -> Result<PathBuf, DbError> {
+    let found_document = get_document_by_id(connection, document_id)?;
+    let document_category = get_category_by_id(connection, &found_document.category_)?;
+    return Ok(document_category.root_path().join(&found_document.path_));
+}
+
+/// Replaces every metadata field of the document, None clears the field.
+pub fn update_document_metadata(connection : &mut SqliteConnection, document_id : &i32, document_metadata : &DocumentMetadata) -> Result<Document, DbError> {
+    return update(document.filter(id.eq(document_id)))
+        .set((description.eq(&document_metadata.description_), 
+            language.eq(&document_metadata.language_), 
+            publisher.eq(&document_metadata.publisher_), 
+            page_count.eq(&document_metadata.page_count_), 
+            file_size.eq(&document_metadata.file_size_), 
+            mime_type.eq(&document_metadata.mime_type_), 
+            sort_title.eq(&document_metadata.sort_title_), 
+            right_to_left.eq(&document_metadata.right_to_left_)))
+        .get_result::<Document>(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not update metadata of document {}", document_id), e));
+}
+
+/// Places a document in a series, indexes can be fractional (1.5 for a side story between 1 and 2).
+pub fn set_document_series_position(connection : &mut SqliteConnection, 
+                                    document_id : &i32, 
+                                    document_series : &Option<i32>, 
+                                    document_series_index : &Option<f64>, 
+                                    document_series_numbering : &Option<SeriesNumbering>) -> Result<Document, DbError> {
+    return update(document.filter(id.eq(document_id)))
+        .set((series.eq(document_series), 
+            series_index.eq(document_series_index), 
+            series_numbering.eq(document_series_numbering)))
+        .get_result::<Document>(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not set series position of document {}", document_id), e));
+}
+
+/// Moves the document to the trash, its links are kept so it can be restored.
+pub fn remove_document(connection : &mut SqliteConnection, document_id : &i32) -> Result<(), DbError> {
+    let trashed_rows = update(document.filter(id.eq(document_id)).filter(deleted_at.is_null()))
+        .set(deleted_at.eq(Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)))
         .execute(connection)
-        .map_err(|e| format!("Could not add document {} to database : {}", document_name, e))?;
-    match added_rows {
-        1 => get_document_by_name(connection, document_name)
-                .ok_or(format!("Could not find newly created author {} in database", document_name)),
-        _ => Err(format!("Something wrong happened while adding author {} in database", document_name))
+        .map_err(|e| DbError::from_diesel(format!("Could not delete document {} from database", document_id), e))?;
+    match trashed_rows {
+        1 => Ok(()),
+        _ => Err(DbError::NotFound(format!("Could not find document {} to delete from database", document_id)))
     }
 }
 
-pub fn remove_document(connection : &mut SqliteConnection, document_id : &i32) -> Result<(), String> {
-    let deleted_rows = delete(document).filter(id.eq(&document_id)).execute(connection)
-        .map_err(|e| format!("Could not delete document {} from database : {}", document_id, e))?;
+pub fn restore_document(connection : &mut SqliteConnection, document_id : &i32) -> Result<Document, DbError> {
+    return update(document.filter(id.eq(document_id)).filter(deleted_at.is_not_null()))
+        .set(deleted_at.eq(None::<String>))
+        .get_result::<Document>(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not restore document {} from trash", document_id), e));
+}
+
+pub fn get_trashed_documents(connection : &mut SqliteConnection) -> Result<Vec<Document>, DbError> {
+    return document.filter(deleted_at.is_not_null()).order((deleted_at.asc(), id.asc())).load::<Document>(connection)
+        .map_err(|e| DbError::from_diesel("An error occured while getting trashed documents".to_string(), e));
+}
+
+/// Deletes a trashed document for good, along with its links.
+pub fn purge_document(connection : &mut SqliteConnection, document_id : &i32) -> Result<(), DbError> {
+    let deleted_rows = delete(document.filter(id.eq(document_id)).filter(deleted_at.is_not_null())).execute(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not purge document {} from database", document_id), e))?;
     match deleted_rows {
         1 => Ok(()),
-        _ => Err(format!("Something wrong happened while deleting document {} from database", document_id))
+        _ => Err(DbError::NotFound(format!("Could not find document {} in trash to purge", document_id)))
     }
 }
 
-pub fn get_documents(connection : &mut SqliteConnection) -> Result<Vec<Document>, String> {
-    return document.load::<Document>(connection)
-    .map_err(|e| format!("An errror occured while getting all documents : {}", e));
+/// Deletes for good every document trashed for longer than the retention, gives how many were deleted.
+pub fn purge_trash(connection : &mut SqliteConnection, retention : &Duration) -> Result<usize, DbError> {
+    // deletion dates are stored to the second in UTC, so they compare as text
+    let oldest_kept = (Utc::now() - *retention).to_rfc3339_opts(SecondsFormat::Secs, true);
+    return delete(document.filter(deleted_at.le(oldest_kept))).execute(connection)
+        .map_err(|e| DbError::from_diesel("Could not purge trash".to_string(), e));
 }
 
-pub fn get_document_by_id(connection : &mut SqliteConnection, document_id : &i32) -> Option<Document> {
-    match document.filter(id.eq(document_id)).first::<Document>(connection) {
-        Ok(d) => Some(d),
-        Err(_) => None
-    }
+pub fn get_documents(connection : &mut SqliteConnection) -> Result<Vec<Document>, DbError> {
+    return document.filter(deleted_at.is_null()).load::<Document>(connection)
+    .map_err(|e| DbError::from_diesel("An errror occured while getting all documents".to_string(), e));
 }
 
-pub fn get_document_by_name(connection : &mut SqliteConnection, document_name : &String) -> Option<Document> {
-    match document.filter(name.eq(document_name)).first::<Document>(connection) {
-        Ok(d) => Some(d),
-        Err(_) => None
-    }
+pub fn get_document_by_id(connection : &mut SqliteConnection, document_id : &i32) -> Result<Document, DbError> {
+    return document.filter(id.eq(document_id)).filter(deleted_at.is_null()).first::<Document>(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not get document {}", document_id), e));
+}
+
+pub fn get_document_by_name(connection : &mut SqliteConnection, document_name : &String) -> Result<Document, DbError> {
+    return document.filter(name.eq(document_name)).filter(deleted_at.is_null()).first::<Document>(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not get document {}", document_name), e));
 }
 
 
-pub fn get_documents_from_author(connection : &mut SqliteConnection, author_id : &i32) -> Result<Vec<Document>, String> {
-    return document.filter(author.eq(author_id)).load::<Document>(connection)
-        .map_err(|e| format!("An error occured while trying to get all documents from author {} : {}", author_id, e));
+/// Gives every document the author contributed to, whatever their role.
+pub fn get_documents_from_author(connection : &mut SqliteConnection, author_id : &i32) -> Result<Vec<Document>, DbError> {
+    use crate::db_manager::entities::schema::document_contributor::dsl::{document_contributor, document as dc_document, author as dc_author};
+    return document
+        .filter(id.eq_any(document_contributor.filter(dc_author.eq(author_id)).select(dc_document)))
+        .filter(deleted_at.is_null())
+        .load::<Document>(connection)
+        .map_err(|e| DbError::from_diesel(format!("An error occured while trying to get all documents from author {}", author_id), e));
 }
 
-pub fn get_documents_from_series(connection : &mut SqliteConnection, series_id : &i32) -> Result<Vec<Document>, String> {
-    return document.filter(series.eq(series_id)).load::<Document>(connection)
-    .map_err(|e| format!("An error occured while trying to get all documents from series {} : {}", series_id, e));
+pub fn get_documents_from_author_with_role(connection : &mut SqliteConnection, author_id : &i32, contributor_role : &ContributorRole) -> Result<Vec<Document>, DbError> {
+    use crate::db_manager::entities::schema::document_contributor::dsl::{document_contributor, document as dc_document, author as dc_author, role as dc_role};
+    return document
+        .filter(id.eq_any(document_contributor.filter(dc_author.eq(author_id)).filter(dc_role.eq(contributor_role)).select(dc_document)))
+        .filter(deleted_at.is_null())
+        .load::<Document>(connection)
+        .map_err(|e| DbError::from_diesel(format!("An error occured while trying to get all documents from author {} as {}", author_id, contributor_role), e));
+}
+
+pub fn get_documents_from_series(connection : &mut SqliteConnection, series_id : &i32) -> Result<Vec<Document>, DbError> {
+    // unnumbered documents come last, in insertion order
+    return document.filter(series.eq(series_id)).filter(deleted_at.is_null())
+    .order((series_index.is_null(), series_index.asc(), id.asc()))
+    .load::<Document>(connection)
+    .map_err(|e| DbError::from_diesel(format!("An error occured while trying to get all documents from series {}", series_id), e));
+}
+
+/// Gives documents stored in the category as well as the ones linked to it.
+pub fn get_documents_from_category(connection : &mut SqliteConnection, category_id : &i32) -> Result<Vec<Document>, DbError> {
+    use crate::db_manager::entities::schema::document_category::dsl::{document_category, document as dc_document, category as dc_category};
+    return document
+        .filter(category.eq(category_id).or(id.eq_any(document_category.filter(dc_category.eq(category_id)).select(dc_document))))
+        .filter(deleted_at.is_null())
+        .load::<Document>(connection)
+        .map_err(|e| DbError::from_diesel(format!("An error occured while trying to get all documents from category {}", category_id), e));
 }
 
-pub fn get_documents_from_category(connection : &mut SqliteConnection, category_id : &i32) -> Result<Vec<Document>, String> {
-    return document.filter(category.eq(category_id)).load::<Document>(connection)
-        .map_err(|e| format!("An error occured while trying to get all documents from category {} : {}", category_id, e));
+/// Gives every document whose file lives under the category root, trashed ones included, since their file is still there.
+pub fn get_documents_stored_in_category(connection : &mut SqliteConnection, category_id : &i32) -> Result<Vec<Document>, DbError> {
+    return document.filter(category.eq(category_id)).order(id.asc()).load::<Document>(connection)
+        .map_err(|e| DbError::from_diesel(format!("An error occured while trying to get documents stored in category {}", category_id), e));
 }
 
-pub fn get_documents_with_genre(connection : &mut SqliteConnection, genre_id : &i32) -> Result<Vec<Document>, String> {
+/// Gives documents with the genre, and with any genre below it when include_descendants is set.
+pub fn get_documents_with_genre(connection : &mut SqliteConnection, genre_id : &i32, include_descendants : &bool) -> Result<Vec<Document>, DbError> {
     use crate::db_manager::entities::schema::document_genre::dsl::{document_genre, document as dg_document, genre as dg_genre};
-    use crate::db_manager::entities::schema::genre::dsl::{genre, id as g_id};
+    let genre_ids = match include_descendants {
+        true => get_genre_subtree(connection, genre_id)?.iter().map(|g| g.id_).collect(),
+        false => vec![*genre_id]
+    };
     return document
-        .inner_join(document_genre.on(id.eq(dg_document)))
-        .inner_join(genre.on(dg_genre.eq(g_id)))
-        .filter(g_id.eq(genre_id))
-        .select(document::all_columns())
+        .filter(id.eq_any(document_genre.filter(dg_genre.eq_any(genre_ids)).select(dg_document)))
+        .filter(deleted_at.is_null())
         .load::<Document>(connection)
-        .map_err(|e| format!("An error occured whiletrying to get all documents with genre {} : {}", genre_id, e));
+        .map_err(|e| DbError::from_diesel(format!("An error occured whiletrying to get all documents with genre {}", genre_id), e));
 
 }
 
-pub fn get_documents_with_tag(connection : &mut SqliteConnection, tag_id : &i32) -> Result<Vec<Document>, String> {
+/// Gives documents with the tag, and with any tag below it when include_descendants is set.
+pub fn get_documents_with_tag(connection : &mut SqliteConnection, tag_id : &i32, include_descendants : &bool) -> Result<Vec<Document>, DbError> {
     use crate::db_manager::entities::schema::document_tag::dsl::{document_tag, document as dt_document, tag as dt_tag};
-    use crate::db_manager::entities::schema::tag::dsl::{tag, id as t_id};
+    let tag_ids = match include_descendants {
+        true => get_tag_subtree(connection, tag_id)?.iter().map(|t| t.id_).collect(),
+        false => vec![*tag_id]
+    };
     return document
-    .inner_join(document_tag.on(id.eq(dt_document)))
-    .inner_join(tag.on(dt_tag.eq(t_id)))
-    .filter(t_id.eq(tag_id))
-    .select(document::all_columns())
+    .filter(id.eq_any(document_tag.filter(dt_tag.eq_any(tag_ids)).select(dt_document)))
+    .filter(deleted_at.is_null())
     .load::<Document>(connection)
-    .map_err(|e| format!("An error occured whiletrying to get all documents with tag {} : {}", tag_id, e));
+    .map_err(|e| DbError::from_diesel(format!("An error occured whiletrying to get all documents with tag {}", tag_id), e));
 
 }
 
 #[cfg(test)]
 mod tests {
-    use std::path::Path;
-    use chrono::NaiveDate;
-    use crate::db_manager::{create_database, delete_database, entities::{add_author, add_category, add_document, add_genre, add_series, add_tag, get_document_by_id, get_documents, get_documents_from_author, get_documents_from_series, get_documents_with_genre, get_documents_with_tag, link_genre_to_document, link_tag_to_document, remove_document, Document}, get_connection};
+    use std::{fs::{create_dir, remove_dir}, path::{Path, PathBuf}};
+    use chrono::{Duration, NaiveDate};
+    use crate::db_manager::{create_database, delete_database, entities::{add_author, add_category, add_document, add_document_with_metadata, add_genre, move_genre, add_series, add_tag, get_document_by_id, get_documents, get_documents_from_author, get_documents_from_author_with_role, get_documents_from_category, get_documents_from_series, get_documents_with_genre, get_documents_with_tag, get_document_genre, get_document_tag, link_category_to_document, link_contributor_to_document, link_genre_to_document, link_tag_to_document, purge_document, purge_trash, remove_document, resolve_document_path, restore_document, get_trashed_documents, set_document_series_position, update_category_path, update_document_metadata, ContributorRole, Document, DocumentMetadata, SeriesNumbering}, get_connection, DbError};
 
     #[test]
     fn adding_document_should_give_newly_created_document() {
@@ -120,8 +346,8 @@ mod tests {
         let books = add_category(&mut connection, &"Books".to_string(), &"~/Documents/Books".to_string()).unwrap();
         let jrr_tolkien = add_author(&mut connection, &"J.R.R Tolkien".to_string()).unwrap();
         let the_lord_of_the_rings = add_series(&mut connection, &"The Lord Of The Rings".to_string(), &jrr_tolkien.id_).unwrap();
-        let maybe_the_fellowship_of_the_ring = add_document(&mut connection, &"The fellowship of the ring".to_string(), &books.id_, &Some(jrr_tolkien.id_), &Some(the_lord_of_the_rings.id_), &NaiveDate::from_ymd_opt(1954, 6, 29).unwrap().to_string(), &"Tolkien/the_fellowship_of_the_ring.epub".to_string()).unwrap();
-        assert_eq!(Document::new(1, "The fellowship of the ring".to_string(), books.id_, Some(jrr_tolkien.id_), Some(the_lord_of_the_rings.id_), NaiveDate::from_ymd_opt(1954, 6, 29).unwrap().to_string(), "Tolkien/the_fellowship_of_the_ring.epub".to_string()), maybe_the_fellowship_of_the_ring);
+        let maybe_the_fellowship_of_the_ring = add_document(&mut connection, &"The fellowship of the ring".to_string(), &books.id_, &Some(jrr_tolkien.id_), &Some(the_lord_of_the_rings.id_), &Some(NaiveDate::from_ymd_opt(1954, 6, 29).unwrap().into()), &"Tolkien/the_fellowship_of_the_ring.epub".to_string()).unwrap();
+        assert_eq!(Document::new(1, "The fellowship of the ring".to_string(), books.id_, Some(jrr_tolkien.id_), Some(the_lord_of_the_rings.id_), Some(NaiveDate::from_ymd_opt(1954, 6, 29).unwrap().into()), "Tolkien/the_fellowship_of_the_ring.epub".to_string(), None, None, None, None, None, None, None, None, None, None, None, None, None), maybe_the_fellowship_of_the_ring);
         delete_database(test_db_path).unwrap();
     }
 
@@ -131,9 +357,9 @@ mod tests {
         create_database(test_db_path).unwrap();
         let mut connection = get_connection(test_db_path).unwrap();
         let books = add_category(&mut connection, &"Books".to_string(), &"~/Documents/Books".to_string()).unwrap();
-        let the_fellowship_of_the_ring = add_document(&mut connection, &"The Fellowship Of The Ring".to_string(), &books.id_, &None, &None, &NaiveDate::from_ymd_opt(1954, 6, 29).unwrap().to_string(), &"Tolkien/the_fellowship_of_the_ring.epub".to_string()).unwrap();
-        let the_two_towers = add_document(&mut connection, &"The Two Towers".to_string(), &books.id_, &None, &None, &NaiveDate::from_ymd_opt(1954, 11, 11).unwrap().to_string(), &"Tolkien/the_two_towers.epub".to_string()).unwrap();
-        let the_return_of_the_king = add_document(&mut connection, &"The Return of The King".to_string(), &books.id_, &None, &None, &NaiveDate::from_ymd_opt(1955, 10, 20).unwrap().to_string(), &"Tolkien/the_return_of_the_king".to_string()).unwrap();
+        let the_fellowship_of_the_ring = add_document(&mut connection, &"The Fellowship Of The Ring".to_string(), &books.id_, &None, &None, &Some(NaiveDate::from_ymd_opt(1954, 6, 29).unwrap().into()), &"Tolkien/the_fellowship_of_the_ring.epub".to_string()).unwrap();
+        let the_two_towers = add_document(&mut connection, &"The Two Towers".to_string(), &books.id_, &None, &None, &Some(NaiveDate::from_ymd_opt(1954, 11, 11).unwrap().into()), &"Tolkien/the_two_towers.epub".to_string()).unwrap();
+        let the_return_of_the_king = add_document(&mut connection, &"The Return of The King".to_string(), &books.id_, &None, &None, &Some(NaiveDate::from_ymd_opt(1955, 10, 20).unwrap().into()), &"Tolkien/the_return_of_the_king".to_string()).unwrap();
         let documents = [the_fellowship_of_the_ring, the_two_towers, the_return_of_the_king].to_vec();
         let queried_documents = get_documents(&mut connection).unwrap();
         assert_eq!(documents, queried_documents);
@@ -146,7 +372,7 @@ mod tests {
         create_database(test_db_path).unwrap();
         let mut connection = get_connection(test_db_path).unwrap();
         let books = add_category(&mut connection, &"Books".to_string(), &"~/Documents/Books".to_string()).unwrap();
-        let the_fellowship_of_the_ring = add_document(&mut connection, &"The Fellowship Of The Ring".to_string(), &books.id_, &None, &None, &NaiveDate::from_ymd_opt(1954, 6, 29).unwrap().to_string(), &"Tolkien/the_fellowship_of_the_ring.epub".to_string()).unwrap();
+        let the_fellowship_of_the_ring = add_document(&mut connection, &"The Fellowship Of The Ring".to_string(), &books.id_, &None, &None, &Some(NaiveDate::from_ymd_opt(1954, 6, 29).unwrap().into()), &"Tolkien/the_fellowship_of_the_ring.epub".to_string()).unwrap();
         let maybe_the_fellowship_of_the_ring = get_document_by_id(&mut connection, &the_fellowship_of_the_ring.id_).unwrap();
         assert_eq!(the_fellowship_of_the_ring, maybe_the_fellowship_of_the_ring);
         delete_database(test_db_path).unwrap();
@@ -158,9 +384,9 @@ mod tests {
         create_database(test_db_path).unwrap();
         let mut connection = get_connection(test_db_path).unwrap();
         let books = add_category(&mut connection, &"Books".to_string(), &"~/Documents/Books".to_string()).unwrap();
-        let the_fellowship_of_the_ring = add_document(&mut connection, &"The Fellowship Of The Ring".to_string(), &books.id_, &None, &None, &NaiveDate::from_ymd_opt(1954, 6, 29).unwrap().to_string(), &"Tolkien/the_fellowship_of_the_ring.epub".to_string()).unwrap();
+        let the_fellowship_of_the_ring = add_document(&mut connection, &"The Fellowship Of The Ring".to_string(), &books.id_, &None, &None, &Some(NaiveDate::from_ymd_opt(1954, 6, 29).unwrap().into()), &"Tolkien/the_fellowship_of_the_ring.epub".to_string()).unwrap();
         remove_document(&mut connection, &the_fellowship_of_the_ring.id_).unwrap();
-        assert!(get_document_by_id(&mut connection, &the_fellowship_of_the_ring.id_).is_none());
+        assert!(matches!(get_document_by_id(&mut connection, &the_fellowship_of_the_ring.id_), Err(DbError::NotFound(_))));
         delete_database(test_db_path).unwrap();
     }
 
@@ -171,12 +397,12 @@ mod tests {
         let mut connection = get_connection(test_db_path).unwrap();
         let books = add_category(&mut connection, &"Books".to_string(), &"~/Documents/Books".to_string()).unwrap();
         let jrr_tolkien = add_author(&mut connection, &"J.R.R Tolkien".to_string()).unwrap();
-        let the_fellowship_of_the_ring = add_document(&mut connection, &"The Fellowship Of The Ring".to_string(), &books.id_, &Some(jrr_tolkien.id_), &None, &NaiveDate::from_ymd_opt(1954, 6, 29).unwrap().to_string(), &"Tolkien/the_fellowship_of_the_ring.epub".to_string()).unwrap();
-        let the_two_towers = add_document(&mut connection, &"The Two Towers".to_string(), &books.id_, &Some(jrr_tolkien.id_), &None, &NaiveDate::from_ymd_opt(1954, 11, 11).unwrap().to_string(), &"Tolkien/the_two_towers.epub".to_string()).unwrap();
-        let the_return_of_the_king = add_document(&mut connection, &"The Return of The King".to_string(), &books.id_, &Some(jrr_tolkien.id_), &None, &NaiveDate::from_ymd_opt(1955, 10, 20).unwrap().to_string(), &"Tolkien/the_return_of_the_king".to_string()).unwrap();
+        let the_fellowship_of_the_ring = add_document(&mut connection, &"The Fellowship Of The Ring".to_string(), &books.id_, &Some(jrr_tolkien.id_), &None, &Some(NaiveDate::from_ymd_opt(1954, 6, 29).unwrap().into()), &"Tolkien/the_fellowship_of_the_ring.epub".to_string()).unwrap();
+        let the_two_towers = add_document(&mut connection, &"The Two Towers".to_string(), &books.id_, &Some(jrr_tolkien.id_), &None, &Some(NaiveDate::from_ymd_opt(1954, 11, 11).unwrap().into()), &"Tolkien/the_two_towers.epub".to_string()).unwrap();
+        let the_return_of_the_king = add_document(&mut connection, &"The Return of The King".to_string(), &books.id_, &Some(jrr_tolkien.id_), &None, &Some(NaiveDate::from_ymd_opt(1955, 10, 20).unwrap().into()), &"Tolkien/the_return_of_the_king".to_string()).unwrap();
         let from_tolkien = [the_fellowship_of_the_ring, the_two_towers, the_return_of_the_king].to_vec();
         let rr_martin = add_author(&mut connection, &"R.R Martin".to_string()).unwrap();
-        let a_game_of_throne = add_document(&mut connection, &"A Game Of Thrones".to_string(), &books.id_, &Some(rr_martin.id_), &None, &NaiveDate::from_ymd_opt(1996, 8, 6).unwrap().to_string(), &"Martin/A_game_of_thrones.epub".to_string()).unwrap();
+        let a_game_of_throne = add_document(&mut connection, &"A Game Of Thrones".to_string(), &books.id_, &Some(rr_martin.id_), &None, &Some(NaiveDate::from_ymd_opt(1996, 8, 6).unwrap().into()), &"Martin/A_game_of_thrones.epub".to_string()).unwrap();
         let queried_documents = get_documents_from_author(&mut connection, &jrr_tolkien.id_).unwrap();
         assert_eq!(from_tolkien, queried_documents);
         delete_database(test_db_path).unwrap();
@@ -190,13 +416,13 @@ mod tests {
         let books = add_category(&mut connection, &"Books".to_string(), &"~/Documents/Books".to_string()).unwrap();
         let jrr_tolkien = add_author(&mut connection, &"J.R.R Tolkien".to_string()).unwrap();
         let the_lord_of_the_ring = add_series(&mut connection, &"The Lord Of The Rings".to_string(), &jrr_tolkien.id_).unwrap();
-        let the_fellowship_of_the_ring = add_document(&mut connection, &"The Fellowship Of The Ring".to_string(), &books.id_, &Some(jrr_tolkien.id_), &Some(the_lord_of_the_ring.id_), &NaiveDate::from_ymd_opt(1954, 6, 29).unwrap().to_string(), &"Tolkien/the_fellowship_of_the_ring.epub".to_string()).unwrap();
-        let the_two_towers = add_document(&mut connection, &"The Two Towers".to_string(), &books.id_, &Some(jrr_tolkien.id_), &Some(the_lord_of_the_ring.id_), &NaiveDate::from_ymd_opt(1954, 11, 11).unwrap().to_string(), &"Tolkien/the_two_towers.epub".to_string()).unwrap();
-        let the_return_of_the_king = add_document(&mut connection, &"The Return of The King".to_string(), &books.id_, &Some(jrr_tolkien.id_), &Some(the_lord_of_the_ring.id_), &NaiveDate::from_ymd_opt(1955, 10, 20).unwrap().to_string(), &"Tolkien/the_return_of_the_king".to_string()).unwrap();
+        let the_fellowship_of_the_ring = add_document(&mut connection, &"The Fellowship Of The Ring".to_string(), &books.id_, &Some(jrr_tolkien.id_), &Some(the_lord_of_the_ring.id_), &Some(NaiveDate::from_ymd_opt(1954, 6, 29).unwrap().into()), &"Tolkien/the_fellowship_of_the_ring.epub".to_string()).unwrap();
+        let the_two_towers = add_document(&mut connection, &"The Two Towers".to_string(), &books.id_, &Some(jrr_tolkien.id_), &Some(the_lord_of_the_ring.id_), &Some(NaiveDate::from_ymd_opt(1954, 11, 11).unwrap().into()), &"Tolkien/the_two_towers.epub".to_string()).unwrap();
+        let the_return_of_the_king = add_document(&mut connection, &"The Return of The King".to_string(), &books.id_, &Some(jrr_tolkien.id_), &Some(the_lord_of_the_ring.id_), &Some(NaiveDate::from_ymd_opt(1955, 10, 20).unwrap().into()), &"Tolkien/the_return_of_the_king".to_string()).unwrap();
         let from_the_lord_of_the_ring = [the_fellowship_of_the_ring, the_two_towers, the_return_of_the_king].to_vec();
         let rr_martin = add_author(&mut connection, &"R.R Martin".to_string()).unwrap();
         let a_song_of_ice_and_fire = add_series(&mut connection, &"A Song Of Ice And Fire".to_string(), &rr_martin.id_).unwrap();
-        let a_game_of_throne = add_document(&mut connection, &"A Game Of Thrones".to_string(), &books.id_, &Some(rr_martin.id_), &Some(a_song_of_ice_and_fire.id_), &NaiveDate::from_ymd_opt(1996, 8, 6).unwrap().to_string(), &"Martin/A_game_of_thrones.epub".to_string()).unwrap();
+        let a_game_of_throne = add_document(&mut connection, &"A Game Of Thrones".to_string(), &books.id_, &Some(rr_martin.id_), &Some(a_song_of_ice_and_fire.id_), &Some(NaiveDate::from_ymd_opt(1996, 8, 6).unwrap().into()), &"Martin/A_game_of_thrones.epub".to_string()).unwrap();
         let queried_documents = get_documents_from_series(&mut connection, &the_lord_of_the_ring.id_).unwrap();
         assert_eq!(from_the_lord_of_the_ring, queried_documents);
         delete_database(test_db_path).unwrap();
@@ -208,16 +434,16 @@ mod tests {
         create_database(test_db_path).unwrap();
         let mut connection = get_connection(test_db_path).unwrap();
         let books = add_category(&mut connection, &"Books".to_string(), &"~/Documents/Books".to_string()).unwrap();
-        let the_fellowship_of_the_ring = add_document(&mut connection, &"The Fellowship Of The Ring".to_string(), &books.id_, &None, &None, &NaiveDate::from_ymd_opt(1954, 6, 29).unwrap().to_string(), &"Tolkien/the_fellowship_of_the_ring.epub".to_string()).unwrap();
-        let the_two_towers = add_document(&mut connection, &"The Two Towers".to_string(), &books.id_, &None, &None, &NaiveDate::from_ymd_opt(1954, 11, 11).unwrap().to_string(), &"Tolkien/the_two_towers.epub".to_string()).unwrap();
-        let the_return_of_the_king = add_document(&mut connection, &"The Return of The King".to_string(), &books.id_, &None, &None, &NaiveDate::from_ymd_opt(1955, 10, 20).unwrap().to_string(), &"Tolkien/the_return_of_the_king".to_string()).unwrap();
+        let the_fellowship_of_the_ring = add_document(&mut connection, &"The Fellowship Of The Ring".to_string(), &books.id_, &None, &None, &Some(NaiveDate::from_ymd_opt(1954, 6, 29).unwrap().into()), &"Tolkien/the_fellowship_of_the_ring.epub".to_string()).unwrap();
+        let the_two_towers = add_document(&mut connection, &"The Two Towers".to_string(), &books.id_, &None, &None, &Some(NaiveDate::from_ymd_opt(1954, 11, 11).unwrap().into()), &"Tolkien/the_two_towers.epub".to_string()).unwrap();
+        let the_return_of_the_king = add_document(&mut connection, &"The Return of The King".to_string(), &books.id_, &None, &None, &Some(NaiveDate::from_ymd_opt(1955, 10, 20).unwrap().into()), &"Tolkien/the_return_of_the_king".to_string()).unwrap();
         let bangers = [the_fellowship_of_the_ring.clone(), the_two_towers.clone(), the_return_of_the_king.clone()].to_vec();
-        let a_game_of_throne = add_document(&mut connection, &"A Game Of Thrones".to_string(), &books.id_, &None, &None, &NaiveDate::from_ymd_opt(1996, 8, 6).unwrap().to_string(), &"Martin/A_game_of_thrones.epub".to_string()).unwrap();
+        let a_game_of_throne = add_document(&mut connection, &"A Game Of Thrones".to_string(), &books.id_, &None, &None, &Some(NaiveDate::from_ymd_opt(1996, 8, 6).unwrap().into()), &"Martin/A_game_of_thrones.epub".to_string()).unwrap();
         let banger = add_tag(&mut connection, &"banger".to_string()).unwrap();
         link_tag_to_document(&mut connection, &the_fellowship_of_the_ring.id_, &banger.id_).unwrap();
         link_tag_to_document(&mut connection, &the_two_towers.id_, &banger.id_).unwrap();
         link_tag_to_document(&mut connection, &the_return_of_the_king.id_, &banger.id_).unwrap();
-        let queried_documents = get_documents_with_tag(&mut connection, &banger.id_).unwrap();
+        let queried_documents = get_documents_with_tag(&mut connection, &banger.id_, &false).unwrap();
         assert_eq!(bangers, queried_documents);
         delete_database(test_db_path).unwrap();
     }
@@ -228,17 +454,232 @@ mod tests {
         create_database(test_db_path).unwrap();
         let mut connection = get_connection(test_db_path).unwrap();
         let books = add_category(&mut connection, &"Books".to_string(), &"~/Documents/Books".to_string()).unwrap();
-        let the_fellowship_of_the_ring = add_document(&mut connection, &"The Fellowship Of The Ring".to_string(), &books.id_, &None, &None, &NaiveDate::from_ymd_opt(1954, 6, 29).unwrap().to_string(), &"Tolkien/the_fellowship_of_the_ring.epub".to_string()).unwrap();
-        let the_two_towers = add_document(&mut connection, &"The Two Towers".to_string(), &books.id_, &None, &None, &NaiveDate::from_ymd_opt(1954, 11, 11).unwrap().to_string(), &"Tolkien/the_two_towers.epub".to_string()).unwrap();
-        let the_return_of_the_king = add_document(&mut connection, &"The Return of The King".to_string(), &books.id_, &None, &None, &NaiveDate::from_ymd_opt(1955, 10, 20).unwrap().to_string(), &"Tolkien/the_return_of_the_king".to_string()).unwrap();
+        let the_fellowship_of_the_ring = add_document(&mut connection, &"The Fellowship Of The Ring".to_string(), &books.id_, &None, &None, &Some(NaiveDate::from_ymd_opt(1954, 6, 29).unwrap().into()), &"Tolkien/the_fellowship_of_the_ring.epub".to_string()).unwrap();
+        let the_two_towers = add_document(&mut connection, &"The Two Towers".to_string(), &books.id_, &None, &None, &Some(NaiveDate::from_ymd_opt(1954, 11, 11).unwrap().into()), &"Tolkien/the_two_towers.epub".to_string()).unwrap();
+        let the_return_of_the_king = add_document(&mut connection, &"The Return of The King".to_string(), &books.id_, &None, &None, &Some(NaiveDate::from_ymd_opt(1955, 10, 20).unwrap().into()), &"Tolkien/the_return_of_the_king".to_string()).unwrap();
         let hf = [the_fellowship_of_the_ring.clone(), the_two_towers.clone(), the_return_of_the_king.clone()].to_vec();
-        let a_game_of_throne = add_document(&mut connection, &"A Game Of Thrones".to_string(), &books.id_, &None, &None, &NaiveDate::from_ymd_opt(1996, 8, 6).unwrap().to_string(), &"Martin/A_game_of_thrones.epub".to_string()).unwrap();
+        let a_game_of_throne = add_document(&mut connection, &"A Game Of Thrones".to_string(), &books.id_, &None, &None, &Some(NaiveDate::from_ymd_opt(1996, 8, 6).unwrap().into()), &"Martin/A_game_of_thrones.epub".to_string()).unwrap();
         let heroic_fantasy = add_genre(&mut connection, &"Heroic Fantasy".to_string()).unwrap();
         link_genre_to_document(&mut connection, &the_fellowship_of_the_ring.id_, &heroic_fantasy.id_).unwrap();
         link_genre_to_document(&mut connection, &the_two_towers.id_, &heroic_fantasy.id_).unwrap();
         link_genre_to_document(&mut connection, &the_return_of_the_king.id_, &heroic_fantasy.id_).unwrap();
-        let queried_documents = get_documents_with_genre(&mut connection, &heroic_fantasy.id_).unwrap();
+        let queried_documents = get_documents_with_genre(&mut connection, &heroic_fantasy.id_, &false).unwrap();
         assert_eq!(hf, queried_documents);
         delete_database(test_db_path).unwrap();
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn purge_document_should_remove_its_tags_and_genres() {
+        let test_db_path = Path::new("./purge_document_should_remove_its_tags_and_genres.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let books = add_category(&mut connection, &"Books".to_string(), &"~/Documents/Books".to_string()).unwrap();
+        let the_fellowship_of_the_ring = add_document(&mut connection, &"The Fellowship Of The Ring".to_string(), &books.id_, &None, &None, &Some(NaiveDate::from_ymd_opt(1954, 6, 29).unwrap().into()), &"Tolkien/the_fellowship_of_the_ring.epub".to_string()).unwrap();
+        let banger = add_tag(&mut connection, &"banger".to_string()).unwrap();
+        let heroic_fantasy = add_genre(&mut connection, &"Heroic Fantasy".to_string()).unwrap();
+        link_tag_to_document(&mut connection, &the_fellowship_of_the_ring.id_, &banger.id_).unwrap();
+        link_genre_to_document(&mut connection, &the_fellowship_of_the_ring.id_, &heroic_fantasy.id_).unwrap();
+        remove_document(&mut connection, &the_fellowship_of_the_ring.id_).unwrap();
+        purge_document(&mut connection, &the_fellowship_of_the_ring.id_).unwrap();
+        assert!(matches!(get_document_tag(&mut connection, &the_fellowship_of_the_ring.id_, &banger.id_), Err(DbError::NotFound(_))));
+        assert!(matches!(get_document_genre(&mut connection, &the_fellowship_of_the_ring.id_, &heroic_fantasy.id_), Err(DbError::NotFound(_))));
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn adding_document_in_missing_category_should_be_refused() {
+        let test_db_path = Path::new("./adding_document_in_missing_category_should_be_refused.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let maybe_the_hobbit = add_document(&mut connection, &"The Hobbit".to_string(), &42, &None, &None, &Some(NaiveDate::from_ymd_opt(1937, 9, 21).unwrap().into()), &"Tolkien/the_hobbit.epub".to_string());
+        assert!(matches!(maybe_the_hobbit, Err(DbError::ForeignKey(_))));
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn adding_documents_with_same_name_should_give_each_document() {
+        let test_db_path = Path::new("./adding_documents_with_same_name_should_give_each_document.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let mangas = add_category(&mut connection, &"Mangas".to_string(), &"~/Documents/Mangas".to_string()).unwrap();
+        let oda = add_author(&mut connection, &"Eiichiro Oda".to_string()).unwrap();
+        let one_piece = add_series(&mut connection, &"One Piece".to_string(), &oda.id_).unwrap();
+        let kishimoto = add_author(&mut connection, &"Masashi Kishimoto".to_string()).unwrap();
+        let boruto = add_series(&mut connection, &"Boruto".to_string(), &kishimoto.id_).unwrap();
+        let one_piece_ch_1001 = add_document(&mut connection, &"Chapter 1001".to_string(), &mangas.id_, &Some(oda.id_), &Some(one_piece.id_), &Some(NaiveDate::from_ymd_opt(2021, 1, 18).unwrap().into()), &"Oda/One piece/chapter-1001.cbz".to_string()).unwrap();
+        let boruto_ch_1001 = add_document(&mut connection, &"Chapter 1001".to_string(), &mangas.id_, &Some(kishimoto.id_), &Some(boruto.id_), &Some(NaiveDate::from_ymd_opt(2030, 1, 1).unwrap().into()), &"Kishimoto/Boruto/chapter-1001.cbz".to_string()).unwrap();
+        assert_ne!(one_piece_ch_1001.id_, boruto_ch_1001.id_);
+        assert_eq!(Some(boruto.id_), boruto_ch_1001.series_);
+        assert_eq!(boruto_ch_1001, get_document_by_id(&mut connection, &boruto_ch_1001.id_).unwrap());
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn get_documents_from_author_should_include_every_role() {
+        let test_db_path = Path::new("./get_documents_from_author_should_include_every_role.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let mangas = add_category(&mut connection, &"Mangas".to_string(), &"~/Documents/Mangas".to_string()).unwrap();
+        let ukyo_kodachi = add_author(&mut connection, &"Ukyo Kodachi".to_string()).unwrap();
+        let mikio_ikemoto = add_author(&mut connection, &"Mikio Ikemoto".to_string()).unwrap();
+        let boruto_ch_1 = add_document(&mut connection, &"Chapter 1".to_string(), &mangas.id_, &Some(ukyo_kodachi.id_), &None, &Some(NaiveDate::from_ymd_opt(2016, 5, 9).unwrap().into()), &"Kodachi/Boruto/chapter-1.cbz".to_string()).unwrap();
+        link_contributor_to_document(&mut connection, &boruto_ch_1.id_, &mikio_ikemoto.id_, &ContributorRole::Illustrator).unwrap();
+        assert_eq!(vec![boruto_ch_1.clone()], get_documents_from_author(&mut connection, &mikio_ikemoto.id_).unwrap());
+        assert_eq!(vec![boruto_ch_1.clone()], get_documents_from_author_with_role(&mut connection, &mikio_ikemoto.id_, &ContributorRole::Illustrator).unwrap());
+        assert!(get_documents_from_author_with_role(&mut connection, &mikio_ikemoto.id_, &ContributorRole::Author).unwrap().is_empty());
+        assert_eq!(Some(ukyo_kodachi.id_), boruto_ch_1.author_);
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn getting_documents_from_series_should_order_them_by_series_index() {
+        let test_db_path = Path::new("./getting_documents_from_series_should_order_them_by_series_index.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let books = add_category(&mut connection, &"Books".to_string(), &"~/Documents/Books".to_string()).unwrap();
+        let jrr_tolkien = add_author(&mut connection, &"J.R.R Tolkien".to_string()).unwrap();
+        let the_lord_of_the_ring = add_series(&mut connection, &"The Lord Of The Rings".to_string(), &jrr_tolkien.id_).unwrap();
+        let the_return_of_the_king = add_document(&mut connection, &"The Return of The King".to_string(), &books.id_, &Some(jrr_tolkien.id_), &None, &Some(NaiveDate::from_ymd_opt(1955, 10, 20).unwrap().into()), &"Tolkien/the_return_of_the_king".to_string()).unwrap();
+        let the_two_towers = add_document(&mut connection, &"The Two Towers".to_string(), &books.id_, &Some(jrr_tolkien.id_), &None, &Some(NaiveDate::from_ymd_opt(1954, 11, 11).unwrap().into()), &"Tolkien/the_two_towers.epub".to_string()).unwrap();
+        let the_fellowship_of_the_ring = add_document(&mut connection, &"The Fellowship Of The Ring".to_string(), &books.id_, &Some(jrr_tolkien.id_), &None, &Some(NaiveDate::from_ymd_opt(1954, 6, 29).unwrap().into()), &"Tolkien/the_fellowship_of_the_ring.epub".to_string()).unwrap();
+        let the_adventures_of_tom_bombadil = add_document(&mut connection, &"The Adventures of Tom Bombadil".to_string(), &books.id_, &Some(jrr_tolkien.id_), &None, &Some(NaiveDate::from_ymd_opt(1962, 11, 22).unwrap().into()), &"Tolkien/the_adventures_of_tom_bombadil.epub".to_string()).unwrap();
+        let unnumbered = set_document_series_position(&mut connection, &the_adventures_of_tom_bombadil.id_, &Some(the_lord_of_the_ring.id_), &None, &None).unwrap();
+        let third = set_document_series_position(&mut connection, &the_return_of_the_king.id_, &Some(the_lord_of_the_ring.id_), &Some(3.0), &Some(SeriesNumbering::Volume)).unwrap();
+        let first = set_document_series_position(&mut connection, &the_fellowship_of_the_ring.id_, &Some(the_lord_of_the_ring.id_), &Some(1.0), &Some(SeriesNumbering::Volume)).unwrap();
+        let side_story = set_document_series_position(&mut connection, &the_two_towers.id_, &Some(the_lord_of_the_ring.id_), &Some(1.5), &Some(SeriesNumbering::Volume)).unwrap();
+        assert_eq!(Some(1.5), side_story.series_index_);
+        assert_eq!(Some(SeriesNumbering::Volume), side_story.series_numbering_);
+        assert_eq!(vec![first, side_story, third, unnumbered], get_documents_from_series(&mut connection, &the_lord_of_the_ring.id_).unwrap());
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn adding_document_with_metadata_should_store_it() {
+        let test_db_path = Path::new("./adding_document_with_metadata_should_store_it.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let books = add_category(&mut connection, &"Books".to_string(), &"~/Documents/Books".to_string()).unwrap();
+        let jrr_tolkien = add_author(&mut connection, &"J.R.R Tolkien".to_string()).unwrap();
+        let metadata = DocumentMetadata::new(Some("Bilbo Baggins goes on an unexpected journey".to_string()), Some("en".to_string()), Some("George Allen & Unwin".to_string()), Some(310), Some(2_621_440), Some("application/epub+zip".to_string()), Some("Hobbit, The".to_string()), Some(false));
+        let the_hobbit = add_document_with_metadata(&mut connection, &"The Hobbit".to_string(), &books.id_, &Some(jrr_tolkien.id_), &None, &Some(NaiveDate::from_ymd_opt(1937, 9, 21).unwrap().into()), &"Tolkien/the_hobbit.epub".to_string(), &metadata).unwrap();
+        assert_eq!(metadata, the_hobbit.metadata());
+        assert_eq!(metadata, get_document_by_id(&mut connection, &the_hobbit.id_).unwrap().metadata());
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn updating_document_metadata_should_replace_it() {
+        let test_db_path = Path::new("./updating_document_metadata_should_replace_it.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let books = add_category(&mut connection, &"Books".to_string(), &"~/Documents/Books".to_string()).unwrap();
+        let the_hobbit = add_document(&mut connection, &"The Hobbit".to_string(), &books.id_, &None, &None, &Some(NaiveDate::from_ymd_opt(1937, 9, 21).unwrap().into()), &"Tolkien/the_hobbit.epub".to_string()).unwrap();
+        assert_eq!(DocumentMetadata::default(), the_hobbit.metadata());
+        let metadata = DocumentMetadata { language_ : Some("fr".to_string()), page_count_ : Some(352), ..Default::default() };
+        let updated_hobbit = update_document_metadata(&mut connection, &the_hobbit.id_, &metadata).unwrap();
+        assert_eq!(metadata, updated_hobbit.metadata());
+        assert_eq!(the_hobbit.name_, updated_hobbit.name_);
+        assert!(matches!(update_document_metadata(&mut connection, &(the_hobbit.id_ + 1), &metadata), Err(DbError::NotFound(_))));
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn getting_documents_from_category_should_include_linked_documents() {
+        let test_db_path = Path::new("./getting_documents_from_category_should_include_linked_documents.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let books = add_category(&mut connection, &"Books".to_string(), &"~/Documents/Books".to_string()).unwrap();
+        let mangas = add_category(&mut connection, &"Mangas".to_string(), &"~/Documents/Mangas".to_string()).unwrap();
+        let favourites = add_category(&mut connection, &"Favourites".to_string(), &"~/Documents/Favourites".to_string()).unwrap();
+        let the_hobbit = add_document(&mut connection, &"The Hobbit".to_string(), &books.id_, &None, &None, &Some(NaiveDate::from_ymd_opt(1937, 9, 21).unwrap().into()), &"Tolkien/the_hobbit.epub".to_string()).unwrap();
+        let one_piece_ch_1001 = add_document(&mut connection, &"Chapter 1001".to_string(), &mangas.id_, &None, &None, &Some(NaiveDate::from_ymd_opt(2021, 1, 18).unwrap().into()), &"Oda/One piece/chapter-1001.cbz".to_string()).unwrap();
+        let personal_notes = add_document(&mut connection, &"Notes".to_string(), &favourites.id_, &None, &None, &Some(NaiveDate::from_ymd_opt(2024, 3, 2).unwrap().into()), &"notes.pdf".to_string()).unwrap();
+        link_category_to_document(&mut connection, &one_piece_ch_1001.id_, &favourites.id_).unwrap();
+        // linking a document to its own category should not list it twice
+        link_category_to_document(&mut connection, &personal_notes.id_, &favourites.id_).unwrap();
+        assert_eq!(vec![one_piece_ch_1001.clone(), personal_notes], get_documents_from_category(&mut connection, &favourites.id_).unwrap());
+        assert_eq!(vec![one_piece_ch_1001], get_documents_from_category(&mut connection, &mangas.id_).unwrap());
+        assert_eq!(vec![the_hobbit], get_documents_from_category(&mut connection, &books.id_).unwrap());
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn getting_documents_with_genre_should_include_descendants_on_demand() {
+        let test_db_path = Path::new("./getting_documents_with_genre_should_include_descendants_on_demand.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let books = add_category(&mut connection, &"Books".to_string(), &"~/Documents/Books".to_string()).unwrap();
+        let fantasy = add_genre(&mut connection, &"Fantasy".to_string()).unwrap();
+        let high_fantasy = add_genre(&mut connection, &"High Fantasy".to_string()).unwrap();
+        move_genre(&mut connection, &high_fantasy.id_, &Some(fantasy.id_)).unwrap();
+        let the_hobbit = add_document(&mut connection, &"The Hobbit".to_string(), &books.id_, &None, &None, &Some(NaiveDate::from_ymd_opt(1937, 9, 21).unwrap().into()), &"Tolkien/the_hobbit.epub".to_string()).unwrap();
+        let the_fellowship_of_the_ring = add_document(&mut connection, &"The Fellowship Of The Ring".to_string(), &books.id_, &None, &None, &Some(NaiveDate::from_ymd_opt(1954, 6, 29).unwrap().into()), &"Tolkien/the_fellowship_of_the_ring.epub".to_string()).unwrap();
+        link_genre_to_document(&mut connection, &the_hobbit.id_, &fantasy.id_).unwrap();
+        link_genre_to_document(&mut connection, &the_fellowship_of_the_ring.id_, &high_fantasy.id_).unwrap();
+        // a document in both the parent and the child genre is only listed once
+        link_genre_to_document(&mut connection, &the_hobbit.id_, &high_fantasy.id_).unwrap();
+        assert_eq!(vec![the_hobbit.clone()], get_documents_with_genre(&mut connection, &fantasy.id_, &false).unwrap());
+        assert_eq!(vec![the_hobbit, the_fellowship_of_the_ring], get_documents_with_genre(&mut connection, &fantasy.id_, &true).unwrap());
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn restore_document_should_bring_back_its_links() {
+        let test_db_path = Path::new("./restore_document_should_bring_back_its_links.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let books = add_category(&mut connection, &"Books".to_string(), &"~/Documents/Books".to_string()).unwrap();
+        let the_fellowship_of_the_ring = add_document(&mut connection, &"The Fellowship Of The Ring".to_string(), &books.id_, &None, &None, &Some(NaiveDate::from_ymd_opt(1954, 6, 29).unwrap().into()), &"Tolkien/the_fellowship_of_the_ring.epub".to_string()).unwrap();
+        let banger = add_tag(&mut connection, &"banger".to_string()).unwrap();
+        link_tag_to_document(&mut connection, &the_fellowship_of_the_ring.id_, &banger.id_).unwrap();
+        remove_document(&mut connection, &the_fellowship_of_the_ring.id_).unwrap();
+        assert!(get_documents(&mut connection).unwrap().is_empty());
+        assert!(get_documents_with_tag(&mut connection, &banger.id_, &false).unwrap().is_empty());
+        let trashed_documents = get_trashed_documents(&mut connection).unwrap();
+        assert_eq!(1, trashed_documents.len());
+        assert!(trashed_documents[0].deleted_at_.is_some());
+        assert!(matches!(remove_document(&mut connection, &the_fellowship_of_the_ring.id_), Err(DbError::NotFound(_))));
+        assert_eq!(the_fellowship_of_the_ring, restore_document(&mut connection, &the_fellowship_of_the_ring.id_).unwrap());
+        assert_eq!(vec![the_fellowship_of_the_ring.clone()], get_documents_with_tag(&mut connection, &banger.id_, &false).unwrap());
+        assert!(matches!(restore_document(&mut connection, &the_fellowship_of_the_ring.id_), Err(DbError::NotFound(_))));
+        assert!(matches!(purge_document(&mut connection, &the_fellowship_of_the_ring.id_), Err(DbError::NotFound(_))));
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn purge_trash_should_only_delete_documents_older_than_retention() {
+        let test_db_path = Path::new("./purge_trash_should_only_delete_documents_older_than_retention.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let books = add_category(&mut connection, &"Books".to_string(), &"~/Documents/Books".to_string()).unwrap();
+        let the_hobbit = add_document(&mut connection, &"The Hobbit".to_string(), &books.id_, &None, &None, &Some(NaiveDate::from_ymd_opt(1937, 9, 21).unwrap().into()), &"Tolkien/the_hobbit.epub".to_string()).unwrap();
+        let the_two_towers = add_document(&mut connection, &"The Two Towers".to_string(), &books.id_, &None, &None, &Some(NaiveDate::from_ymd_opt(1954, 11, 11).unwrap().into()), &"Tolkien/the_two_towers.epub".to_string()).unwrap();
+        remove_document(&mut connection, &the_hobbit.id_).unwrap();
+        assert_eq!(0, purge_trash(&mut connection, &Duration::days(30)).unwrap());
+        assert_eq!(1, get_trashed_documents(&mut connection).unwrap().len());
+        assert_eq!(1, purge_trash(&mut connection, &Duration::zero()).unwrap());
+        assert!(get_trashed_documents(&mut connection).unwrap().is_empty());
+        assert_eq!(vec![the_two_towers], get_documents(&mut connection).unwrap());
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn document_paths_should_be_relative_to_their_category() {
+        let test_db_path = Path::new("./document_paths_should_be_relative_to_their_category.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let books_root = Path::new("./document_paths_should_be_relative_to_their_category");
+        create_dir(books_root).unwrap();
+        let books_root = books_root.canonicalize().unwrap();
+        let books = add_category(&mut connection, &"Books".to_string(), &books_root.to_string_lossy().to_string()).unwrap();
+        let the_hobbit = add_document(&mut connection, &"The Hobbit".to_string(), &books.id_, &None, &None, &None, &books_root.join("Tolkien/the_hobbit.epub").to_string_lossy().to_string()).unwrap();
+        assert_eq!("Tolkien/the_hobbit.epub", the_hobbit.path_);
+        assert_eq!(books_root.join("Tolkien/the_hobbit.epub"), resolve_document_path(&mut connection, &the_hobbit.id_).unwrap());
+        let outside_of_books = add_document(&mut connection, &"Notes".to_string(), &books.id_, &None, &None, &None, &"/elsewhere/notes.pdf".to_string());
+        assert!(matches!(outside_of_books, Err(DbError::Conflict(_))));
+        update_category_path(&mut connection, &books.id_, &"/mnt/library/Books".to_string()).unwrap();
+        assert_eq!(PathBuf::from("/mnt/library/Books/Tolkien/the_hobbit.epub"), resolve_document_path(&mut connection, &the_hobbit.id_).unwrap());
+        remove_dir(books_root).unwrap();
+        delete_database(test_db_path).unwrap();
+    }
+}
@@ -0,0 +1,88 @@
+use derive_new::new;
+use diesel::{dsl::delete, insert_into, prelude::Queryable, query_dsl::methods::FilterDsl, ExpressionMethods, RunQueryDsl, SqliteConnection};
+use crate::db_manager::{entities::schema::document_category::dsl::*, DbError};
+
+/// Membership of a document in a category other than the one it is stored in.
+#[derive(Queryable, PartialEq, Debug, new, Clone)]
+pub struct DocumentCategory {
+    pub document_ : i32,
+    pub category_ : i32
+}
+
+pub fn link_category_to_document(connection : &mut SqliteConnection, document_id : &i32, category_id : &i32) -> Result<DocumentCategory, DbError> {
+    return insert_into(document_category)
+        .values((document.eq(document_id), category.eq(category_id)))
+        .get_result::<DocumentCategory>(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not link category {} and document {} to database", category_id, document_id), e));
+}
+
+pub fn unlink_category_to_document(connection : &mut SqliteConnection, document_id : &i32, category_id : &i32) -> Result<(), DbError> {
+    let deleted_rows = delete(document_category).filter(document.eq(document_id)).filter(category.eq(category_id)).execute(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not delete link between document {} and category {} from database", document_id, category_id), e))?;
+    match deleted_rows {
+        1 => Ok(()),
+        _ => Err(DbError::NotFound(format!("Could not find the link between document {} and category {} to delete from database", document_id, category_id)))
+    }
+}
+
+pub fn get_document_category(connection : &mut SqliteConnection, document_id : &i32, category_id : &i32) -> Result<DocumentCategory, DbError> {
+    return document_category.filter(document.eq(document_id)).filter(category.eq(category_id)).first::<DocumentCategory>(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not get link between document {} and category {}", document_id, category_id), e));
+}
+
+pub fn get_document_categories(connection : &mut SqliteConnection, document_id : &i32) -> Result<Vec<DocumentCategory>, DbError> {
+    return document_category.filter(document.eq(document_id)).load::<DocumentCategory>(connection)
+        .map_err(|e| DbError::from_diesel(format!("An error occured while trying to get all categories linked to document {}", document_id), e));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use chrono::NaiveDate;
+
+    use crate::db_manager::{create_database, delete_database, entities::{add_category, add_document, get_document_categories, get_document_category, link_category_to_document, remove_category, unlink_category_to_document, DocumentCategory}, get_connection, DbError};
+
+    #[test]
+    fn linking_category_should_give_newly_created_document_category() {
+        let test_db_path = Path::new("./linking_category_should_give_newly_created_document_category.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let mangas = add_category(&mut connection, &"Mangas".to_string(), &"~/Documents/Mangas".to_string()).unwrap();
+        let favourites = add_category(&mut connection, &"Favourites".to_string(), &"~/Documents/Favourites".to_string()).unwrap();
+        let one_piece_ch_1001 = add_document(&mut connection, &"Chapter 1001".to_string(), &mangas.id_, &None, &None, &Some(NaiveDate::from_ymd_opt(2021, 1, 18).unwrap().into()), &"Oda/One piece/chapter-1001.cbz".to_string()).unwrap();
+        let maybe_document_category = link_category_to_document(&mut connection, &one_piece_ch_1001.id_, &favourites.id_).unwrap();
+        assert_eq!(DocumentCategory::new(one_piece_ch_1001.id_, favourites.id_), maybe_document_category);
+        assert_eq!(vec![maybe_document_category], get_document_categories(&mut connection, &one_piece_ch_1001.id_).unwrap());
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn unlinking_category_should_delete_it() {
+        let test_db_path = Path::new("./unlinking_category_should_delete_it.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let mangas = add_category(&mut connection, &"Mangas".to_string(), &"~/Documents/Mangas".to_string()).unwrap();
+        let favourites = add_category(&mut connection, &"Favourites".to_string(), &"~/Documents/Favourites".to_string()).unwrap();
+        let one_piece_ch_1001 = add_document(&mut connection, &"Chapter 1001".to_string(), &mangas.id_, &None, &None, &Some(NaiveDate::from_ymd_opt(2021, 1, 18).unwrap().into()), &"Oda/One piece/chapter-1001.cbz".to_string()).unwrap();
+        link_category_to_document(&mut connection, &one_piece_ch_1001.id_, &favourites.id_).unwrap();
+        unlink_category_to_document(&mut connection, &one_piece_ch_1001.id_, &favourites.id_).unwrap();
+        assert!(matches!(get_document_category(&mut connection, &one_piece_ch_1001.id_, &favourites.id_), Err(DbError::NotFound(_))));
+        assert!(matches!(unlink_category_to_document(&mut connection, &one_piece_ch_1001.id_, &favourites.id_), Err(DbError::NotFound(_))));
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn remove_linked_category_should_unlink_its_documents() {
+        let test_db_path = Path::new("./remove_linked_category_should_unlink_its_documents.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let mangas = add_category(&mut connection, &"Mangas".to_string(), &"~/Documents/Mangas".to_string()).unwrap();
+        let favourites = add_category(&mut connection, &"Favourites".to_string(), &"~/Documents/Favourites".to_string()).unwrap();
+        let one_piece_ch_1001 = add_document(&mut connection, &"Chapter 1001".to_string(), &mangas.id_, &None, &None, &Some(NaiveDate::from_ymd_opt(2021, 1, 18).unwrap().into()), &"Oda/One piece/chapter-1001.cbz".to_string()).unwrap();
+        link_category_to_document(&mut connection, &one_piece_ch_1001.id_, &favourites.id_).unwrap();
+        remove_category(&mut connection, &favourites.id_).unwrap();
+        assert!(get_document_categories(&mut connection, &one_piece_ch_1001.id_).unwrap().is_empty());
+        delete_database(test_db_path).unwrap();
+    }
+}
@@ -0,0 +1,165 @@
+use std::{fmt, str::FromStr};
+use derive_new::new;
+use diesel::{backend::Backend, deserialize::{self, FromSql, FromSqlRow}, dsl::delete, expression::AsExpression, insert_into, prelude::Queryable, query_dsl::methods::FilterDsl, serialize::{self, IsNull, Output, ToSql}, sql_types::Text, sqlite::Sqlite, ExpressionMethods, RunQueryDsl, SqliteConnection};
+use crate::db_manager::{entities::schema::document_contributor::dsl::*, DbError};
+
+/// Part an author took in a document, stored as lowercase text.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Text)]
+pub enum ContributorRole {
+    Author,
+    Illustrator,
+    Translator,
+    Editor,
+    Narrator
+}
+
+impl ContributorRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContributorRole::Author => "author",
+            ContributorRole::Illustrator => "illustrator",
+            ContributorRole::Translator => "translator",
+            ContributorRole::Editor => "editor",
+            ContributorRole::Narrator => "narrator"
+        }
+    }
+}
+
+impl fmt::Display for ContributorRole {
+    fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for ContributorRole {
+    type Err = String;
+
+    fn from_str(value : &str) -> Result<ContributorRole, String> {
+        match value {
+            "author" => Ok(ContributorRole::Author),
+            "illustrator" => Ok(ContributorRole::Illustrator),
+            "translator" => Ok(ContributorRole::Translator),
+            "editor" => Ok(ContributorRole::Editor),
+            "narrator" => Ok(ContributorRole::Narrator),
+            _ => Err(format!("Unknown contributor role {}", value))
+        }
+    }
+}
+
+impl ToSql<Text, Sqlite> for ContributorRole {
+    fn to_sql<'b>(&'b self, out : &mut Output<'b, '_, Sqlite>) -> serialize::Result {
+        out.set_value(self.as_str());
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Text, Sqlite> for ContributorRole {
+    fn from_sql(bytes : <Sqlite as Backend>::RawValue<'_>) -> deserialize::Result<ContributorRole> {
+        let value = <String as FromSql<Text, Sqlite>>::from_sql(bytes)?;
+        return value.parse::<ContributorRole>().map_err(|e| e.into());
+    }
+}
+
+#[derive(Queryable, PartialEq, Debug, new, Clone)]
+pub struct DocumentContributor {
+    pub document_ : i32,
+    pub author_ : i32,
+    pub role_ : ContributorRole
+}
+
+pub fn link_contributor_to_document(connection : &mut SqliteConnection, document_id : &i32, author_id : &i32, contributor_role : &ContributorRole) -> Result<DocumentContributor, DbError> {
+    return insert_into(document_contributor)
+        .values((document.eq(document_id), author.eq(author_id), role.eq(contributor_role)))
+        .get_result::<DocumentContributor>(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not link author {} as {} of document {} to database", author_id, contributor_role, document_id), e));
+}
+
+pub fn unlink_contributor_to_document(connection : &mut SqliteConnection, document_id : &i32, author_id : &i32, contributor_role : &ContributorRole) -> Result<(), DbError> {
+    let deleted_rows = delete(document_contributor).filter(document.eq(document_id)).filter(author.eq(author_id)).filter(role.eq(contributor_role)).execute(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not delete author {} as {} of document {} from database", author_id, contributor_role, document_id), e))?;
+    match deleted_rows {
+        1 => Ok(()),
+        _ => Err(DbError::NotFound(format!("Could not find author {} as {} of document {} to delete from database", author_id, contributor_role, document_id)))
+    }
+}
+
+pub fn get_document_contributor(connection : &mut SqliteConnection, document_id : &i32, author_id : &i32, contributor_role : &ContributorRole) -> Result<DocumentContributor, DbError> {
+    return document_contributor.filter(document.eq(document_id)).filter(author.eq(author_id)).filter(role.eq(contributor_role)).first::<DocumentContributor>(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not get author {} as {} of document {}", author_id, contributor_role, document_id), e));
+}
+
+pub fn get_document_contributors(connection : &mut SqliteConnection, document_id : &i32) -> Result<Vec<DocumentContributor>, DbError> {
+    return document_contributor.filter(document.eq(document_id)).load::<DocumentContributor>(connection)
+        .map_err(|e| DbError::from_diesel(format!("An error occured while trying to get all contributors of document {}", document_id), e));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use chrono::NaiveDate;
+
+    use crate::db_manager::{create_database, delete_database, entities::{add_author, add_category, add_document, get_document_contributor, get_document_contributors, link_contributor_to_document, remove_author, unlink_contributor_to_document, ContributorRole, DocumentContributor}, get_connection, DbError};
+
+    #[test]
+    fn linking_contributor_should_give_newly_created_contributor() {
+        let test_db_path = Path::new("./linking_contributor_should_give_newly_created_contributor.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let books = add_category(&mut connection, &"Books".to_string(), &"~/Documents/Books".to_string()).unwrap();
+        let the_hobbit = add_document(&mut connection, &"The Hobbit".to_string(), &books.id_, &None, &None, &Some(NaiveDate::from_ymd_opt(1937, 9, 21).unwrap().into()), &"Tolkien/the_hobbit.epub".to_string()).unwrap();
+        let francis_ledoux = add_author(&mut connection, &"Francis Ledoux".to_string()).unwrap();
+        let maybe_translator = link_contributor_to_document(&mut connection, &the_hobbit.id_, &francis_ledoux.id_, &ContributorRole::Translator).unwrap();
+        assert_eq!(DocumentContributor::new(the_hobbit.id_, francis_ledoux.id_, ContributorRole::Translator), maybe_translator);
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn adding_document_with_author_should_link_it_as_author() {
+        let test_db_path = Path::new("./adding_document_with_author_should_link_it_as_author.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let books = add_category(&mut connection, &"Books".to_string(), &"~/Documents/Books".to_string()).unwrap();
+        let jrr_tolkien = add_author(&mut connection, &"J.R.R Tolkien".to_string()).unwrap();
+        let the_hobbit = add_document(&mut connection, &"The Hobbit".to_string(), &books.id_, &Some(jrr_tolkien.id_), &None, &Some(NaiveDate::from_ymd_opt(1937, 9, 21).unwrap().into()), &"Tolkien/the_hobbit.epub".to_string()).unwrap();
+        assert_eq!(vec![DocumentContributor::new(the_hobbit.id_, jrr_tolkien.id_, ContributorRole::Author)], get_document_contributors(&mut connection, &the_hobbit.id_).unwrap());
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn unlinking_contributor_should_delete_it() {
+        let test_db_path = Path::new("./unlinking_contributor_should_delete_it.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let books = add_category(&mut connection, &"Books".to_string(), &"~/Documents/Books".to_string()).unwrap();
+        let the_hobbit = add_document(&mut connection, &"The Hobbit".to_string(), &books.id_, &None, &None, &Some(NaiveDate::from_ymd_opt(1937, 9, 21).unwrap().into()), &"Tolkien/the_hobbit.epub".to_string()).unwrap();
+        let francis_ledoux = add_author(&mut connection, &"Francis Ledoux".to_string()).unwrap();
+        link_contributor_to_document(&mut connection, &the_hobbit.id_, &francis_ledoux.id_, &ContributorRole::Translator).unwrap();
+        unlink_contributor_to_document(&mut connection, &the_hobbit.id_, &francis_ledoux.id_, &ContributorRole::Translator).unwrap();
+        assert!(matches!(get_document_contributor(&mut connection, &the_hobbit.id_, &francis_ledoux.id_, &ContributorRole::Translator), Err(DbError::NotFound(_))));
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn remove_author_should_unlink_its_contributions() {
+        let test_db_path = Path::new("./remove_author_should_unlink_its_contributions.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let books = add_category(&mut connection, &"Books".to_string(), &"~/Documents/Books".to_string()).unwrap();
+        let the_hobbit = add_document(&mut connection, &"The Hobbit".to_string(), &books.id_, &None, &None, &Some(NaiveDate::from_ymd_opt(1937, 9, 21).unwrap().into()), &"Tolkien/the_hobbit.epub".to_string()).unwrap();
+        let francis_ledoux = add_author(&mut connection, &"Francis Ledoux".to_string()).unwrap();
+        link_contributor_to_document(&mut connection, &the_hobbit.id_, &francis_ledoux.id_, &ContributorRole::Translator).unwrap();
+        remove_author(&mut connection, &francis_ledoux.id_).unwrap();
+        assert!(get_document_contributors(&mut connection, &the_hobbit.id_).unwrap().is_empty());
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn contributor_roles_should_round_trip_through_text() {
+        for contributor_role in [ContributorRole::Author, ContributorRole::Illustrator, ContributorRole::Translator, ContributorRole::Editor, ContributorRole::Narrator] {
+            assert_eq!(contributor_role, contributor_role.as_str().parse::<ContributorRole>().unwrap());
+        }
+        assert!("penciller".parse::<ContributorRole>().is_err());
+    }
+}
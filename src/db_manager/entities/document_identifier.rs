@@ -0,0 +1,72 @@
+use derive_new::new;
+use diesel::{dsl::delete, insert_into, prelude::Queryable, ExpressionMethods, QueryDsl, RunQueryDsl, SqliteConnection};
+use crate::db_manager::{entities::{schema::document_identifier::dsl::*, Document}, DbError};
+
+/// Identifier given to a document by its publisher or a catalogue, like an ISBN.
+/// Schemes are lowercase ("isbn", "uuid", "asin"...).
+#[derive(Queryable, PartialEq, Debug, new, Clone)]
+pub struct DocumentIdentifier {
+    pub document_ : i32,
+    pub scheme_ : String,
+    pub value_ : String
+}
+
+pub fn link_identifier_to_document(connection : &mut SqliteConnection, document_id : &i32, identifier_scheme : &String, identifier_value : &String) -> Result<DocumentIdentifier, DbError> {
+    return insert_into(document_identifier)
+        .values((document.eq(document_id), scheme.eq(identifier_scheme), value.eq(identifier_value)))
+        .get_result::<DocumentIdentifier>(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not link identifier {}:{} and document {} to database", identifier_scheme, identifier_value, document_id), e));
+}
+
+pub fn unlink_identifier_to_document(connection : &mut SqliteConnection, document_id : &i32, identifier_scheme : &String, identifier_value : &String) -> Result<(), DbError> {
+    let deleted_rows = delete(document_identifier.filter(document.eq(document_id)).filter(scheme.eq(identifier_scheme)).filter(value.eq(identifier_value))).execute(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not delete identifier {}:{} of document {} from database", identifier_scheme, identifier_value, document_id), e))?;
+    match deleted_rows {
+        1 => Ok(()),
+        _ => Err(DbError::NotFound(format!("Could not find identifier {}:{} of document {} to delete from database", identifier_scheme, identifier_value, document_id)))
+    }
+}
+
+pub fn get_document_identifiers(connection : &mut SqliteConnection, document_id : &i32) -> Result<Vec<DocumentIdentifier>, DbError> {
+    return document_identifier.filter(document.eq(document_id)).order((scheme.asc(), value.asc())).load::<DocumentIdentifier>(connection)
+        .map_err(|e| DbError::from_diesel(format!("An error occured while trying to get all identifiers of document {}", document_id), e));
+}
+
+pub fn get_documents_by_identifier(connection : &mut SqliteConnection, identifier_scheme : &String, identifier_value : &String) -> Result<Vec<Document>, DbError> {
+    use crate::db_manager::entities::schema::document::dsl::{document as all_documents, id as document_id, deleted_at};
+    return all_documents
+        .filter(document_id.eq_any(document_identifier.filter(scheme.eq(identifier_scheme)).filter(value.eq(identifier_value)).select(document)))
+        .filter(deleted_at.is_null())
+        .order(document_id.asc())
+        .load::<Document>(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not get documents with identifier {}:{}", identifier_scheme, identifier_value), e));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::db_manager::{create_database, delete_database, entities::{add_category, add_document, get_document_identifiers, get_documents_by_identifier, link_identifier_to_document, purge_document, remove_document, unlink_identifier_to_document, DocumentIdentifier}, get_connection, DbError};
+
+    #[test]
+    fn documents_should_be_found_by_identifier() {
+        let test_db_path = Path::new("./documents_should_be_found_by_identifier.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let books = add_category(&mut connection, &"Books".to_string(), &"~/Documents/Books".to_string()).unwrap();
+        let the_hobbit = add_document(&mut connection, &"The Hobbit".to_string(), &books.id_, &None, &None, &None, &"Tolkien/the_hobbit.epub".to_string()).unwrap();
+        let isbn = link_identifier_to_document(&mut connection, &the_hobbit.id_, &"isbn".to_string(), &"9780261103344".to_string()).unwrap();
+        assert_eq!(DocumentIdentifier::new(the_hobbit.id_, "isbn".to_string(), "9780261103344".to_string()), isbn);
+        link_identifier_to_document(&mut connection, &the_hobbit.id_, &"asin".to_string(), &"B007978NPG".to_string()).unwrap();
+        assert!(matches!(link_identifier_to_document(&mut connection, &the_hobbit.id_, &"isbn".to_string(), &"9780261103344".to_string()), Err(DbError::Conflict(_))));
+        assert_eq!(vec!["asin".to_string(), "isbn".to_string()], get_document_identifiers(&mut connection, &the_hobbit.id_).unwrap().into_iter().map(|identifier| identifier.scheme_).collect::<Vec<String>>());
+        assert_eq!(vec![the_hobbit.clone()], get_documents_by_identifier(&mut connection, &"isbn".to_string(), &"9780261103344".to_string()).unwrap());
+        unlink_identifier_to_document(&mut connection, &the_hobbit.id_, &"asin".to_string(), &"B007978NPG".to_string()).unwrap();
+        assert!(matches!(unlink_identifier_to_document(&mut connection, &the_hobbit.id_, &"asin".to_string(), &"B007978NPG".to_string()), Err(DbError::NotFound(_))));
+        remove_document(&mut connection, &the_hobbit.id_).unwrap();
+        assert!(get_documents_by_identifier(&mut connection, &"isbn".to_string(), &"9780261103344".to_string()).unwrap().is_empty());
+        purge_document(&mut connection, &the_hobbit.id_).unwrap();
+        assert!(get_document_identifiers(&mut connection, &the_hobbit.id_).unwrap().is_empty());
+        delete_database(test_db_path).unwrap();
+    }
+}
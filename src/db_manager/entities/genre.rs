@@ -1,61 +1,126 @@
 use chrono::NaiveDate;
 use derive_new::new;
-use diesel::{backend::Backend, deserialize::{self, FromSql}, dsl::delete, insert_into, prelude::Queryable, query_dsl::methods::FilterDsl, sql_types::Text, sqlite::Sqlite, ExpressionMethods, RunQueryDsl, SqliteConnection};
-use crate::db_manager::entities::schema::genre::dsl::*;
+use diesel::{backend::Backend, deserialize::{self, FromSql}, dsl::{delete, insert_or_ignore_into, update}, insert_into, prelude::Queryable, sql_types::Text, sqlite::Sqlite, Connection, ExpressionMethods, QueryDsl, RunQueryDsl, SqliteConnection};
+use crate::db_manager::{entities::schema::genre::dsl::*, DbError};
 
 #[derive(Queryable, PartialEq, Debug, new, Clone)]
 pub struct Genre {
     pub id_ : i32,
-    pub name_ : String
+    pub name_ : String,
+    pub parent_ : Option<i32>
 }
 
-pub fn add_genre(connection : &mut SqliteConnection, genre_name : &String) -> Result<Genre, String> {
-    let added_rows = insert_into(genre)
+pub fn add_genre(connection : &mut SqliteConnection, genre_name : &String) -> Result<Genre, DbError> {
+    return insert_into(genre)
         .values(name.eq(genre_name))
-        .execute(connection)
-        .map_err(|e| format!("Could not add genre {} to database : {}", genre_name, e))?;
-    match added_rows {
-        1 => get_genre_by_name(connection, genre_name)
-                .ok_or(format!("Could not find newly created author {} in database", genre_name)),
-        _ => Err(format!("Something wrong happened while adding author {} in database", genre_name))
-    }
+        .get_result::<Genre>(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not add genre {} to database", genre_name), e));
+}
+
+/// Gives the genre with the given name, creating it if it does not exist yet.
+pub fn get_or_create_genre(connection : &mut SqliteConnection, genre_name : &String) -> Result<Genre, DbError> {
+    return match add_genre(connection, genre_name) {
+        Err(DbError::Conflict(_)) => get_genre_by_name(connection, genre_name),
+        added => added
+    };
 }
 
-pub fn remove_genre(connection : &mut SqliteConnection, genre_id : &i32) -> Result<(), String> {
+pub fn remove_genre(connection : &mut SqliteConnection, genre_id : &i32) -> Result<(), DbError> {
     let deleted_rows = delete(genre).filter(id.eq(&genre_id)).execute(connection)
-        .map_err(|e| format!("Could not delete author {} from database : {}", genre_id, e))?;
+        .map_err(|e| DbError::from_diesel(format!("Could not delete genre {} from database", genre_id), e))?;
     match deleted_rows {
         1 => Ok(()),
-        _ => Err(format!("Something wrong happened while deleting author {} from database", genre_id))
+        _ => Err(DbError::NotFound(format!("Could not find genre {} to delete from database", genre_id)))
     }
 }
 
-pub fn get_genres(connection : &mut SqliteConnection) -> Result<Vec<Genre>, String> {
+pub fn get_genres(connection : &mut SqliteConnection) -> Result<Vec<Genre>, DbError> {
     return genre.load::<Genre>(connection)
-    .map_err(|e| format!("An errror occured while getting all genres : {}", e));
+    .map_err(|e| DbError::from_diesel("An errror occured while getting all genres".to_string(), e));
 }
 
-pub fn get_genre_by_id(connection : &mut SqliteConnection, genre_id : &i32) -> Option<Genre> {
-    match genre.filter(id.eq(genre_id)).first::<Genre>(connection) {
-        Ok(g) => Some(g),
-        Err(_) => None
+pub fn get_genre_by_id(connection : &mut SqliteConnection, genre_id : &i32) -> Result<Genre, DbError> {
+    return genre.filter(id.eq(genre_id)).first::<Genre>(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not get genre {}", genre_id), e));
+}
+
+pub fn get_genre_by_name(connection : &mut SqliteConnection, genre_name : &String) -> Result<Genre, DbError> {
+    return genre.filter(name.eq(genre_name)).first::<Genre>(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not get genre {}", genre_name), e));
+}
+
+/// Moves the genre under another one, or back to the top level with None.
+pub fn move_genre(connection : &mut SqliteConnection, genre_id : &i32, new_parent : &Option<i32>) -> Result<Genre, DbError> {
+    return connection.transaction::<Genre, DbError, _>(|connection| {
+        if let Some(parent_id) = new_parent {
+            // the new parent can't be the genre itself or one of its descendants
+            if get_genre_subtree(connection, genre_id)?.iter().any(|descendant| descendant.id_ == *parent_id) {
+                return Err(DbError::Conflict(format!("Could not move genre {} under genre {} as it would create a cycle", genre_id, parent_id)));
+            }
+        }
+        update(genre.filter(id.eq(genre_id)))
+            .set(parent.eq(new_parent))
+            .get_result::<Genre>(connection)
+            .map_err(|e| DbError::from_diesel(format!("Could not move genre {}", genre_id), e))
+    });
+}
+
+/// Gives the genre followed by all of its descendants, level by level.
+pub fn get_genre_subtree(connection : &mut SqliteConnection, genre_id : &i32) -> Result<Vec<Genre>, DbError> {
+    let mut subtree = vec![get_genre_by_id(connection, genre_id)?];
+    let mut level = vec![*genre_id];
+    while !level.is_empty() {
+        let children = genre.filter(parent.eq_any(&level)).load::<Genre>(connection)
+            .map_err(|e| DbError::from_diesel(format!("Could not get descendants of genre {}", genre_id), e))?;
+        let new_children : Vec<Genre> = children.into_iter().filter(|child| !subtree.iter().any(|known| known.id_ == child.id_)).collect();
+        level = new_children.iter().map(|child| child.id_).collect();
+        subtree.extend(new_children);
     }
+    return Ok(subtree);
+}
+
+pub fn rename_genre(connection : &mut SqliteConnection, genre_id : &i32, new_name : &String) -> Result<Genre, DbError> {
+    return update(genre.filter(id.eq(genre_id)))
+        .set(name.eq(new_name))
+        .get_result::<Genre>(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not rename genre {} to {}", genre_id, new_name), e));
 }
 
-pub fn get_genre_by_name(connection : &mut SqliteConnection, genre_name : &String) -> Option<Genre> {
-    match genre.filter(name.eq(genre_name)).first::<Genre>(connection) {
-        Ok(g) => Some(g),
-        Err(_) => None
+/// Moves every document and child of the source genre to the target one, then deletes the source.
+pub fn merge_genres(connection : &mut SqliteConnection, source_id : &i32, target_id : &i32) -> Result<Genre, DbError> {
+    use crate::db_manager::entities::schema::document_genre::dsl::{document_genre, document as linked_document, genre as linked_genre};
+    if source_id == target_id {
+        return Err(DbError::Conflict(format!("Could not merge genre {} into itself", source_id)));
     }
+    return connection.transaction::<Genre, DbError, _>(|connection| {
+        let source = get_genre_by_id(connection, source_id)?;
+        get_genre_by_id(connection, target_id)?;
+        let linked_documents = document_genre.filter(linked_genre.eq(source_id)).select(linked_document).load::<i32>(connection)
+            .map_err(|e| DbError::from_diesel(format!("Could not get documents of genre {}", source_id), e))?;
+        insert_or_ignore_into(document_genre)
+            .values(linked_documents.iter().map(|document_id| (linked_document.eq(document_id), linked_genre.eq(target_id))).collect::<Vec<_>>())
+            .execute(connection)
+            .map_err(|e| DbError::from_diesel(format!("Could not move documents of genre {} to genre {}", source_id, target_id), e))?;
+        // a target below the source takes its place, so adopting the source children can't create a cycle
+        if get_genre_subtree(connection, source_id)?.iter().any(|descendant| descendant.id_ == *target_id) {
+            update(genre.filter(id.eq(target_id))).set(parent.eq(source.parent_)).execute(connection)
+                .map_err(|e| DbError::from_diesel(format!("Could not move genre {}", target_id), e))?;
+        }
+        update(genre.filter(parent.eq(source_id))).set(parent.eq(target_id)).execute(connection)
+            .map_err(|e| DbError::from_diesel(format!("Could not move children of genre {} to genre {}", source_id, target_id), e))?;
+        remove_genre(connection, source_id)?;
+        get_genre_by_id(connection, target_id)
+    });
 }
 
 #[cfg(test)]
 mod tests {
     use std::path::Path;
+    use chrono::NaiveDate;
 
-    use crate::db_manager::{create_database, delete_database, entities::{add_author, get_authors, Author}, get_connection};
+    use crate::db_manager::{create_database, delete_database, entities::{add_author, add_category, add_document, get_authors, get_document_genre, link_genre_to_document, Author}, get_connection, DbError};
 
-    use super::{add_genre, get_genres, remove_genre, get_genre_by_id, get_genre_by_name, Genre};
+    use super::{add_genre, get_genres, get_or_create_genre, remove_genre, get_genre_by_id, get_genre_by_name, get_genre_subtree, merge_genres, move_genre, Genre};
 
     #[test]
     fn adding_genre_should_give_newly_created_genre() {
@@ -63,7 +128,7 @@ mod tests {
         create_database(test_db_path).unwrap();
         let mut connection = get_connection(test_db_path).unwrap();
         let maybe_added_genre = add_genre(&mut connection, &"Sci-Fi".to_string()).unwrap();
-        assert_eq!(Genre::new(1, "Sci-Fi".to_string()), maybe_added_genre);
+        assert_eq!(Genre::new(1, "Sci-Fi".to_string(), None), maybe_added_genre);
         delete_database(test_db_path).unwrap();
     }
 
@@ -99,7 +164,78 @@ mod tests {
         let mut connection = get_connection(test_db_path).unwrap();
         let sci_fi = add_genre(&mut connection, &"Sci-Fi".to_string()).unwrap();
         remove_genre(&mut connection, &sci_fi.id_).unwrap();
-        assert!(get_genre_by_id(&mut connection, &sci_fi.id_).is_none());
+        assert!(matches!(get_genre_by_id(&mut connection, &sci_fi.id_), Err(DbError::NotFound(_))));
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn remove_genre_should_unlink_it_from_documents() {
+        let test_db_path = Path::new("./remove_genre_should_unlink_it_from_documents.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let books = add_category(&mut connection, &"Books".to_string(), &"~/Documents/Books".to_string()).unwrap();
+        let the_hobbit = add_document(&mut connection, &"The Hobbit".to_string(), &books.id_, &None, &None, &Some(NaiveDate::from_ymd_opt(1937, 9, 21).unwrap().into()), &"Tolkien/the_hobbit.epub".to_string()).unwrap();
+        let heroic_fantasy = add_genre(&mut connection, &"Heroic Fantasy".to_string()).unwrap();
+        link_genre_to_document(&mut connection, &the_hobbit.id_, &heroic_fantasy.id_).unwrap();
+        remove_genre(&mut connection, &heroic_fantasy.id_).unwrap();
+        assert!(matches!(get_document_genre(&mut connection, &the_hobbit.id_, &heroic_fantasy.id_), Err(DbError::NotFound(_))));
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn get_or_create_genre_should_reuse_existing_genre() {
+        let test_db_path = Path::new("./get_or_create_genre_should_reuse_existing_genre.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let heroic_fantasy = add_genre(&mut connection, &"Heroic Fantasy".to_string()).unwrap();
+        let sci_fi = get_or_create_genre(&mut connection, &"Science Fiction".to_string()).unwrap();
+        assert_eq!(heroic_fantasy, get_or_create_genre(&mut connection, &"Heroic Fantasy".to_string()).unwrap());
+        assert_eq!(vec![heroic_fantasy, sci_fi], get_genres(&mut connection).unwrap());
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn moving_genre_under_its_descendant_should_fail() {
+        let test_db_path = Path::new("./moving_genre_under_its_descendant_should_fail.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let fantasy = add_genre(&mut connection, &"Fantasy".to_string()).unwrap();
+        let high_fantasy = add_genre(&mut connection, &"High Fantasy".to_string()).unwrap();
+        let high_fantasy = move_genre(&mut connection, &high_fantasy.id_, &Some(fantasy.id_)).unwrap();
+        assert_eq!(vec![fantasy.clone(), high_fantasy.clone()], get_genre_subtree(&mut connection, &fantasy.id_).unwrap());
+        assert!(matches!(move_genre(&mut connection, &fantasy.id_, &Some(high_fantasy.id_)), Err(DbError::Conflict(_))));
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn remove_parent_genre_should_move_children_to_top_level() {
+        let test_db_path = Path::new("./remove_parent_genre_should_move_children_to_top_level.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let fantasy = add_genre(&mut connection, &"Fantasy".to_string()).unwrap();
+        let high_fantasy = add_genre(&mut connection, &"High Fantasy".to_string()).unwrap();
+        move_genre(&mut connection, &high_fantasy.id_, &Some(fantasy.id_)).unwrap();
+        remove_genre(&mut connection, &fantasy.id_).unwrap();
+        assert_eq!(high_fantasy, get_genre_by_id(&mut connection, &high_fantasy.id_).unwrap());
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn merging_genre_into_its_descendant_should_keep_hierarchy() {
+        let test_db_path = Path::new("./merging_genre_into_its_descendant_should_keep_hierarchy.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let fiction = add_genre(&mut connection, &"Fiction".to_string()).unwrap();
+        let sci_fi = add_genre(&mut connection, &"Sci-Fi".to_string()).unwrap();
+        let space_opera = add_genre(&mut connection, &"Space Opera".to_string()).unwrap();
+        let science_fiction = add_genre(&mut connection, &"Science Fiction".to_string()).unwrap();
+        move_genre(&mut connection, &sci_fi.id_, &Some(fiction.id_)).unwrap();
+        move_genre(&mut connection, &space_opera.id_, &Some(sci_fi.id_)).unwrap();
+        move_genre(&mut connection, &science_fiction.id_, &Some(sci_fi.id_)).unwrap();
+        let merged_genre = merge_genres(&mut connection, &sci_fi.id_, &science_fiction.id_).unwrap();
+        assert_eq!(Some(fiction.id_), merged_genre.parent_);
+        assert_eq!(vec![fiction.clone(), merged_genre.clone(), get_genre_by_id(&mut connection, &space_opera.id_).unwrap()], get_genre_subtree(&mut connection, &fiction.id_).unwrap());
+        assert_eq!(Some(science_fiction.id_), get_genre_by_id(&mut connection, &space_opera.id_).unwrap().parent_);
         delete_database(test_db_path).unwrap();
     }
 }
\ No newline at end of file
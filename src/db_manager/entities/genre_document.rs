@@ -1,6 +1,6 @@
 use derive_new::new;
 use diesel::{dsl::delete, insert_into, prelude::Queryable, query_dsl::methods::FilterDsl, Connection, ExpressionMethods, RunQueryDsl, SqliteConnection};
-use crate::db_manager::entities::schema::document_genre::dsl::*;
+use crate::db_manager::{entities::schema::document_genre::dsl::*, DbError};
 
 #[derive(Queryable, PartialEq, Debug, new, Clone)]
 pub struct DocumentGenre {
@@ -8,33 +8,26 @@ pub struct DocumentGenre {
     pub genre_ : i32
 }
 
-pub fn link_genre_to_document(connection : &mut SqliteConnection, document_id: &i32, genre_id : &i32) -> Result<DocumentGenre, String> {
-    let added_rows = insert_into(document_genre)
+pub fn link_genre_to_document(connection : &mut SqliteConnection, document_id: &i32, genre_id : &i32) -> Result<DocumentGenre, DbError> {
+    return insert_into(document_genre)
         .values((document.eq(document_id), (genre.eq(genre_id))))
-        .execute(connection)
-        .map_err(|e| format!("Could not link genre {} and document {} to database : {}", genre_id, document_id, e))?;
-    match added_rows {
-        1 => get_document_genre(connection, document_id, genre_id)
-                .ok_or(format!("Could not find newly created link between document {} and genre {} in database", document_id, genre_id)),
-        _ => Err(format!("Something wrong happened while linking document {} and genre {} in database", document_id, genre_id))
-    }
+        .get_result::<DocumentGenre>(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not link genre {} and document {} to database", genre_id, document_id), e));
 }
 
-pub fn unlink_genre_to_document(connection : &mut SqliteConnection, document_id: &i32, genre_id : &i32) -> Result<(), String> {
+pub fn unlink_genre_to_document(connection : &mut SqliteConnection, document_id: &i32, genre_id : &i32) -> Result<(), DbError> {
     let deleted_rows = delete(document_genre).filter(document.eq(document_id)).filter(genre.eq(genre_id)).execute(connection)
-        .map_err(|e| format!("Could not delete link between document {} and genre {} from database : {}", document_id, genre_id, e))?;
+        .map_err(|e| DbError::from_diesel(format!("Could not delete link between document {} and genre {} from database", document_id, genre_id), e))?;
     match deleted_rows {
         1 => Ok(()),
-        _ => Err(format!("Something wrong happened while deleting the link between document {} and genre {} from database", document_id, genre_id))
+        _ => Err(DbError::NotFound(format!("Could not find the link between document {} and genre {} to delete from database", document_id, genre_id)))
 }
 }
 
 
-pub fn get_document_genre(connection : &mut SqliteConnection, document_id : &i32, genre_id : &i32) -> Option<DocumentGenre> {
-    match document_genre.filter(document.eq(document_id)).filter(genre.eq(genre_id)).first::<DocumentGenre>(connection) {
-        Ok(dg) => Some(dg),
-        Err(_) => None
-    }
+pub fn get_document_genre(connection : &mut SqliteConnection, document_id : &i32, genre_id : &i32) -> Result<DocumentGenre, DbError> {
+    return document_genre.filter(document.eq(document_id)).filter(genre.eq(genre_id)).first::<DocumentGenre>(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not get link between document {} and genre {}", document_id, genre_id), e));
 }
 
 #[cfg(test)]
@@ -43,7 +36,7 @@ mod tests {
 
     use chrono::NaiveDate;
 
-    use crate::db_manager::{create_database, delete_database, entities::{add_category, add_document, add_genre, get_document_genre, link_genre_to_document, unlink_genre_to_document, DocumentGenre}, get_connection};
+    use crate::db_manager::{create_database, delete_database, entities::{add_category, add_document, add_genre, get_document_genre, link_genre_to_document, unlink_genre_to_document, DocumentGenre}, get_connection, DbError};
 
     #[test]
     fn adding_genre_document_should_give_newly_created_document_tag() {
@@ -51,7 +44,7 @@ mod tests {
         create_database(test_db_path).unwrap();
         let mut connection = get_connection(test_db_path).unwrap();
         let books = add_category(&mut connection, &"Books".to_string(), &"~/Documents/Books".to_string()).unwrap();
-        let the_fellowship_of_the_ring = add_document(&mut connection, &"The Fellowship Of The Ring".to_string(), &books.id_, &None, &None, &NaiveDate::from_ymd_opt(1954, 6, 29).unwrap().to_string(), &"Tolkien/the_fellowship_of_the_ring.epub".to_string()).unwrap();
+        let the_fellowship_of_the_ring = add_document(&mut connection, &"The Fellowship Of The Ring".to_string(), &books.id_, &None, &None, &Some(NaiveDate::from_ymd_opt(1954, 6, 29).unwrap().into()), &"Tolkien/the_fellowship_of_the_ring.epub".to_string()).unwrap();
         let heroic_fantasy = add_genre(&mut connection, &"Heroic Fantasy".to_string()).unwrap();
         let maybe_added_genre_document = link_genre_to_document(&mut connection, &the_fellowship_of_the_ring.id_,&heroic_fantasy.id_).unwrap();
         assert_eq!(DocumentGenre::new(the_fellowship_of_the_ring.id_, heroic_fantasy.id_), maybe_added_genre_document);
@@ -64,11 +57,11 @@ mod tests {
         create_database(test_db_path).unwrap();
         let mut connection = get_connection(test_db_path).unwrap();
         let books = add_category(&mut connection, &"Books".to_string(), &"~/Documents/Books".to_string()).unwrap();
-        let the_fellowship_of_the_ring = add_document(&mut connection, &"The Fellowship Of The Ring".to_string(), &books.id_, &None, &None, &NaiveDate::from_ymd_opt(1954, 6, 29).unwrap().to_string(), &"Tolkien/the_fellowship_of_the_ring.epub".to_string()).unwrap();
+        let the_fellowship_of_the_ring = add_document(&mut connection, &"The Fellowship Of The Ring".to_string(), &books.id_, &None, &None, &Some(NaiveDate::from_ymd_opt(1954, 6, 29).unwrap().into()), &"Tolkien/the_fellowship_of_the_ring.epub".to_string()).unwrap();
         let heroic_fantasy = add_genre(&mut connection, &"Heroic Fantasy".to_string()).unwrap();
         link_genre_to_document(&mut connection, &the_fellowship_of_the_ring.id_,&heroic_fantasy.id_).unwrap();
         unlink_genre_to_document(&mut connection, &the_fellowship_of_the_ring.id_, &heroic_fantasy.id_).unwrap();
-        assert!(get_document_genre(&mut connection, &the_fellowship_of_the_ring.id_, &heroic_fantasy.id_).is_none());
+        assert!(matches!(get_document_genre(&mut connection, &the_fellowship_of_the_ring.id_, &heroic_fantasy.id_), Err(DbError::NotFound(_))));
         delete_database(test_db_path).unwrap();
     }
 
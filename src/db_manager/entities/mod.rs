@@ -8,6 +8,10 @@ pub(crate) mod author;
 pub(crate) mod series;
 pub(crate) mod tag;
 pub(crate) mod document_tag;
+pub(crate) mod document_contributor;
+pub(crate) mod document_category;
+pub(crate) mod document_identifier;
+pub(crate) mod change_history;
 pub(crate) mod user;
 pub(crate) mod schema;
 
@@ -20,4 +24,8 @@ pub use author::*;
 pub use series::*;
 pub use tag::*;
 pub use document_tag::*;
+pub use document_contributor::*;
+pub use document_category::*;
+pub use document_identifier::*;
+pub use change_history::*;
 
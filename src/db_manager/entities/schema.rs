@@ -10,7 +10,7 @@ table! {
 table! {
     series (id) {
         id -> Integer,
-        author -> Integer,
+        author -> Nullable<Integer>,
         name -> Text,
     }
 }
@@ -19,6 +19,7 @@ table! {
     tag (id) {
         id -> Integer,
         name -> Text,
+        parent -> Nullable<Integer>,
     }
 }
 
@@ -26,6 +27,7 @@ table! {
     genre (id) {
         id -> Integer,
         name -> Text,
+        parent -> Nullable<Integer>,
     }
 }
 
@@ -34,6 +36,7 @@ table! {
         id -> Integer,
         name -> Text,
         path -> Text,
+        path_template -> Nullable<Text>,
     }
 }
 
@@ -44,8 +47,21 @@ table! {
         category -> Integer,
         author -> Nullable<Integer>,
         series -> Nullable<Integer>,
-        date -> Text,
+        date -> Nullable<Text>,
         path -> Text,
+        series_index -> Nullable<Double>,
+        series_numbering -> Nullable<Text>,
+        description -> Nullable<Text>,
+        language -> Nullable<Text>,
+        publisher -> Nullable<Text>,
+        page_count -> Nullable<Integer>,
+        file_size -> Nullable<BigInt>,
+        mime_type -> Nullable<Text>,
+        sort_title -> Nullable<Text>,
+        deleted_at -> Nullable<Text>,
+        content_hash -> Nullable<Text>,
+        modified_at -> Nullable<Text>,
+        right_to_left -> Nullable<Bool>,
     }
 }
 
@@ -63,6 +79,14 @@ table! {
     }
 }
 
+table! {
+    document_identifier (document, scheme, value) {
+        document -> Integer,
+        scheme -> Text,
+        value -> Text,
+    }
+}
+
 table! {
     document_tag (document, tag) {
         document -> Integer,
@@ -70,6 +94,34 @@ table! {
     }
 }
 
+table! {
+    document_contributor (document, author, role) {
+        document -> Integer,
+        author -> Integer,
+        role -> Text,
+    }
+}
+
+table! {
+    change_author (id) {
+        id -> Integer,
+        username -> Text,
+    }
+}
+
+table! {
+    change_history (id) {
+        id -> Integer,
+        entity -> Text,
+        entity_id -> Integer,
+        field -> Text,
+        old_value -> Nullable<Text>,
+        new_value -> Nullable<Text>,
+        changed_by -> Nullable<Text>,
+        changed_at -> Text,
+    }
+}
+
 table! {
     users (id) {
         id -> Integer,
@@ -79,13 +131,23 @@ table! {
     }
 }
 
+table! {
+    schema_version (version) {
+        version -> Integer,
+        applied_at -> Text,
+    }
+}
+
 joinable!(document -> author (author));
 joinable!(document -> category (category));
 joinable!(document -> series (series));
 joinable!(document_category -> category (category));
 joinable!(document_category -> document (document));
+joinable!(document_contributor -> author (author));
+joinable!(document_contributor -> document (document));
 joinable!(document_genre -> document (document));
 joinable!(document_genre -> genre (genre));
+joinable!(document_identifier -> document (document));
 joinable!(document_tag -> document (document));
 joinable!(document_tag -> tag (tag));
 
@@ -98,6 +160,8 @@ allow_tables_to_appear_in_same_query!(
     category,
     document,
     document_category,
+    document_contributor,
     document_genre,
+    document_identifier,
     document_tag,
 );
@@ -1,72 +1,99 @@
 use derive_new::new;
-use diesel::{dsl::delete, insert_into, prelude::Queryable, query_dsl::methods::FilterDsl, ExpressionMethods, RunQueryDsl, SqliteConnection};
-use crate::db_manager::entities::schema::series::dsl::*;
+use diesel::{dsl::{delete, update}, insert_into, prelude::Queryable, query_dsl::methods::FilterDsl, Connection, ExpressionMethods, RunQueryDsl, SqliteConnection};
+use crate::db_manager::{entities::schema::series::dsl::*, DbError};
 
 #[derive(Queryable, PartialEq, Debug, new, Clone)]
 
 pub struct Series {
     pub id_ : i32,
-    pub author_ : i32,
+    /// Series imported without any known author have none
+    pub author_ : Option<i32>,
     pub name_ : String
 }
 
-pub fn add_series(connection : &mut SqliteConnection, series_name : &String, series_author : &i32) -> Result<Series, String> {
-    let added_rows = insert_into(series)
+pub fn add_series(connection : &mut SqliteConnection, series_name : &String, series_author : &i32) -> Result<Series, DbError> {
+    return insert_into(series)
         .values((name.eq(series_name), author.eq(series_author)))
-        .execute(connection)
-        .map_err(|e| format!("Could not add series {} to database : {}", series_name, e))?;
-    match added_rows {
-        1 => get_series_by_name(connection, series_name)
-                .ok_or(format!("Could not find newly created series {} in database", series_name)),
-        _ => Err(format!("Something wrong happened while adding series {} in database", series_name))
-    }
+        .get_result::<Series>(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not add series {} to database", series_name), e));
+}
+
+/// Gives the series of the author with the given name, creating it if it does not exist yet.
+/// Without author, only a series without author is reused, never the same-name series of some author.
+pub fn get_or_create_series(connection : &mut SqliteConnection, series_name : &String, series_author : &Option<i32>) -> Result<Series, DbError> {
+    let found_series = match series_author {
+        Some(author_id) => series.filter(name.eq(series_name)).filter(author.eq(author_id)).first::<Series>(connection),
+        None => series.filter(name.eq(series_name)).filter(author.is_null()).first::<Series>(connection)
+    };
+    return match found_series {
+        Err(diesel::result::Error::NotFound) => insert_into(series)
+            .values((name.eq(series_name), author.eq(series_author)))
+            .get_result::<Series>(connection)
+            .map_err(|e| DbError::from_diesel(format!("Could not add series {} to database", series_name), e)),
+        found => found.map_err(|e| DbError::from_diesel(format!("Could not get series {}", series_name), e))
+    };
 }
 
-pub fn remove_series(connection : &mut SqliteConnection, series_id : &i32) -> Result<(), String> {
+pub fn remove_series(connection : &mut SqliteConnection, series_id : &i32) -> Result<(), DbError> {
     let deleted_rows = delete(series).filter(id.eq(&series_id)).execute(connection)
-        .map_err(|e| format!("Could not delete series {} from database : {}", series_id, e))?;
+        .map_err(|e| DbError::from_diesel(format!("Could not delete series {} from database", series_id), e))?;
     match deleted_rows {
         1 => Ok(()),
-        _ => Err(format!("Something wrong happened while deleting series {} from database", series_id))
+        _ => Err(DbError::NotFound(format!("Could not find series {} to delete from database", series_id)))
     }
 }
 
-pub fn get_series(connection : &mut SqliteConnection) -> Result<Vec<Series>, String> {
+pub fn get_series(connection : &mut SqliteConnection) -> Result<Vec<Series>, DbError> {
     return series.load::<Series>(connection)
-    .map_err(|e| format!("An errror occured while getting all series : {}", e));
+    .map_err(|e| DbError::from_diesel("An errror occured while getting all series".to_string(), e));
 }
 
-pub fn get_series_by_id(connection : &mut SqliteConnection, series_id : &i32) -> Option<Series> {
-    match series.filter(id.eq(series_id)).first::<Series>(connection) {
-        Ok(s) => Some(s),
-        Err(_) => None
-    }
+pub fn get_series_by_id(connection : &mut SqliteConnection, series_id : &i32) -> Result<Series, DbError> {
+    return series.filter(id.eq(series_id)).first::<Series>(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not get series {}", series_id), e));
 }
 
-pub fn get_series_by_name(connection : &mut SqliteConnection, series_name : &String) -> Option<Series> {
-    match series.filter(name.eq(series_name)).first::<Series>(connection) {
-        Ok(s) => Some(s),
-        Err(_) => None
-    }
+pub fn get_series_by_name(connection : &mut SqliteConnection, series_name : &String) -> Result<Series, DbError> {
+    return series.filter(name.eq(series_name)).first::<Series>(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not get series {}", series_name), e));
+}
+
+pub fn get_series_from_author(connection : &mut SqliteConnection, author_id : &i32) -> Result<Vec<Series>, DbError> {
+    return series.filter(author.eq(author_id)).load::<Series>(connection)
+        .map_err(|e| DbError::from_diesel(format!("An error occured while trying to get all series from author {}", author_id), e));
 }
 
-pub fn get_series_from_author(connection : &mut SqliteConnection, author_id : &i32) -> Option<Vec<Series>> {
-    match series.filter(author.eq(author_id)).load::<Series>(connection) {
-        Ok(s) => match s.len() {
-            0 => None,
-            _ => Some(s)
-        },
-        Err(_) => None
+pub fn rename_series(connection : &mut SqliteConnection, series_id : &i32, new_name : &String) -> Result<Series, DbError> {
+    return update(series.filter(id.eq(series_id)))
+        .set(name.eq(new_name))
+        .get_result::<Series>(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not rename series {} to {}", series_id, new_name), e));
+}
+
+/// Moves every document of the source series to the target one, then deletes the source.
+pub fn merge_series(connection : &mut SqliteConnection, source_id : &i32, target_id : &i32) -> Result<Series, DbError> {
+    use crate::db_manager::entities::schema::document::dsl::{document, series as document_series};
+    if source_id == target_id {
+        return Err(DbError::Conflict(format!("Could not merge series {} into itself", source_id)));
     }
+    return connection.transaction::<Series, DbError, _>(|connection| {
+        get_series_by_id(connection, source_id)?;
+        get_series_by_id(connection, target_id)?;
+        update(document.filter(document_series.eq(source_id))).set(document_series.eq(target_id)).execute(connection)
+            .map_err(|e| DbError::from_diesel(format!("Could not move documents of series {} to series {}", source_id, target_id), e))?;
+        remove_series(connection, source_id)?;
+        get_series_by_id(connection, target_id)
+    });
 }
 
 #[cfg(test)]
 mod tests {
     use std::{fs::remove_file, path::Path};
+    use chrono::NaiveDate;
 
-    use crate::db_manager::{create_database, delete_database, entities::{add_author, get_series_from_author, series}, get_connection};
+    use crate::db_manager::{create_database, delete_database, entities::{add_author, add_category, add_document, get_document_by_id, get_series_from_author, series}, get_connection, DbError};
 
-    use super::{Series, add_series, remove_series, get_series, get_series_by_id, get_series_by_name};
+    use super::{Series, add_series, merge_series, remove_series, rename_series, get_series, get_series_by_id, get_series_by_name};
 
     #[test]
     fn adding_series_should_give_newly_created_series() {
@@ -75,7 +102,7 @@ mod tests {
         let mut connection = get_connection(test_db_path).unwrap();
         let jk_rowling = add_author(&mut connection, &"J.K Rowling".to_string()).unwrap();
         let maybe_added_series = add_series(&mut connection, &"Harry Potter".to_string(), &jk_rowling.id_).unwrap();
-        assert_eq!(Series::new(1, jk_rowling.id_,"Harry Potter".to_string()), maybe_added_series);
+        assert_eq!(Series::new(1, Some(jk_rowling.id_),"Harry Potter".to_string()), maybe_added_series);
         delete_database(test_db_path).unwrap();
     }
 
@@ -116,7 +143,7 @@ mod tests {
         let jk_rowling = add_author(&mut connection, &"J.K Rowling".to_string()).unwrap();
         let harry_potter = add_series(&mut connection, &"Harry Potter".to_string(), &jk_rowling.id_).unwrap();
         remove_series(&mut connection, &harry_potter.id_).unwrap();
-        assert!(get_series_by_id(&mut connection, &harry_potter.id_).is_none());
+        assert!(matches!(get_series_by_id(&mut connection, &harry_potter.id_), Err(DbError::NotFound(_))));
         delete_database(test_db_path).unwrap();
     }
 
@@ -136,4 +163,38 @@ mod tests {
         assert_eq!(from_tolkien, get_series_from_author(&mut connection, &jrr_tolkien.id_).unwrap());
         delete_database(test_db_path).unwrap();
     }
+
+    #[test]
+    fn remove_series_should_unset_it_on_documents() {
+        let test_db_path = Path::new("./remove_series_should_unset_it_on_documents.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let books = add_category(&mut connection, &"Books".to_string(), &"~/Documents/Books".to_string()).unwrap();
+        let jk_rowling = add_author(&mut connection, &"J.K Rowling".to_string()).unwrap();
+        let harry_potter = add_series(&mut connection, &"Harry Potter".to_string(), &jk_rowling.id_).unwrap();
+        let the_philosophers_stone = add_document(&mut connection, &"Harry Potter and the Philosopher's Stone".to_string(), &books.id_, &Some(jk_rowling.id_), &Some(harry_potter.id_), &Some(NaiveDate::from_ymd_opt(1997, 6, 26).unwrap().into()), &"Rowling/the_philosophers_stone.epub".to_string()).unwrap();
+        remove_series(&mut connection, &harry_potter.id_).unwrap();
+        let maybe_the_philosophers_stone = get_document_by_id(&mut connection, &the_philosophers_stone.id_).unwrap();
+        assert_eq!(None, maybe_the_philosophers_stone.series_);
+        assert_eq!(Some(jk_rowling.id_), maybe_the_philosophers_stone.author_);
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn merging_series_should_move_documents_to_target() {
+        let test_db_path = Path::new("./merging_series_should_move_documents_to_target.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let books = add_category(&mut connection, &"Books".to_string(), &"~/Documents/Books".to_string()).unwrap();
+        let jrr_tolkien = add_author(&mut connection, &"J.R.R Tolkien".to_string()).unwrap();
+        let the_lord_of_the_rings = add_series(&mut connection, &"The Lord Of The Rings".to_string(), &jrr_tolkien.id_).unwrap();
+        let lotr = add_series(&mut connection, &"LOTR".to_string(), &jrr_tolkien.id_).unwrap();
+        let the_two_towers = add_document(&mut connection, &"The Two Towers".to_string(), &books.id_, &Some(jrr_tolkien.id_), &Some(lotr.id_), &Some(NaiveDate::from_ymd_opt(1954, 11, 11).unwrap().into()), &"Tolkien/the_two_towers.epub".to_string()).unwrap();
+        let renamed_series = rename_series(&mut connection, &the_lord_of_the_rings.id_, &"The Lord of the Rings".to_string()).unwrap();
+        assert_eq!(Series::new(the_lord_of_the_rings.id_, Some(jrr_tolkien.id_), "The Lord of the Rings".to_string()), renamed_series);
+        assert_eq!(renamed_series, merge_series(&mut connection, &lotr.id_, &the_lord_of_the_rings.id_).unwrap());
+        assert_eq!(Some(the_lord_of_the_rings.id_), get_document_by_id(&mut connection, &the_two_towers.id_).unwrap().series_);
+        assert_eq!(vec![renamed_series], get_series(&mut connection).unwrap());
+        delete_database(test_db_path).unwrap();
+    }
 }
\ No newline at end of file
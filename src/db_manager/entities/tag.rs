@@ -1,58 +1,123 @@
 use derive_new::new;
-use diesel::{dsl::delete, insert_into, prelude::Queryable, query_dsl::methods::FilterDsl, ExpressionMethods, RunQueryDsl, SqliteConnection};
-use crate::db_manager::entities::schema::tag::dsl::*;
+use diesel::{dsl::{delete, insert_or_ignore_into, update}, insert_into, prelude::Queryable, Connection, ExpressionMethods, QueryDsl, RunQueryDsl, SqliteConnection};
+use crate::db_manager::{entities::schema::tag::dsl::*, DbError};
 
 #[derive(Queryable, PartialEq, Debug, new, Clone)]
 pub struct Tag {
     pub id_ : i32,
-    pub name_ : String
+    pub name_ : String,
+    pub parent_ : Option<i32>
 }
 
-pub fn add_tag(connection : &mut SqliteConnection, tag_name : &String) -> Result<Tag, String> {
-    let added_rows = insert_into(tag)
+pub fn add_tag(connection : &mut SqliteConnection, tag_name : &String) -> Result<Tag, DbError> {
+    return insert_into(tag)
         .values(name.eq(tag_name))
-        .execute(connection)
-        .map_err(|e| format!("Could not add tag {} to database : {}", tag_name, e))?;
-    match added_rows {
-        1 => get_tag_by_name(connection, tag_name)
-                .ok_or(format!("Could not find newly created tag {} in database", tag_name)),
-        _ => Err(format!("Something wrong happened while adding tag {} in database", tag_name))
-    }
+        .get_result::<Tag>(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not add tag {} to database", tag_name), e));
+}
+
+/// Gives the tag with the given name, creating it if it does not exist yet.
+pub fn get_or_create_tag(connection : &mut SqliteConnection, tag_name : &String) -> Result<Tag, DbError> {
+    return match add_tag(connection, tag_name) {
+        Err(DbError::Conflict(_)) => get_tag_by_name(connection, tag_name),
+        added => added
+    };
 }
 
-pub fn remove_tag(connection : &mut SqliteConnection, tag_id : &i32) -> Result<(), String> {
+pub fn remove_tag(connection : &mut SqliteConnection, tag_id : &i32) -> Result<(), DbError> {
     let deleted_rows = delete(tag).filter(id.eq(&tag_id)).execute(connection)
-        .map_err(|e| format!("Could not delete tag {} from database : {}", tag_id, e))?;
+        .map_err(|e| DbError::from_diesel(format!("Could not delete tag {} from database", tag_id), e))?;
     match deleted_rows {
         1 => Ok(()),
-        _ => Err(format!("Something wrong happened while deleting tag {} from database", tag_id))
+        _ => Err(DbError::NotFound(format!("Could not find tag {} to delete from database", tag_id)))
     }
 }
 
-pub fn get_tags(connection : &mut SqliteConnection) -> Result<Vec<Tag>, String> {
+pub fn get_tags(connection : &mut SqliteConnection) -> Result<Vec<Tag>, DbError> {
     return tag.load::<Tag>(connection)
-    .map_err(|e| format!("An errror occured while getting all tags : {}", e));
+    .map_err(|e| DbError::from_diesel("An errror occured while getting all tags".to_string(), e));
+}
+
+pub fn get_tag_by_id(connection : &mut SqliteConnection, tag_id : &i32) -> Result<Tag, DbError> {
+    return tag.filter(id.eq(tag_id)).first::<Tag>(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not get tag {}", tag_id), e));
+}
+
+pub fn get_tag_by_name(connection : &mut SqliteConnection, tag_name : &String) -> Result<Tag, DbError> {
+    return tag.filter(name.eq(tag_name)).first::<Tag>(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not get tag {}", tag_name), e));
+}
+
+/// Moves the tag under another one, or back to the top level with None.
+pub fn move_tag(connection : &mut SqliteConnection, tag_id : &i32, new_parent : &Option<i32>) -> Result<Tag, DbError> {
+    return connection.transaction::<Tag, DbError, _>(|connection| {
+        if let Some(parent_id) = new_parent {
+            // the new parent can't be the tag itself or one of its descendants
+            if get_tag_subtree(connection, tag_id)?.iter().any(|descendant| descendant.id_ == *parent_id) {
+                return Err(DbError::Conflict(format!("Could not move tag {} under tag {} as it would create a cycle", tag_id, parent_id)));
+            }
+        }
+        update(tag.filter(id.eq(tag_id)))
+            .set(parent.eq(new_parent))
+            .get_result::<Tag>(connection)
+            .map_err(|e| DbError::from_diesel(format!("Could not move tag {}", tag_id), e))
+    });
 }
 
-pub fn get_tag_by_id(connection : &mut SqliteConnection, tag_id : &i32) -> Option<Tag> {
-    match tag.filter(id.eq(tag_id)).first::<Tag>(connection) {
-        Ok(t) => Some(t),
-        Err(_) => None
+/// Gives the tag followed by all of its descendants, level by level.
+pub fn get_tag_subtree(connection : &mut SqliteConnection, tag_id : &i32) -> Result<Vec<Tag>, DbError> {
+    let mut subtree = vec![get_tag_by_id(connection, tag_id)?];
+    let mut level = vec![*tag_id];
+    while !level.is_empty() {
+        let children = tag.filter(parent.eq_any(&level)).load::<Tag>(connection)
+            .map_err(|e| DbError::from_diesel(format!("Could not get descendants of tag {}", tag_id), e))?;
+        let new_children : Vec<Tag> = children.into_iter().filter(|child| !subtree.iter().any(|known| known.id_ == child.id_)).collect();
+        level = new_children.iter().map(|child| child.id_).collect();
+        subtree.extend(new_children);
     }
+    return Ok(subtree);
 }
 
-pub fn get_tag_by_name(connection : &mut SqliteConnection, tag_name : &String) -> Option<Tag> {
-    match tag.filter(name.eq(tag_name)).first::<Tag>(connection) {
-        Ok(t) => Some(t),
-        Err(_) => None
+pub fn rename_tag(connection : &mut SqliteConnection, tag_id : &i32, new_name : &String) -> Result<Tag, DbError> {
+    return update(tag.filter(id.eq(tag_id)))
+        .set(name.eq(new_name))
+        .get_result::<Tag>(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not rename tag {} to {}", tag_id, new_name), e));
+}
+
+/// Moves every document and child of the source tag to the target one, then deletes the source.
+pub fn merge_tags(connection : &mut SqliteConnection, source_id : &i32, target_id : &i32) -> Result<Tag, DbError> {
+    use crate::db_manager::entities::schema::document_tag::dsl::{document_tag, document as linked_document, tag as linked_tag};
+    if source_id == target_id {
+        return Err(DbError::Conflict(format!("Could not merge tag {} into itself", source_id)));
     }
+    return connection.transaction::<Tag, DbError, _>(|connection| {
+        let source = get_tag_by_id(connection, source_id)?;
+        get_tag_by_id(connection, target_id)?;
+        let linked_documents = document_tag.filter(linked_tag.eq(source_id)).select(linked_document).load::<i32>(connection)
+            .map_err(|e| DbError::from_diesel(format!("Could not get documents of tag {}", source_id), e))?;
+        insert_or_ignore_into(document_tag)
+            .values(linked_documents.iter().map(|document_id| (linked_document.eq(document_id), linked_tag.eq(target_id))).collect::<Vec<_>>())
+            .execute(connection)
+            .map_err(|e| DbError::from_diesel(format!("Could not move documents of tag {} to tag {}", source_id, target_id), e))?;
+        // a target below the source takes its place, so adopting the source children can't create a cycle
+        if get_tag_subtree(connection, source_id)?.iter().any(|descendant| descendant.id_ == *target_id) {
+            update(tag.filter(id.eq(target_id))).set(parent.eq(source.parent_)).execute(connection)
+                .map_err(|e| DbError::from_diesel(format!("Could not move tag {}", target_id), e))?;
+        }
+        update(tag.filter(parent.eq(source_id))).set(parent.eq(target_id)).execute(connection)
+            .map_err(|e| DbError::from_diesel(format!("Could not move children of tag {} to tag {}", source_id, target_id), e))?;
+        remove_tag(connection, source_id)?;
+        get_tag_by_id(connection, target_id)
+    });
 }
 
 #[cfg(test)]
 mod tests {
     use std::path::Path;
+    use chrono::NaiveDate;
 
-    use crate::db_manager::{create_database, delete_database, entities::{add_tag, get_tag_by_id, get_tags, remove_tag, Tag}, get_connection};
+    use crate::db_manager::{create_database, delete_database, entities::{add_category, add_document, add_tag, get_document_tag, get_or_create_tag, get_documents_with_tag, get_tag_by_id, get_tag_subtree, get_tags, link_tag_to_document, merge_tags, move_tag, remove_tag, rename_tag, Tag}, get_connection, DbError};
 
     #[test]
     fn adding_tag_should_give_newly_created_tag() {
@@ -60,7 +125,7 @@ mod tests {
         create_database(test_db_path).unwrap();
         let mut connection = get_connection(test_db_path).unwrap();
         let maybe_added_tag = add_tag(&mut connection, &"favorites".to_string()).unwrap();
-        assert_eq!(Tag::new(1, "favorites".to_string()), maybe_added_tag);
+        assert_eq!(Tag::new(1, "favorites".to_string(), None), maybe_added_tag);
         delete_database(test_db_path).unwrap();
 
     }
@@ -98,7 +163,102 @@ mod tests {
         let mut connection = get_connection(test_db_path).unwrap();
         let favorites = add_tag(&mut connection, &"favorites".to_string()).unwrap();
         remove_tag(&mut connection, &favorites.id_).unwrap();
-        assert!(get_tag_by_id(&mut connection, &favorites.id_).is_none());
+        assert!(matches!(get_tag_by_id(&mut connection, &favorites.id_), Err(DbError::NotFound(_))));
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn remove_tag_should_unlink_it_from_documents() {
+        let test_db_path = Path::new("./remove_tag_should_unlink_it_from_documents.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let mangas = add_category(&mut connection, &"Mangas".to_string(), &"~/Documents/Mangas".to_string()).unwrap();
+        let one_piece_ch_1001 = add_document(&mut connection, &"Chapter 1001".to_string(), &mangas.id_, &None, &None, &Some(NaiveDate::from_ymd_opt(2021, 1, 18).unwrap().into()), &"Oda/One piece/chapter-1001.cbz".to_string()).unwrap();
+        let chapters = add_tag(&mut connection, &"manga chapters".to_string()).unwrap();
+        link_tag_to_document(&mut connection, &one_piece_ch_1001.id_, &chapters.id_).unwrap();
+        remove_tag(&mut connection, &chapters.id_).unwrap();
+        assert!(matches!(get_document_tag(&mut connection, &one_piece_ch_1001.id_, &chapters.id_), Err(DbError::NotFound(_))));
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn get_or_create_tag_should_reuse_existing_tag() {
+        let test_db_path = Path::new("./get_or_create_tag_should_reuse_existing_tag.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let favorites = add_tag(&mut connection, &"favorites".to_string()).unwrap();
+        let to_read = get_or_create_tag(&mut connection, &"to read".to_string()).unwrap();
+        assert_eq!(favorites, get_or_create_tag(&mut connection, &"favorites".to_string()).unwrap());
+        assert_eq!(vec![favorites, to_read], get_tags(&mut connection).unwrap());
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn moving_tag_should_build_its_subtree() {
+        let test_db_path = Path::new("./moving_tag_should_build_its_subtree.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let to_read = add_tag(&mut connection, &"to read".to_string()).unwrap();
+        let this_year = add_tag(&mut connection, &"this year".to_string()).unwrap();
+        let this_summer = add_tag(&mut connection, &"this summer".to_string()).unwrap();
+        let this_year = move_tag(&mut connection, &this_year.id_, &Some(to_read.id_)).unwrap();
+        let this_summer = move_tag(&mut connection, &this_summer.id_, &Some(this_year.id_)).unwrap();
+        assert_eq!(Some(to_read.id_), this_year.parent_);
+        assert_eq!(vec![to_read.clone(), this_year.clone(), this_summer.clone()], get_tag_subtree(&mut connection, &to_read.id_).unwrap());
+        assert_eq!(vec![this_summer.clone()], get_tag_subtree(&mut connection, &this_summer.id_).unwrap());
+        let this_summer = move_tag(&mut connection, &this_summer.id_, &None).unwrap();
+        assert_eq!(None, this_summer.parent_);
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn moving_tag_under_its_descendant_should_fail() {
+        let test_db_path = Path::new("./moving_tag_under_its_descendant_should_fail.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let to_read = add_tag(&mut connection, &"to read".to_string()).unwrap();
+        let this_year = add_tag(&mut connection, &"this year".to_string()).unwrap();
+        move_tag(&mut connection, &this_year.id_, &Some(to_read.id_)).unwrap();
+        assert!(matches!(move_tag(&mut connection, &to_read.id_, &Some(this_year.id_)), Err(DbError::Conflict(_))));
+        assert!(matches!(move_tag(&mut connection, &to_read.id_, &Some(to_read.id_)), Err(DbError::Conflict(_))));
+        assert!(matches!(move_tag(&mut connection, &(this_year.id_ + 1), &None), Err(DbError::NotFound(_))));
+        assert_eq!(None, get_tag_by_id(&mut connection, &to_read.id_).unwrap().parent_);
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn merging_tags_should_move_documents_and_children_to_target() {
+        let test_db_path = Path::new("./merging_tags_should_move_documents_and_children_to_target.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let mangas = add_category(&mut connection, &"Mangas".to_string(), &"~/Documents/Mangas".to_string()).unwrap();
+        let one_piece_ch_1000 = add_document(&mut connection, &"Chapter 1000".to_string(), &mangas.id_, &None, &None, &Some(NaiveDate::from_ymd_opt(2021, 1, 4).unwrap().into()), &"Oda/One piece/chapter-1000.cbz".to_string()).unwrap();
+        let one_piece_ch_1001 = add_document(&mut connection, &"Chapter 1001".to_string(), &mangas.id_, &None, &None, &Some(NaiveDate::from_ymd_opt(2021, 1, 18).unwrap().into()), &"Oda/One piece/chapter-1001.cbz".to_string()).unwrap();
+        let banger = add_tag(&mut connection, &"banger".to_string()).unwrap();
+        let bangers = add_tag(&mut connection, &"bangers".to_string()).unwrap();
+        let re_read = add_tag(&mut connection, &"re-read".to_string()).unwrap();
+        move_tag(&mut connection, &re_read.id_, &Some(bangers.id_)).unwrap();
+        link_tag_to_document(&mut connection, &one_piece_ch_1000.id_, &banger.id_).unwrap();
+        link_tag_to_document(&mut connection, &one_piece_ch_1000.id_, &bangers.id_).unwrap();
+        link_tag_to_document(&mut connection, &one_piece_ch_1001.id_, &bangers.id_).unwrap();
+        let merged_tag = merge_tags(&mut connection, &bangers.id_, &banger.id_).unwrap();
+        assert_eq!(banger, merged_tag);
+        assert_eq!(vec![one_piece_ch_1000, one_piece_ch_1001], get_documents_with_tag(&mut connection, &banger.id_, &false).unwrap());
+        assert_eq!(Some(banger.id_), get_tag_by_id(&mut connection, &re_read.id_).unwrap().parent_);
+        assert!(matches!(get_tag_by_id(&mut connection, &bangers.id_), Err(DbError::NotFound(_))));
+        assert!(matches!(merge_tags(&mut connection, &banger.id_, &banger.id_), Err(DbError::Conflict(_))));
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn renaming_tag_to_existing_name_should_fail() {
+        let test_db_path = Path::new("./renaming_tag_to_existing_name_should_fail.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let banger = add_tag(&mut connection, &"banger".to_string()).unwrap();
+        let bangers = add_tag(&mut connection, &"bangers".to_string()).unwrap();
+        assert!(matches!(rename_tag(&mut connection, &bangers.id_, &"banger".to_string()), Err(DbError::Conflict(_))));
+        assert_eq!(Tag::new(banger.id_, "masterpiece".to_string(), None), rename_tag(&mut connection, &banger.id_, &"masterpiece".to_string()).unwrap());
         delete_database(test_db_path).unwrap();
     }
 }
\ No newline at end of file
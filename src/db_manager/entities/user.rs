@@ -1,6 +1,6 @@
 use derive_new::new;
 use diesel::{dsl::delete, insert_into, prelude::Queryable, query_dsl::methods::FilterDsl, ExpressionMethods, RunQueryDsl, SqliteConnection};
-use crate::db_manager::entities::schema::users::dsl::*;
+use crate::db_manager::{entities::schema::users::dsl::*, DbError};
 use rusqlite::params;
 use std::path::Path;
 use serde::{Deserialize, Serialize};
@@ -14,7 +14,7 @@ pub struct User {
     pub password_ : String,
 }
 
-pub fn get_users(connection : &mut SqliteConnection) -> Result<Vec<User>, String> {
+pub fn get_users(connection : &mut SqliteConnection) -> Result<Vec<User>, DbError> {
     // if (fields.len() == 0) {
     //     return Err("No fields specified".to_string());
     // }
@@ -29,7 +29,7 @@ pub fn get_users(connection : &mut SqliteConnection) -> Result<Vec<User>, String
     // println!("{}", query);
 
     return users.load::<User>(connection)
-        .map_err(|e| format!("An errror occured while getting all users : {}", e));
+        .map_err(|e| DbError::from_diesel("An errror occured while getting all users".to_string(), e));
 
     // let mut statement = connection.prepare(&*query)
     //     .map_err(|e| format!("Could not prepare statement to get users : {}", e))?;
@@ -45,34 +45,30 @@ pub fn get_users(connection : &mut SqliteConnection) -> Result<Vec<User>, String
     // Ok(result)
 }
 
-pub fn get_user_by_id(connection : &mut SqliteConnection, user_id : i32) -> Result<Vec<User>, String> {
+pub fn get_user_by_id(connection : &mut SqliteConnection, user_id : i32) -> Result<Vec<User>, DbError> {
     return users.filter(id.eq(user_id))
         .load::<User>(connection)
-        .map_err(|e| format!("An errror occured while getting user {:?} : {}", user_id, e));
+        .map_err(|e| DbError::from_diesel(format!("An errror occured while getting user {:?}", user_id), e));
 }
 
-pub fn get_user_by_username(connection : &mut SqliteConnection, filter_username : &str) -> Result<Vec<User>, String> {
+pub fn get_user_by_username(connection : &mut SqliteConnection, filter_username : &str) -> Result<Vec<User>, DbError> {
     return users.filter(username.eq(filter_username))
         .load::<User>(connection)
-        .map_err(|e| format!("An errror occured while getting user {:?} : {}", filter_username, e));
+        .map_err(|e| DbError::from_diesel(format!("An errror occured while getting user {:?}", filter_username), e));
 }
 
-pub fn add_user(connection : &mut SqliteConnection, user: User) -> Result<User, String> {
-    let added_rows = insert_into(users)
-        .values((username.eq(user.username_.clone()), email.eq(user.email_), password.eq(user.password_)))
-        .execute(connection)
-        .map_err(|e| format!("Could not add user {} to database : {}", "username", e))?;
-    match added_rows {
-        1 => Ok(get_user_by_username(connection, &user.username_.clone())?.pop().unwrap()),
-        _ => Err(format!("Something wrong happened while adding user {} to database", "username"))
-    }
+pub fn add_user(connection : &mut SqliteConnection, user: User) -> Result<User, DbError> {
+    return insert_into(users)
+        .values((username.eq(&user.username_), email.eq(user.email_), password.eq(user.password_)))
+        .get_result::<User>(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not add user {} to database", user.username_), e));
 }
 
-pub fn remove_users(connection : &mut SqliteConnection) -> Result<(), String> {
+pub fn remove_users(connection : &mut SqliteConnection) -> Result<(), DbError> {
     let deleted_rows = delete(users).filter(id.eq(1)).execute(connection)
-        .map_err(|e| format!("Could not delete user {} from database : {}", 1, e))?;
+        .map_err(|e| DbError::from_diesel(format!("Could not delete user {} from database", 1), e))?;
     match deleted_rows {
         1 => Ok(()),
-        _ => Err(format!("Something wrong happened while deleting user {} from database", 1))
+        _ => Err(DbError::NotFound(format!("Could not find user {} to delete from database", 1)))
     }
 }
\ No newline at end of file
@@ -0,0 +1,72 @@
+use std::fmt;
+use diesel::result::{DatabaseErrorKind, Error};
+
+/// Error returned by every db_manager function, so callers can tell what kind of failure happened.
+#[derive(PartialEq, Debug, Clone)]
+pub enum DbError {
+    /// The requested row does not exist
+    NotFound(String),
+    /// The operation would break a unique constraint, or the database is not in a compatible state
+    Conflict(String),
+    /// The operation references a missing row, or would leave rows referencing a deleted one
+    ForeignKey(String),
+    /// The database could not be reached, read or written
+    Io(String)
+}
+
+impl DbError {
+    /// Classifies a diesel error and prefixes its message with what was being attempted.
+    pub fn from_diesel(context : String, error : Error) -> DbError {
+        let message = format!("{} : {}", context, error);
+        match error {
+            Error::NotFound => DbError::NotFound(message),
+            Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => DbError::Conflict(message),
+            Error::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _) => DbError::ForeignKey(message),
+            // SQLite reports ON DELETE RESTRICT failures without the foreign key extended code
+            Error::DatabaseError(_, info) if info.message().starts_with("FOREIGN KEY constraint failed") => DbError::ForeignKey(message),
+            _ => DbError::Io(message)
+        }
+    }
+
+    pub fn message(&self) -> &String {
+        match self {
+            DbError::NotFound(m) | DbError::Conflict(m) | DbError::ForeignKey(m) | DbError::Io(m) => m
+        }
+    }
+}
+
+impl From<Error> for DbError {
+    fn from(error : Error) -> DbError {
+        DbError::from_diesel("Database error".to_string(), error)
+    }
+}
+
+impl fmt::Display for DbError {
+    fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+impl std::error::Error for DbError {}
+
+#[cfg(test)]
+mod tests {
+    use diesel::result::{DatabaseErrorKind, Error};
+
+    use super::DbError;
+
+    #[test]
+    fn diesel_errors_should_be_classified() {
+        assert!(matches!(DbError::from_diesel("Could not get author 1".to_string(), Error::NotFound), DbError::NotFound(_)));
+        assert!(matches!(DbError::from_diesel("Could not add tag".to_string(), Error::DatabaseError(DatabaseErrorKind::UniqueViolation, Box::new("UNIQUE constraint failed".to_string()))), DbError::Conflict(_)));
+        assert!(matches!(DbError::from_diesel("Could not delete author".to_string(), Error::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, Box::new("FOREIGN KEY constraint failed".to_string()))), DbError::ForeignKey(_)));
+        assert!(matches!(DbError::from_diesel("Could not delete category".to_string(), Error::DatabaseError(DatabaseErrorKind::Unknown, Box::new("FOREIGN KEY constraint failed".to_string()))), DbError::ForeignKey(_)));
+        assert!(matches!(DbError::from_diesel("Could not get tags".to_string(), Error::BrokenTransactionManager), DbError::Io(_)));
+    }
+
+    #[test]
+    fn error_message_should_keep_context() {
+        let error = DbError::from_diesel("Could not get author 1".to_string(), Error::NotFound);
+        assert_eq!("Could not get author 1 : Record not found", error.to_string());
+    }
+}
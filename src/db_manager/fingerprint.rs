@@ -0,0 +1,93 @@
+use std::{fs::File, io, path::Path, time::SystemTime};
+use chrono::{DateTime, SecondsFormat, Utc};
+use derive_new::new;
+use diesel::{dsl::update, ExpressionMethods, QueryDsl, RunQueryDsl, SqliteConnection};
+use sha2::{Digest, Sha256};
+use crate::db_manager::{entities::{schema::document::dsl::*, Document}, DbError};
+
+/// What the file of a document held when it was last read.
+#[derive(PartialEq, Eq, Debug, new, Clone)]
+pub struct FileFingerprint {
+    /// SHA-256 of the file content, as lowercase hexadecimal
+    pub hash_ : String,
+    pub size_ : i64,
+    /// Modification time reported by the file system, in UTC to the second
+    pub modified_at_ : String
+}
+
+/// Formats a file modification time the way fingerprints store it, so both can be compared as text.
+pub fn format_modified_at(modified : SystemTime) -> String {
+    let last_modified : DateTime<Utc> = modified.into();
+    return last_modified.to_rfc3339_opts(SecondsFormat::Secs, true);
+}
+
+/// Hashes the file by streaming it, so big archives don't have to fit in memory.
+pub fn compute_fingerprint(file_path : &Path) -> Result<FileFingerprint, DbError> {
+    let io_error = |e : io::Error| DbError::Io(format!("Could not fingerprint file {} : {}", file_path.display(), e));
+    let mut file = File::open(file_path).map_err(io_error)?;
+    let file_metadata = file.metadata().map_err(io_error)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher).map_err(io_error)?;
+    let last_modified = format_modified_at(file_metadata.modified().map_err(io_error)?);
+    return Ok(FileFingerprint::new(format!("{:x}", hasher.finalize()), file_metadata.len() as i64, last_modified));
+}
+
+/// Records the fingerprint on the document, replacing its file size.
+pub fn set_document_fingerprint(connection : &mut SqliteConnection, document_id : &i32, fingerprint : &FileFingerprint) -> Result<Document, DbError> {
+    return update(document.filter(id.eq(document_id)))
+        .set((content_hash.eq(&fingerprint.hash_), 
+            file_size.eq(fingerprint.size_), 
+            modified_at.eq(&fingerprint.modified_at_)))
+        .get_result::<Document>(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not set fingerprint of document {}", document_id), e));
+}
+
+/// Documents whose file had the given content when last fingerprinted, oldest first.
+pub fn get_documents_by_hash(connection : &mut SqliteConnection, hash : &String) -> Result<Vec<Document>, DbError> {
+    return document.filter(content_hash.eq(hash)).filter(deleted_at.is_null()).order(id.asc()).load::<Document>(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not get documents with hash {}", hash), e));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::{remove_file, write}, path::Path};
+
+    use crate::db_manager::{create_database, delete_database, entities::{add_category, add_document, get_document_by_id, remove_document}, get_connection, DbError};
+
+    use super::{compute_fingerprint, get_documents_by_hash, set_document_fingerprint};
+
+    #[test]
+    fn fingerprinting_file_should_give_its_sha256_and_size() {
+        let file_path = Path::new("./fingerprinting_file_should_give_its_sha256_and_size.txt");
+        write(file_path, "abc").unwrap();
+        let fingerprint = compute_fingerprint(file_path).unwrap();
+        assert_eq!("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad", fingerprint.hash_);
+        assert_eq!(3, fingerprint.size_);
+        assert!(fingerprint.modified_at_.ends_with('Z'));
+        remove_file(file_path).unwrap();
+        assert!(matches!(compute_fingerprint(file_path), Err(DbError::Io(_))));
+    }
+
+    #[test]
+    fn documents_should_be_found_by_hash() {
+        let test_db_path = Path::new("./documents_should_be_found_by_hash.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let file_path = Path::new("./documents_should_be_found_by_hash.epub");
+        write(file_path, "In a hole in the ground there lived a hobbit.").unwrap();
+        let fingerprint = compute_fingerprint(file_path).unwrap();
+        let books = add_category(&mut connection, &"Books".to_string(), &"~/Documents/Books".to_string()).unwrap();
+        let the_hobbit = add_document(&mut connection, &"The Hobbit".to_string(), &books.id_, &None, &None, &None, &"Tolkien/the_hobbit.epub".to_string()).unwrap();
+        let the_hobbit_copy = add_document(&mut connection, &"The Hobbit".to_string(), &books.id_, &None, &None, &None, &"Copies/the_hobbit.epub".to_string()).unwrap();
+        let the_hobbit = set_document_fingerprint(&mut connection, &the_hobbit.id_, &fingerprint).unwrap();
+        let the_hobbit_copy = set_document_fingerprint(&mut connection, &the_hobbit_copy.id_, &fingerprint).unwrap();
+        assert_eq!(Some(fingerprint.hash_.clone()), the_hobbit.content_hash_);
+        assert_eq!(Some(fingerprint.size_), the_hobbit.file_size_);
+        assert_eq!(Some(fingerprint.modified_at_.clone()), get_document_by_id(&mut connection, &the_hobbit.id_).unwrap().modified_at_);
+        assert_eq!(vec![the_hobbit.clone(), the_hobbit_copy.clone()], get_documents_by_hash(&mut connection, &fingerprint.hash_).unwrap());
+        remove_document(&mut connection, &the_hobbit_copy.id_).unwrap();
+        assert_eq!(vec![the_hobbit], get_documents_by_hash(&mut connection, &fingerprint.hash_).unwrap());
+        remove_file(file_path).unwrap();
+        delete_database(test_db_path).unwrap();
+    }
+}
@@ -0,0 +1,206 @@
+use derive_new::new;
+use diesel::{Connection, SqliteConnection};
+use crate::db_manager::{entities::{add_document_with_metadata, get_document_by_id, get_or_create_author, get_or_create_genre, get_or_create_series, get_or_create_tag, link_contributor_to_document, link_genre_to_document, link_identifier_to_document, link_tag_to_document, resolve_document_path, set_document_series_position, ContributorRole, Document, DocumentMetadata, SeriesNumbering}, fingerprint::{compute_fingerprint, set_document_fingerprint}, DbError, PartialDate};
+
+/// Everything known about a new document, with author, series, tags and genres given by name.
+#[derive(PartialEq, Debug, new, Clone)]
+#[allow(clippy::too_many_arguments)]
+pub struct DocumentImport {
+    pub name_ : String,
+    pub category_ : i32,
+    pub author_ : Option<String>,
+    pub contributors_ : Vec<(String, ContributorRole)>,
+    pub series_ : Option<String>,
+    pub series_index_ : Option<f64>,
+    pub series_numbering_ : Option<SeriesNumbering>,
+    pub date_ : Option<PartialDate>,
+    pub path_ : String,
+    pub tags_ : Vec<String>,
+    pub genres_ : Vec<String>,
+    /// Scheme and value of each identifier, like ("isbn", "9780261103344")
+    pub identifiers_ : Vec<(String, String)>,
+    pub metadata_ : DocumentMetadata
+}
+
+/// Adds the document and everything it references in a single transaction, reusing existing entities by name.
+/// The document file is fingerprinted if it is already there. Nothing is written if any step fails.
+pub fn import_document(connection : &mut SqliteConnection, document_import : &DocumentImport) -> Result<Document, DbError> {
+    return connection.transaction::<Document, DbError, _>(|connection| {
+        let document_author = match &document_import.author_ {
+            Some(author_name) => Some(get_or_create_author(connection, author_name)?.id_),
+            None => None
+        };
+        let document_series = match &document_import.series_ {
+            Some(series_name) => Some(get_or_create_series(connection, series_name, &document_author)?.id_),
+            None => None
+        };
+        let added_document = add_document_with_metadata(connection, 
+                                                        &document_import.name_, 
+                                                        &document_import.category_, 
+                                                        &document_author, 
+                                                        &document_series, 
+                                                        &document_import.date_, 
+                                                        &document_import.path_, 
+                                                        &document_import.metadata_)?;
+        if document_import.series_index_.is_some() || document_import.series_numbering_.is_some() {
+            set_document_series_position(connection, &added_document.id_, &document_series, &document_import.series_index_, &document_import.series_numbering_)?;
+        }
+        let mut contributors : Vec<(i32, ContributorRole)> = Vec::new();
+        if let Some(author_id) = document_author {
+            contributors.push((author_id, ContributorRole::Author));
+        }
+        for (contributor_name, contributor_role) in &document_import.contributors_ {
+            let contributor = (get_or_create_author(connection, contributor_name)?.id_, *contributor_role);
+            if !contributors.contains(&contributor) {
+                link_contributor_to_document(connection, &added_document.id_, &contributor.0, &contributor.1)?;
+                contributors.push(contributor);
+            }
+        }
+        let mut tag_ids : Vec<i32> = Vec::new();
+        for tag_name in &document_import.tags_ {
+            let tag_id = get_or_create_tag(connection, tag_name)?.id_;
+            if !tag_ids.contains(&tag_id) {
+                link_tag_to_document(connection, &added_document.id_, &tag_id)?;
+                tag_ids.push(tag_id);
+            }
+        }
+        let mut genre_ids : Vec<i32> = Vec::new();
+        for genre_name in &document_import.genres_ {
+            let genre_id = get_or_create_genre(connection, genre_name)?.id_;
+            if !genre_ids.contains(&genre_id) {
+                link_genre_to_document(connection, &added_document.id_, &genre_id)?;
+                genre_ids.push(genre_id);
+            }
+        }
+        let mut identifiers : Vec<&(String, String)> = Vec::new();
+        for identifier in &document_import.identifiers_ {
+            if !identifiers.contains(&identifier) {
+                link_identifier_to_document(connection, &added_document.id_, &identifier.0, &identifier.1)?;
+                identifiers.push(identifier);
+            }
+        }
+        let document_file = resolve_document_path(connection, &added_document.id_)?;
+        if document_file.is_file() {
+            set_document_fingerprint(connection, &added_document.id_, &compute_fingerprint(&document_file)?)?;
+        }
+        get_document_by_id(connection, &added_document.id_)
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::{create_dir_all, remove_dir_all, write}, path::Path};
+
+    use crate::db_manager::{create_database, delete_database, entities::{add_category, add_tag, get_authors, get_document_contributors, get_document_identifiers, get_documents, get_documents_from_series, get_documents_with_genre, get_documents_with_tag, get_genres, get_or_create_series, get_tag_by_name, get_tags, ContributorRole, DocumentContributor, DocumentIdentifier, DocumentMetadata, SeriesNumbering}, compute_fingerprint, get_connection, DbError, PartialDate};
+
+    use super::{import_document, DocumentImport};
+
+    fn the_two_towers_import(category_id : i32) -> DocumentImport {
+        return DocumentImport::new("The Two Towers".to_string(), 
+                                    category_id, 
+                                    Some("J.R.R Tolkien".to_string()), 
+                                    vec![("Alan Lee".to_string(), ContributorRole::Illustrator)], 
+                                    Some("The Lord Of The Rings".to_string()), 
+                                    Some(2.0), 
+                                    Some(SeriesNumbering::Volume), 
+                                    Some(PartialDate::year_month(1954, 11).unwrap()), 
+                                    "Tolkien/the_two_towers.epub".to_string(), 
+                                    vec!["favorites".to_string(), "favorites".to_string()], 
+                                    vec!["Heroic Fantasy".to_string()], 
+                                    vec![("isbn".to_string(), "9780261102361".to_string())], 
+                                    DocumentMetadata { language_ : Some("en".to_string()), ..Default::default() });
+    }
+
+    #[test]
+    fn importing_document_should_create_and_link_everything() {
+        let test_db_path = Path::new("./importing_document_should_create_and_link_everything.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let books = add_category(&mut connection, &"Books".to_string(), &"~/Documents/Books".to_string()).unwrap();
+        let favorites = add_tag(&mut connection, &"favorites".to_string()).unwrap();
+        let the_two_towers = import_document(&mut connection, &the_two_towers_import(books.id_)).unwrap();
+        let authors = get_authors(&mut connection).unwrap();
+        assert_eq!(vec!["J.R.R Tolkien".to_string(), "Alan Lee".to_string()], authors.iter().map(|a| a.name_.clone()).collect::<Vec<String>>());
+        assert_eq!(Some(authors[0].id_), the_two_towers.author_);
+        assert_eq!(Some(2.0), the_two_towers.series_index_);
+        assert_eq!(Some("en".to_string()), the_two_towers.language_);
+        assert_eq!(vec![DocumentContributor::new(the_two_towers.id_, authors[0].id_, ContributorRole::Author), DocumentContributor::new(the_two_towers.id_, authors[1].id_, ContributorRole::Illustrator)], get_document_contributors(&mut connection, &the_two_towers.id_).unwrap());
+        assert_eq!(vec![favorites.clone()], get_tags(&mut connection).unwrap());
+        assert_eq!(vec![the_two_towers.clone()], get_documents_with_tag(&mut connection, &favorites.id_, &false).unwrap());
+        let heroic_fantasy = get_genres(&mut connection).unwrap()[0].clone();
+        assert_eq!(vec![the_two_towers.clone()], get_documents_with_genre(&mut connection, &heroic_fantasy.id_, &false).unwrap());
+        assert_eq!(vec![DocumentIdentifier::new(the_two_towers.id_, "isbn".to_string(), "9780261102361".to_string())], get_document_identifiers(&mut connection, &the_two_towers.id_).unwrap());
+        let the_lord_of_the_rings = get_or_create_series(&mut connection, &"The Lord Of The Rings".to_string(), &Some(authors[0].id_)).unwrap();
+        assert_eq!(vec![the_two_towers], get_documents_from_series(&mut connection, &the_lord_of_the_rings.id_).unwrap());
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn failed_import_should_write_nothing() {
+        let test_db_path = Path::new("./failed_import_should_write_nothing.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let books = add_category(&mut connection, &"Books".to_string(), &"~/Documents/Books".to_string()).unwrap();
+        assert!(matches!(import_document(&mut connection, &the_two_towers_import(books.id_ + 1)), Err(DbError::ForeignKey(_))));
+        assert!(get_authors(&mut connection).unwrap().is_empty());
+        assert!(get_documents(&mut connection).unwrap().is_empty());
+        assert!(matches!(get_tag_by_name(&mut connection, &"favorites".to_string()), Err(DbError::NotFound(_))));
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn importing_document_twice_should_reuse_entities() {
+        let test_db_path = Path::new("./importing_document_twice_should_reuse_entities.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let books = add_category(&mut connection, &"Books".to_string(), &"~/Documents/Books".to_string()).unwrap();
+        let mut the_return_of_the_king = the_two_towers_import(books.id_);
+        import_document(&mut connection, &the_two_towers_import(books.id_)).unwrap();
+        the_return_of_the_king.name_ = "The Return Of The King".to_string();
+        the_return_of_the_king.path_ = "Tolkien/the_return_of_the_king.epub".to_string();
+        the_return_of_the_king.series_index_ = Some(3.0);
+        let the_return_of_the_king = import_document(&mut connection, &the_return_of_the_king).unwrap();
+        assert_eq!(2, get_authors(&mut connection).unwrap().len());
+        assert_eq!(1, get_genres(&mut connection).unwrap().len());
+        let the_lord_of_the_rings = get_or_create_series(&mut connection, &"The Lord Of The Rings".to_string(), &the_return_of_the_king.author_).unwrap();
+        assert_eq!(2, get_documents_from_series(&mut connection, &the_lord_of_the_rings.id_).unwrap().len());
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn importing_series_without_author_should_not_reuse_series_of_an_author() {
+        let test_db_path = Path::new("./importing_series_without_author_should_not_reuse_series_of_an_author.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let books = add_category(&mut connection, &"Books".to_string(), &"~/Documents/Books".to_string()).unwrap();
+        let the_two_towers = import_document(&mut connection, &the_two_towers_import(books.id_)).unwrap();
+        let mut unknown_volume = the_two_towers_import(books.id_);
+        unknown_volume.author_ = None;
+        unknown_volume.contributors_ = Vec::new();
+        unknown_volume.path_ = "Unknown/the_lord_of_the_rings_1.epub".to_string();
+        let first_volume = import_document(&mut connection, &unknown_volume).unwrap();
+        unknown_volume.path_ = "Unknown/the_lord_of_the_rings_2.epub".to_string();
+        let second_volume = import_document(&mut connection, &unknown_volume).unwrap();
+        assert_ne!(the_two_towers.series_, first_volume.series_);
+        assert_eq!(first_volume.series_, second_volume.series_);
+        let unattributed_series = get_or_create_series(&mut connection, &"The Lord Of The Rings".to_string(), &None).unwrap();
+        assert_eq!((first_volume.series_, None), (Some(unattributed_series.id_), unattributed_series.author_));
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn importing_document_should_fingerprint_its_file() {
+        let test_db_path = Path::new("./importing_document_should_fingerprint_its_file.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let books_root = Path::new("./importing_document_should_fingerprint_its_file");
+        create_dir_all(books_root.join("Tolkien")).unwrap();
+        write(books_root.join("Tolkien/the_two_towers.epub"), "Aragorn sped on up the hill.").unwrap();
+        let books = add_category(&mut connection, &"Books".to_string(), &books_root.to_string_lossy().to_string()).unwrap();
+        let the_two_towers = import_document(&mut connection, &the_two_towers_import(books.id_)).unwrap();
+        let fingerprint = compute_fingerprint(&books_root.join("Tolkien/the_two_towers.epub")).unwrap();
+        assert_eq!((Some(fingerprint.hash_), Some(fingerprint.size_), Some(fingerprint.modified_at_)), (the_two_towers.content_hash_, the_two_towers.file_size_, the_two_towers.modified_at_));
+        remove_dir_all(books_root).unwrap();
+        delete_database(test_db_path).unwrap();
+    }
+}
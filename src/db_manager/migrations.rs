@@ -0,0 +1,619 @@
+use chrono::Utc;
+use diesel::{connection::SimpleConnection, insert_into, Connection, ExpressionMethods, QueryDsl, RunQueryDsl, SqliteConnection};
+use crate::db_manager::{entities::schema::schema_version::dsl::*, DbError};
+
+const SCHEMA_VERSION_CREATION_QUERY : &str =
+    "CREATE TABLE IF NOT EXISTS schema_version (
+        version INTEGER PRIMARY KEY,
+        applied_at TEXT NOT NULL
+    );";
+
+/// Ordered up-migrations, the migration at index `i` brings the schema to version `i + 1`.
+/// Migrations are only ever appended to this list, never edited once released.
+const MIGRATIONS : [&str; 17] = [
+    // 1 : initial schema, kept idempotent so databases created before versioning can adopt it
+    "CREATE TABLE IF NOT EXISTS author (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS series (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        author INTEGER,
+        name TEXT NOT NULL,
+        FOREIGN KEY (author) REFERENCES author(id)
+    );
+
+    CREATE TABLE IF NOT EXISTS tag (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS genre (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS category (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL,
+        path TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS document (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL,
+        category INTEGER,
+        author INTEGER,
+        series INTEGER,
+        date TEXT,
+        path TEXT NOT NULL,
+    FOREIGN KEY (category) REFERENCES category(id),
+    FOREIGN KEY (author) REFERENCES author(id),
+    FOREIGN KEY (series) REFERENCES series(id)
+    );
+
+    CREATE TABLE IF NOT EXISTS document_category (
+        document INTEGER,
+        category INTEGER,
+        PRIMARY KEY (document, category),
+        FOREIGN KEY (document) REFERENCES document(id),
+        FOREIGN KEY (category) REFERENCES category(id)
+    );
+
+    CREATE TABLE IF NOT EXISTS document_genre (
+        document INTEGER,
+        genre INTEGER,
+        PRIMARY KEY (document, genre),
+        FOREIGN KEY (document) REFERENCES document(id),
+        FOREIGN KEY (genre) REFERENCES genre(id)
+    );
+
+    CREATE TABLE IF NOT EXISTS document_tag (
+        document INTEGER,
+        tag INTEGER,
+        PRIMARY KEY (document, tag),
+        FOREIGN KEY (document) REFERENCES document(id),
+        FOREIGN KEY (tag) REFERENCES tag(id)
+    );
+
+    CREATE TABLE IF NOT EXISTS users (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        username TEXT NOT NULL,
+        email TEXT DEFAULT NULL,
+        password TEXT NOT NULL
+    );
+    ",
+    // 2 : explicit delete policies, link rows follow their document, tag, genre or category,
+    // documents lose their author or series, while authors with series and categories with documents can't be deleted
+    "DELETE FROM document_category WHERE document NOT IN (SELECT id FROM document) OR category NOT IN (SELECT id FROM category);
+    DELETE FROM document_genre WHERE document NOT IN (SELECT id FROM document) OR genre NOT IN (SELECT id FROM genre);
+    DELETE FROM document_tag WHERE document NOT IN (SELECT id FROM document) OR tag NOT IN (SELECT id FROM tag);
+    UPDATE document SET author = NULL WHERE author NOT IN (SELECT id FROM author);
+    UPDATE document SET series = NULL WHERE series NOT IN (SELECT id FROM series);
+
+    CREATE TABLE series_new (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        author INTEGER,
+        name TEXT NOT NULL,
+        FOREIGN KEY (author) REFERENCES author(id) ON DELETE RESTRICT
+    );
+    INSERT INTO series_new (id, author, name) SELECT id, author, name FROM series;
+    DROP TABLE series;
+    ALTER TABLE series_new RENAME TO series;
+
+    CREATE TABLE document_new (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL,
+        category INTEGER,
+        author INTEGER,
+        series INTEGER,
+        date TEXT,
+        path TEXT NOT NULL,
+        FOREIGN KEY (category) REFERENCES category(id) ON DELETE RESTRICT,
+        FOREIGN KEY (author) REFERENCES author(id) ON DELETE SET NULL,
+        FOREIGN KEY (series) REFERENCES series(id) ON DELETE SET NULL
+    );
+    INSERT INTO document_new (id, name, category, author, series, date, path) SELECT id, name, category, author, series, date, path FROM document;
+    DROP TABLE document;
+    ALTER TABLE document_new RENAME TO document;
+
+    CREATE TABLE document_category_new (
+        document INTEGER,
+        category INTEGER,
+        PRIMARY KEY (document, category),
+        FOREIGN KEY (document) REFERENCES document(id) ON DELETE CASCADE,
+        FOREIGN KEY (category) REFERENCES category(id) ON DELETE CASCADE
+    );
+    INSERT INTO document_category_new (document, category) SELECT document, category FROM document_category;
+    DROP TABLE document_category;
+    ALTER TABLE document_category_new RENAME TO document_category;
+
+    CREATE TABLE document_genre_new (
+        document INTEGER,
+        genre INTEGER,
+        PRIMARY KEY (document, genre),
+        FOREIGN KEY (document) REFERENCES document(id) ON DELETE CASCADE,
+        FOREIGN KEY (genre) REFERENCES genre(id) ON DELETE CASCADE
+    );
+    INSERT INTO document_genre_new (document, genre) SELECT document, genre FROM document_genre;
+    DROP TABLE document_genre;
+    ALTER TABLE document_genre_new RENAME TO document_genre;
+
+    CREATE TABLE document_tag_new (
+        document INTEGER,
+        tag INTEGER,
+        PRIMARY KEY (document, tag),
+        FOREIGN KEY (document) REFERENCES document(id) ON DELETE CASCADE,
+        FOREIGN KEY (tag) REFERENCES tag(id) ON DELETE CASCADE
+    );
+    INSERT INTO document_tag_new (document, tag) SELECT document, tag FROM document_tag;
+    DROP TABLE document_tag;
+    ALTER TABLE document_tag_new RENAME TO document_tag;
+    ",
+    // 3 : authors, tags and genres are identified by their name, duplicates are folded into the oldest row
+    "UPDATE document SET author = (SELECT MIN(a.id) FROM author a WHERE a.name = (SELECT name FROM author WHERE id = document.author)) WHERE author IS NOT NULL;
+    UPDATE series SET author = (SELECT MIN(a.id) FROM author a WHERE a.name = (SELECT name FROM author WHERE id = series.author)) WHERE author IS NOT NULL;
+    DELETE FROM author WHERE id NOT IN (SELECT MIN(id) FROM author GROUP BY name);
+    CREATE UNIQUE INDEX author_name_unique ON author(name);
+
+    INSERT OR IGNORE INTO document_tag (document, tag)
+        SELECT dt.document, (SELECT MIN(t.id) FROM tag t WHERE t.name = (SELECT name FROM tag WHERE id = dt.tag)) FROM document_tag dt;
+    DELETE FROM document_tag WHERE tag NOT IN (SELECT MIN(id) FROM tag GROUP BY name);
+    DELETE FROM tag WHERE id NOT IN (SELECT MIN(id) FROM tag GROUP BY name);
+    CREATE UNIQUE INDEX tag_name_unique ON tag(name);
+
+    INSERT OR IGNORE INTO document_genre (document, genre)
+        SELECT dg.document, (SELECT MIN(g.id) FROM genre g WHERE g.name = (SELECT name FROM genre WHERE id = dg.genre)) FROM document_genre dg;
+    DELETE FROM document_genre WHERE genre NOT IN (SELECT MIN(id) FROM genre GROUP BY name);
+    DELETE FROM genre WHERE id NOT IN (SELECT MIN(id) FROM genre GROUP BY name);
+    CREATE UNIQUE INDEX genre_name_unique ON genre(name);
+    ",
+    // 4 : several contributors per document, document.author is kept as the main author
+    "CREATE TABLE document_contributor (
+        document INTEGER NOT NULL,
+        author INTEGER NOT NULL,
+        role TEXT NOT NULL CHECK (role IN ('author', 'illustrator', 'translator', 'editor', 'narrator')),
+        PRIMARY KEY (document, author, role),
+        FOREIGN KEY (document) REFERENCES document(id) ON DELETE CASCADE,
+        FOREIGN KEY (author) REFERENCES author(id) ON DELETE CASCADE
+    );
+    CREATE INDEX document_contributor_author ON document_contributor(author);
+    INSERT INTO document_contributor (document, author, role) SELECT id, author, 'author' FROM document WHERE author IS NOT NULL;
+    ",
+    // 5 : position of a document inside its series
+    "ALTER TABLE document ADD COLUMN series_index REAL;
+    ALTER TABLE document ADD COLUMN series_numbering TEXT CHECK (series_numbering IN ('volume', 'chapter'));
+    CREATE INDEX document_series_position ON document(series, series_index);
+    ",
+    // 6 : bibliographic metadata
+    "ALTER TABLE document ADD COLUMN description TEXT;
+    ALTER TABLE document ADD COLUMN language TEXT;
+    ALTER TABLE document ADD COLUMN publisher TEXT;
+    ALTER TABLE document ADD COLUMN page_count INTEGER CHECK (page_count >= 0);
+    ALTER TABLE document ADD COLUMN file_size INTEGER CHECK (file_size >= 0);
+    ALTER TABLE document ADD COLUMN mime_type TEXT;
+    ALTER TABLE document ADD COLUMN sort_title TEXT;
+    ",
+    // 7 : genre and tag hierarchies, children move to the top level when their parent is removed
+    "ALTER TABLE genre ADD COLUMN parent INTEGER REFERENCES genre(id) ON DELETE SET NULL;
+    ALTER TABLE tag ADD COLUMN parent INTEGER REFERENCES tag(id) ON DELETE SET NULL;
+    CREATE INDEX genre_parent ON genre(parent);
+    CREATE INDEX tag_parent ON tag(parent);
+    ",
+    // 8 : full text search over documents and the names they reference, every row is rebuilt from document_search_source
+    "CREATE VIRTUAL TABLE document_search USING fts5(name, authors, series, tags, genres, tokenize = 'unicode61 remove_diacritics 2');
+    CREATE VIEW document_search_source AS SELECT d.id AS id, d.name AS name,
+        (SELECT group_concat(a.name, ' ') FROM author a WHERE a.id = d.author OR a.id IN (SELECT c.author FROM document_contributor c WHERE c.document = d.id)) AS authors,
+        (SELECT s.name FROM series s WHERE s.id = d.series) AS series,
+        (SELECT group_concat(t.name, ' ') FROM tag t JOIN document_tag dt ON dt.tag = t.id WHERE dt.document = d.id) AS tags,
+        (SELECT group_concat(g.name, ' ') FROM genre g JOIN document_genre dg ON dg.genre = g.id WHERE dg.document = d.id) AS genres
+        FROM document d;
+    INSERT INTO document_search (rowid, name, authors, series, tags, genres) SELECT * FROM document_search_source;
+    CREATE TRIGGER document_search_document_insert AFTER INSERT ON document BEGIN
+        DELETE FROM document_search WHERE rowid IN (NEW.id);
+        INSERT INTO document_search (rowid, name, authors, series, tags, genres) SELECT * FROM document_search_source WHERE id IN (NEW.id);
+    END;
+    CREATE TRIGGER document_search_document_update AFTER UPDATE OF name, author, series ON document BEGIN
+        DELETE FROM document_search WHERE rowid IN (NEW.id);
+        INSERT INTO document_search (rowid, name, authors, series, tags, genres) SELECT * FROM document_search_source WHERE id IN (NEW.id);
+    END;
+    CREATE TRIGGER document_search_contributor_insert AFTER INSERT ON document_contributor BEGIN
+        DELETE FROM document_search WHERE rowid IN (NEW.document);
+        INSERT INTO document_search (rowid, name, authors, series, tags, genres) SELECT * FROM document_search_source WHERE id IN (NEW.document);
+    END;
+    CREATE TRIGGER document_search_contributor_delete AFTER DELETE ON document_contributor BEGIN
+        DELETE FROM document_search WHERE rowid IN (OLD.document);
+        INSERT INTO document_search (rowid, name, authors, series, tags, genres) SELECT * FROM document_search_source WHERE id IN (OLD.document);
+    END;
+    CREATE TRIGGER document_search_author_update AFTER UPDATE OF name ON author BEGIN
+        DELETE FROM document_search WHERE rowid IN (SELECT id FROM document WHERE author = NEW.id UNION SELECT document FROM document_contributor WHERE author = NEW.id);
+        INSERT INTO document_search (rowid, name, authors, series, tags, genres) SELECT * FROM document_search_source WHERE id IN (SELECT id FROM document WHERE author = NEW.id UNION SELECT document FROM document_contributor WHERE author = NEW.id);
+    END;
+    CREATE TRIGGER document_search_series_update AFTER UPDATE OF name ON series BEGIN
+        DELETE FROM document_search WHERE rowid IN (SELECT id FROM document WHERE series = NEW.id);
+        INSERT INTO document_search (rowid, name, authors, series, tags, genres) SELECT * FROM document_search_source WHERE id IN (SELECT id FROM document WHERE series = NEW.id);
+    END;
+    CREATE TRIGGER document_search_tag_insert AFTER INSERT ON document_tag BEGIN
+        DELETE FROM document_search WHERE rowid IN (NEW.document);
+        INSERT INTO document_search (rowid, name, authors, series, tags, genres) SELECT * FROM document_search_source WHERE id IN (NEW.document);
+    END;
+    CREATE TRIGGER document_search_tag_delete AFTER DELETE ON document_tag BEGIN
+        DELETE FROM document_search WHERE rowid IN (OLD.document);
+        INSERT INTO document_search (rowid, name, authors, series, tags, genres) SELECT * FROM document_search_source WHERE id IN (OLD.document);
+    END;
+    CREATE TRIGGER document_search_tag_update AFTER UPDATE OF name ON tag BEGIN
+        DELETE FROM document_search WHERE rowid IN (SELECT document FROM document_tag WHERE tag = NEW.id);
+        INSERT INTO document_search (rowid, name, authors, series, tags, genres) SELECT * FROM document_search_source WHERE id IN (SELECT document FROM document_tag WHERE tag = NEW.id);
+    END;
+    CREATE TRIGGER document_search_genre_insert AFTER INSERT ON document_genre BEGIN
+        DELETE FROM document_search WHERE rowid IN (NEW.document);
+        INSERT INTO document_search (rowid, name, authors, series, tags, genres) SELECT * FROM document_search_source WHERE id IN (NEW.document);
+    END;
+    CREATE TRIGGER document_search_genre_delete AFTER DELETE ON document_genre BEGIN
+        DELETE FROM document_search WHERE rowid IN (OLD.document);
+        INSERT INTO document_search (rowid, name, authors, series, tags, genres) SELECT * FROM document_search_source WHERE id IN (OLD.document);
+    END;
+    CREATE TRIGGER document_search_genre_update AFTER UPDATE OF name ON genre BEGIN
+        DELETE FROM document_search WHERE rowid IN (SELECT document FROM document_genre WHERE genre = NEW.id);
+        INSERT INTO document_search (rowid, name, authors, series, tags, genres) SELECT * FROM document_search_source WHERE id IN (SELECT document FROM document_genre WHERE genre = NEW.id);
+    END;
+    CREATE TRIGGER document_search_document_delete AFTER DELETE ON document BEGIN
+        DELETE FROM document_search WHERE rowid = OLD.id;
+    END;
+    ",
+    // 9 : trash, removed documents keep their row and links until purged
+    "ALTER TABLE document ADD COLUMN deleted_at TEXT;
+    CREATE INDEX document_deleted_at ON document(deleted_at);
+    ",
+    // 10 : change history filled by triggers, change_author holds who is writing during a with_change_author transaction
+    "CREATE TABLE change_author (
+        id INTEGER PRIMARY KEY CHECK (id = 1),
+        username TEXT NOT NULL
+    );
+    CREATE TABLE change_history (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        entity TEXT NOT NULL CHECK (entity IN ('document', 'author', 'series', 'tag', 'genre')),
+        entity_id INTEGER NOT NULL,
+        field TEXT NOT NULL,
+        old_value TEXT,
+        new_value TEXT,
+        changed_by TEXT,
+        changed_at TEXT NOT NULL
+    );
+    CREATE INDEX change_history_entity ON change_history(entity, entity_id);
+    CREATE TRIGGER document_history_insert AFTER INSERT ON document BEGIN
+        INSERT INTO change_history (entity, entity_id, field, old_value, new_value, changed_by, changed_at) VALUES ('document', NEW.id, 'created', NULL, NEW.name, (SELECT username FROM change_author), strftime('%Y-%m-%dT%H:%M:%SZ', 'now'));
+    END;
+    CREATE TRIGGER document_history_delete AFTER DELETE ON document BEGIN
+        INSERT INTO change_history (entity, entity_id, field, old_value, new_value, changed_by, changed_at) VALUES ('document', OLD.id, 'deleted', OLD.name, NULL, (SELECT username FROM change_author), strftime('%Y-%m-%dT%H:%M:%SZ', 'now'));
+    END;
+    CREATE TRIGGER author_history_insert AFTER INSERT ON author BEGIN
+        INSERT INTO change_history (entity, entity_id, field, old_value, new_value, changed_by, changed_at) VALUES ('author', NEW.id, 'created', NULL, NEW.name, (SELECT username FROM change_author), strftime('%Y-%m-%dT%H:%M:%SZ', 'now'));
+    END;
+    CREATE TRIGGER author_history_delete AFTER DELETE ON author BEGIN
+        INSERT INTO change_history (entity, entity_id, field, old_value, new_value, changed_by, changed_at) VALUES ('author', OLD.id, 'deleted', OLD.name, NULL, (SELECT username FROM change_author), strftime('%Y-%m-%dT%H:%M:%SZ', 'now'));
+    END;
+    CREATE TRIGGER series_history_insert AFTER INSERT ON series BEGIN
+        INSERT INTO change_history (entity, entity_id, field, old_value, new_value, changed_by, changed_at) VALUES ('series', NEW.id, 'created', NULL, NEW.name, (SELECT username FROM change_author), strftime('%Y-%m-%dT%H:%M:%SZ', 'now'));
+    END;
+    CREATE TRIGGER series_history_delete AFTER DELETE ON series BEGIN
+        INSERT INTO change_history (entity, entity_id, field, old_value, new_value, changed_by, changed_at) VALUES ('series', OLD.id, 'deleted', OLD.name, NULL, (SELECT username FROM change_author), strftime('%Y-%m-%dT%H:%M:%SZ', 'now'));
+    END;
+    CREATE TRIGGER tag_history_insert AFTER INSERT ON tag BEGIN
+        INSERT INTO change_history (entity, entity_id, field, old_value, new_value, changed_by, changed_at) VALUES ('tag', NEW.id, 'created', NULL, NEW.name, (SELECT username FROM change_author), strftime('%Y-%m-%dT%H:%M:%SZ', 'now'));
+    END;
+    CREATE TRIGGER tag_history_delete AFTER DELETE ON tag BEGIN
+        INSERT INTO change_history (entity, entity_id, field, old_value, new_value, changed_by, changed_at) VALUES ('tag', OLD.id, 'deleted', OLD.name, NULL, (SELECT username FROM change_author), strftime('%Y-%m-%dT%H:%M:%SZ', 'now'));
+    END;
+    CREATE TRIGGER genre_history_insert AFTER INSERT ON genre BEGIN
+        INSERT INTO change_history (entity, entity_id, field, old_value, new_value, changed_by, changed_at) VALUES ('genre', NEW.id, 'created', NULL, NEW.name, (SELECT username FROM change_author), strftime('%Y-%m-%dT%H:%M:%SZ', 'now'));
+    END;
+    CREATE TRIGGER genre_history_delete AFTER DELETE ON genre BEGIN
+        INSERT INTO change_history (entity, entity_id, field, old_value, new_value, changed_by, changed_at) VALUES ('genre', OLD.id, 'deleted', OLD.name, NULL, (SELECT username FROM change_author), strftime('%Y-%m-%dT%H:%M:%SZ', 'now'));
+    END;
+    CREATE TRIGGER document_history_update AFTER UPDATE ON document BEGIN
+        INSERT INTO change_history (entity, entity_id, field, old_value, new_value, changed_by, changed_at)
+        SELECT 'document', NEW.id, field, old_value, new_value, (SELECT username FROM change_author), strftime('%Y-%m-%dT%H:%M:%SZ', 'now') FROM (
+            SELECT 'name' AS field, OLD.name AS old_value, NEW.name AS new_value UNION ALL
+            SELECT 'category', OLD.category, NEW.category UNION ALL
+            SELECT 'author', OLD.author, NEW.author UNION ALL
+            SELECT 'series', OLD.series, NEW.series UNION ALL
+            SELECT 'date', OLD.date, NEW.date UNION ALL
+            SELECT 'path', OLD.path, NEW.path UNION ALL
+            SELECT 'series_index', OLD.series_index, NEW.series_index UNION ALL
+            SELECT 'series_numbering', OLD.series_numbering, NEW.series_numbering UNION ALL
+            SELECT 'description', OLD.description, NEW.description UNION ALL
+            SELECT 'language', OLD.language, NEW.language UNION ALL
+            SELECT 'publisher', OLD.publisher, NEW.publisher UNION ALL
+            SELECT 'page_count', OLD.page_count, NEW.page_count UNION ALL
+            SELECT 'file_size', OLD.file_size, NEW.file_size UNION ALL
+            SELECT 'mime_type', OLD.mime_type, NEW.mime_type UNION ALL
+            SELECT 'sort_title', OLD.sort_title, NEW.sort_title UNION ALL
+            SELECT 'deleted_at', OLD.deleted_at, NEW.deleted_at
+        ) WHERE old_value IS NOT new_value;
+    END;
+    CREATE TRIGGER author_history_update AFTER UPDATE ON author BEGIN
+        INSERT INTO change_history (entity, entity_id, field, old_value, new_value, changed_by, changed_at)
+        SELECT 'author', NEW.id, field, old_value, new_value, (SELECT username FROM change_author), strftime('%Y-%m-%dT%H:%M:%SZ', 'now') FROM (
+            SELECT 'name' AS field, OLD.name AS old_value, NEW.name AS new_value
+        ) WHERE old_value IS NOT new_value;
+    END;
+    CREATE TRIGGER series_history_update AFTER UPDATE ON series BEGIN
+        INSERT INTO change_history (entity, entity_id, field, old_value, new_value, changed_by, changed_at)
+        SELECT 'series', NEW.id, field, old_value, new_value, (SELECT username FROM change_author), strftime('%Y-%m-%dT%H:%M:%SZ', 'now') FROM (
+            SELECT 'name' AS field, OLD.name AS old_value, NEW.name AS new_value UNION ALL
+            SELECT 'author', OLD.author, NEW.author
+        ) WHERE old_value IS NOT new_value;
+    END;
+    CREATE TRIGGER tag_history_update AFTER UPDATE ON tag BEGIN
+        INSERT INTO change_history (entity, entity_id, field, old_value, new_value, changed_by, changed_at)
+        SELECT 'tag', NEW.id, field, old_value, new_value, (SELECT username FROM change_author), strftime('%Y-%m-%dT%H:%M:%SZ', 'now') FROM (
+            SELECT 'name' AS field, OLD.name AS old_value, NEW.name AS new_value UNION ALL
+            SELECT 'parent', OLD.parent, NEW.parent
+        ) WHERE old_value IS NOT new_value;
+    END;
+    CREATE TRIGGER genre_history_update AFTER UPDATE ON genre BEGIN
+        INSERT INTO change_history (entity, entity_id, field, old_value, new_value, changed_by, changed_at)
+        SELECT 'genre', NEW.id, field, old_value, new_value, (SELECT username FROM change_author), strftime('%Y-%m-%dT%H:%M:%SZ', 'now') FROM (
+            SELECT 'name' AS field, OLD.name AS old_value, NEW.name AS new_value UNION ALL
+            SELECT 'parent', OLD.parent, NEW.parent
+        ) WHERE old_value IS NOT new_value;
+    END;
+    CREATE TRIGGER document_tag_history_insert AFTER INSERT ON document_tag BEGIN
+        INSERT INTO change_history (entity, entity_id, field, old_value, new_value, changed_by, changed_at) VALUES ('document', NEW.document, 'tag', NULL, (SELECT name FROM tag WHERE id = NEW.tag), (SELECT username FROM change_author), strftime('%Y-%m-%dT%H:%M:%SZ', 'now'));
+    END;
+    CREATE TRIGGER document_tag_history_delete AFTER DELETE ON document_tag BEGIN
+        INSERT INTO change_history (entity, entity_id, field, old_value, new_value, changed_by, changed_at) VALUES ('document', OLD.document, 'tag', COALESCE((SELECT name FROM tag WHERE id = OLD.tag), OLD.tag), NULL, (SELECT username FROM change_author), strftime('%Y-%m-%dT%H:%M:%SZ', 'now'));
+    END;
+    CREATE TRIGGER document_genre_history_insert AFTER INSERT ON document_genre BEGIN
+        INSERT INTO change_history (entity, entity_id, field, old_value, new_value, changed_by, changed_at) VALUES ('document', NEW.document, 'genre', NULL, (SELECT name FROM genre WHERE id = NEW.genre), (SELECT username FROM change_author), strftime('%Y-%m-%dT%H:%M:%SZ', 'now'));
+    END;
+    CREATE TRIGGER document_genre_history_delete AFTER DELETE ON document_genre BEGIN
+        INSERT INTO change_history (entity, entity_id, field, old_value, new_value, changed_by, changed_at) VALUES ('document', OLD.document, 'genre', COALESCE((SELECT name FROM genre WHERE id = OLD.genre), OLD.genre), NULL, (SELECT username FROM change_author), strftime('%Y-%m-%dT%H:%M:%SZ', 'now'));
+    END;
+    CREATE TRIGGER document_category_history_insert AFTER INSERT ON document_category BEGIN
+        INSERT INTO change_history (entity, entity_id, field, old_value, new_value, changed_by, changed_at) VALUES ('document', NEW.document, 'linked_category', NULL, (SELECT name FROM category WHERE id = NEW.category), (SELECT username FROM change_author), strftime('%Y-%m-%dT%H:%M:%SZ', 'now'));
+    END;
+    CREATE TRIGGER document_category_history_delete AFTER DELETE ON document_category BEGIN
+        INSERT INTO change_history (entity, entity_id, field, old_value, new_value, changed_by, changed_at) VALUES ('document', OLD.document, 'linked_category', COALESCE((SELECT name FROM category WHERE id = OLD.category), OLD.category), NULL, (SELECT username FROM change_author), strftime('%Y-%m-%dT%H:%M:%SZ', 'now'));
+    END;
+    CREATE TRIGGER document_contributor_history_insert AFTER INSERT ON document_contributor BEGIN
+        INSERT INTO change_history (entity, entity_id, field, old_value, new_value, changed_by, changed_at) VALUES ('document', NEW.document, 'contributor', NULL, (SELECT name FROM author WHERE id = NEW.author) || ' (' || NEW.role || ')', (SELECT username FROM change_author), strftime('%Y-%m-%dT%H:%M:%SZ', 'now'));
+    END;
+    CREATE TRIGGER document_contributor_history_delete AFTER DELETE ON document_contributor BEGIN
+        INSERT INTO change_history (entity, entity_id, field, old_value, new_value, changed_by, changed_at) VALUES ('document', OLD.document, 'contributor', COALESCE((SELECT name FROM author WHERE id = OLD.author), OLD.author) || ' (' || OLD.role || ')', NULL, (SELECT username FROM change_author), strftime('%Y-%m-%dT%H:%M:%SZ', 'now'));
+    END;
+    ",
+    // 11 : dates are ISO dates that may stop at the year or month, anything else can't be read back and is cleared,
+    // the history triggers record these changes as made by the migration
+    "REPLACE INTO change_author (id, username) VALUES (1, 'migration 11');
+    UPDATE document SET date = substr(date, 1, 10) WHERE date GLOB '[0-9][0-9][0-9][0-9]-[0-9][0-9]-[0-9][0-9]?*';
+    UPDATE document SET date = NULL WHERE date IS NOT NULL
+        AND NOT (date GLOB '[0-9][0-9][0-9][0-9]' AND date > '0000')
+        AND NOT (date GLOB '[0-9][0-9][0-9][0-9]-[0-1][0-9]' AND substr(date, 6, 2) BETWEEN '01' AND '12')
+        AND NOT (date GLOB '[0-9][0-9][0-9][0-9]-[0-1][0-9]-[0-3][0-9]' AND date(date) = date);
+    DELETE FROM change_author;
+    ",
+    // 12 : document paths are relative to their category root, recorded in history as made by the migration
+    "REPLACE INTO change_author (id, username) VALUES (1, 'migration 12');
+    UPDATE document SET path = substr(document.path, length(rtrim(category.path, '/')) + 2)
+        FROM category
+        WHERE category.id = document.category
+        AND substr(document.path, 1, length(rtrim(category.path, '/')) + 1) = rtrim(category.path, '/') || '/';
+    DELETE FROM change_author;
+    ",
+    // 13 : fingerprint of the document file, to find duplicates and moved or altered files
+    "ALTER TABLE document ADD COLUMN content_hash TEXT;
+    ALTER TABLE document ADD COLUMN modified_at TEXT;
+    CREATE INDEX document_content_hash ON document(content_hash);
+    ",
+    // 14 : layout of the document files under the category root, used when importing scanned files
+    "ALTER TABLE category ADD COLUMN path_template TEXT;
+    ",
+    // 15 : identifiers of documents, like the ISBN read from their files
+    "CREATE TABLE document_identifier (
+        document INTEGER NOT NULL,
+        scheme TEXT NOT NULL,
+        value TEXT NOT NULL,
+        PRIMARY KEY (document, scheme, value),
+        FOREIGN KEY (document) REFERENCES document(id) ON DELETE CASCADE
+    );
+    CREATE INDEX document_identifier_value ON document_identifier(scheme, value);
+    ",
+    // 16 : reading direction, known for comics and manga
+    "ALTER TABLE document ADD COLUMN right_to_left INTEGER CHECK (right_to_left IN (0, 1));
+    ",
+    // 17 : history of the document columns added since the history triggers
+    "DROP TRIGGER document_history_update;
+    CREATE TRIGGER document_history_update AFTER UPDATE ON document BEGIN
+        INSERT INTO change_history (entity, entity_id, field, old_value, new_value, changed_by, changed_at)
+        SELECT 'document', NEW.id, field, old_value, new_value, (SELECT username FROM change_author), strftime('%Y-%m-%dT%H:%M:%SZ', 'now') FROM (
+            SELECT 'name' AS field, OLD.name AS old_value, NEW.name AS new_value UNION ALL
+            SELECT 'category', OLD.category, NEW.category UNION ALL
+            SELECT 'author', OLD.author, NEW.author UNION ALL
+            SELECT 'series', OLD.series, NEW.series UNION ALL
+            SELECT 'date', OLD.date, NEW.date UNION ALL
+            SELECT 'path', OLD.path, NEW.path UNION ALL
+            SELECT 'series_index', OLD.series_index, NEW.series_index UNION ALL
+            SELECT 'series_numbering', OLD.series_numbering, NEW.series_numbering UNION ALL
+            SELECT 'description', OLD.description, NEW.description UNION ALL
+            SELECT 'language', OLD.language, NEW.language UNION ALL
+            SELECT 'publisher', OLD.publisher, NEW.publisher UNION ALL
+            SELECT 'page_count', OLD.page_count, NEW.page_count UNION ALL
+            SELECT 'file_size', OLD.file_size, NEW.file_size UNION ALL
+            SELECT 'mime_type', OLD.mime_type, NEW.mime_type UNION ALL
+            SELECT 'sort_title', OLD.sort_title, NEW.sort_title UNION ALL
+            SELECT 'deleted_at', OLD.deleted_at, NEW.deleted_at UNION ALL
+            SELECT 'content_hash', OLD.content_hash, NEW.content_hash UNION ALL
+            SELECT 'modified_at', OLD.modified_at, NEW.modified_at UNION ALL
+            SELECT 'right_to_left', OLD.right_to_left, NEW.right_to_left
+        ) WHERE old_value IS NOT new_value;
+    END;
+    ",
+];
+
+/// Schema version this binary expects once every migration has been applied.
+pub const LATEST_SCHEMA_VERSION : i32 = MIGRATIONS.len() as i32;
+
+/// Gives the version of the schema currently stored in the database, 0 for a database that was never migrated.
+pub fn get_schema_version(connection : &mut SqliteConnection) -> Result<i32, DbError> {
+    connection.batch_execute(SCHEMA_VERSION_CREATION_QUERY)
+        .map_err(|e| DbError::from_diesel("Could not create schema version table".to_string(), e))?;
+    return schema_version.select(diesel::dsl::max(version)).first::<Option<i32>>(connection)
+        .map(|v| v.unwrap_or(0))
+        .map_err(|e| DbError::from_diesel("Could not read schema version".to_string(), e));
+}
+
+/// Applies every migration newer than the current schema version, each one in its own transaction.
+/// Refuses to touch a database whose schema is newer than this binary.
+pub fn run_migrations(connection : &mut SqliteConnection) -> Result<i32, DbError> {
+    return migrate_to(connection, LATEST_SCHEMA_VERSION);
+}
+
+fn migrate_to(connection : &mut SqliteConnection, target_version : i32) -> Result<i32, DbError> {
+    let current_version = get_schema_version(connection)?;
+    if current_version > LATEST_SCHEMA_VERSION {
+        return Err(DbError::Conflict(format!("Database schema version {} is newer than the latest version {} supported by this binary", current_version, LATEST_SCHEMA_VERSION)));
+    }
+    // tables are rebuilt by some migrations, which can't happen while foreign keys are enforced
+    connection.batch_execute("PRAGMA foreign_keys = OFF;")
+        .map_err(|e| DbError::from_diesel("Could not disable foreign keys before migrating".to_string(), e))?;
+    let migrated = apply_migrations(connection, current_version, target_version);
+    connection.batch_execute("PRAGMA foreign_keys = ON;")
+        .map_err(|e| DbError::from_diesel("Could not enable foreign keys after migrating".to_string(), e))?;
+    return migrated;
+}
+
+fn apply_migrations(connection : &mut SqliteConnection, current_version : i32, target_version : i32) -> Result<i32, DbError> {
+    for (index, migration) in MIGRATIONS.iter().enumerate().take(target_version as usize).skip(current_version as usize) {
+        let migration_version = index as i32 + 1;
+        connection.transaction::<_, diesel::result::Error, _>(|connection| {
+            connection.batch_execute(migration)?;
+            insert_into(schema_version)
+                .values((version.eq(migration_version), applied_at.eq(Utc::now().to_rfc3339())))
+                .execute(connection)?;
+            Ok(())
+        }).map_err(|e| DbError::from_diesel(format!("An error occured while applying migration {}", migration_version), e))?;
+    }
+    return Ok(target_version.max(current_version));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use diesel::{connection::SimpleConnection, insert_into, ExpressionMethods, QueryDsl, RunQueryDsl};
+
+    use crate::db_manager::{create_database, delete_database, entities::{get_authors, get_changes_by_user, get_document_by_id, get_document_history, get_document_tag, get_tags, schema::{document_genre, document_tag, schema_version::dsl::*}, Author, DocumentTag, Tag}, get_connection, DbError};
+
+    use super::{get_schema_version, migrate_to, run_migrations, LATEST_SCHEMA_VERSION};
+
+    #[test]
+    fn created_database_should_be_at_latest_version() {
+        let test_db_path = Path::new("./created_database_should_be_at_latest_version.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        assert_eq!(LATEST_SCHEMA_VERSION, get_schema_version(&mut connection).unwrap());
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn running_migrations_twice_should_keep_latest_version() {
+        let test_db_path = Path::new("./running_migrations_twice_should_keep_latest_version.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        assert_eq!(LATEST_SCHEMA_VERSION, run_migrations(&mut connection).unwrap());
+        assert_eq!(LATEST_SCHEMA_VERSION, get_schema_version(&mut connection).unwrap());
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn database_newer_than_binary_should_be_refused() {
+        let test_db_path = Path::new("./database_newer_than_binary_should_be_refused.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        insert_into(schema_version)
+            .values((version.eq(LATEST_SCHEMA_VERSION + 1), applied_at.eq("2099-01-01T00:00:00+00:00")))
+            .execute(&mut connection)
+            .unwrap();
+        assert!(matches!(run_migrations(&mut connection), Err(DbError::Conflict(_))));
+        assert!(matches!(create_database(test_db_path), Err(DbError::Conflict(_))));
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn migrating_should_clean_rows_pointing_at_deleted_rows() {
+        let test_db_path = Path::new("./migrating_should_clean_rows_pointing_at_deleted_rows.db");
+        let mut connection = get_connection(test_db_path).unwrap();
+        migrate_to(&mut connection, 1).unwrap();
+        connection.batch_execute("PRAGMA foreign_keys = OFF;
+            INSERT INTO category (id, name, path) VALUES (1, 'Books', '~/Documents/Books');
+            INSERT INTO document (id, name, category, author, series, date, path) VALUES (1, 'The Two Towers', 1, 42, 42, '1954-11-11', 'Tolkien/the_two_towers.epub');
+            INSERT INTO document_tag (document, tag) VALUES (1, 42);
+            INSERT INTO document_genre (document, genre) VALUES (42, 1);
+            PRAGMA foreign_keys = ON;").unwrap();
+        run_migrations(&mut connection).unwrap();
+        let the_two_towers = get_document_by_id(&mut connection, &1).unwrap();
+        assert_eq!(None, the_two_towers.author_);
+        assert_eq!(None, the_two_towers.series_);
+        assert_eq!(0, document_tag::table.count().get_result::<i64>(&mut connection).unwrap());
+        assert_eq!(0, document_genre::table.count().get_result::<i64>(&mut connection).unwrap());
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn migrating_should_fold_duplicated_names() {
+        let test_db_path = Path::new("./migrating_should_fold_duplicated_names.db");
+        let mut connection = get_connection(test_db_path).unwrap();
+        migrate_to(&mut connection, 2).unwrap();
+        connection.batch_execute("INSERT INTO category (id, name, path) VALUES (1, 'Books', '~/Documents/Books');
+            INSERT INTO author (id, name) VALUES (1, 'J.R.R Tolkien'), (2, 'J.R.R Tolkien');
+            INSERT INTO document (id, name, category, author, series, date, path) VALUES (1, 'The Two Towers', 1, 2, NULL, '1954-11-11', 'Tolkien/the_two_towers.epub');
+            INSERT INTO tag (id, name) VALUES (1, 'banger'), (2, 'banger');
+            INSERT INTO document_tag (document, tag) VALUES (1, 1), (1, 2);").unwrap();
+        run_migrations(&mut connection).unwrap();
+        assert_eq!(vec![Author::new(1, "J.R.R Tolkien".to_string())], get_authors(&mut connection).unwrap());
+        assert_eq!(Some(1), get_document_by_id(&mut connection, &1).unwrap().author_);
+        assert_eq!(vec![Tag::new(1, "banger".to_string(), None)], get_tags(&mut connection).unwrap());
+        assert_eq!(DocumentTag::new(1, 1), get_document_tag(&mut connection, &1, &1).unwrap());
+        assert_eq!(1, document_tag::table.count().get_result::<i64>(&mut connection).unwrap());
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn migrating_should_normalize_document_dates() {
+        let test_db_path = Path::new("./migrating_should_normalize_document_dates.db");
+        let mut connection = get_connection(test_db_path).unwrap();
+        migrate_to(&mut connection, 10).unwrap();
+        connection.batch_execute("INSERT INTO category (id, name, path) VALUES (1, 'Books', '~/Documents/Books');
+            INSERT INTO document (id, name, category, date, path) VALUES
+                (1, 'The Hobbit', 1, '1937-09-21', 'Tolkien/the_hobbit.epub'),
+                (2, 'The Two Towers', 1, '1954-11-11T00:00:00', 'Tolkien/the_two_towers.epub'),
+                (3, 'The Fellowship Of The Ring', 1, '1954-06', 'Tolkien/the_fellowship_of_the_ring.epub'),
+                (4, 'The Silmarillion', 1, 'September 1977', 'Tolkien/the_silmarillion.epub'),
+                (5, 'Unfinished Tales', 1, '1980-02-30', 'Tolkien/unfinished_tales.epub');").unwrap();
+        run_migrations(&mut connection).unwrap();
+        let dates = (1..=5).map(|document_id| get_document_by_id(&mut connection, &document_id).unwrap().date_.map(|document_date| document_date.to_string())).collect::<Vec<_>>();
+        assert_eq!(vec![Some("1937-09-21".to_string()), Some("1954-11-11".to_string()), Some("1954-06".to_string()), None, None], dates);
+        let migration_changes = get_document_history(&mut connection, &2).unwrap().into_iter().map(|change| (change.field_, change.changed_by_)).collect::<Vec<_>>();
+        assert_eq!(vec![("created".to_string(), None), ("date".to_string(), Some("migration 11".to_string()))], migration_changes);
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn migrating_should_make_document_paths_relative() {
+        let test_db_path = Path::new("./migrating_should_make_document_paths_relative.db");
+        let mut connection = get_connection(test_db_path).unwrap();
+        migrate_to(&mut connection, 11).unwrap();
+        connection.batch_execute("INSERT INTO category (id, name, path) VALUES (1, 'Books', '/mnt/library/Books/');
+            INSERT INTO document (id, name, category, path) VALUES
+                (1, 'The Hobbit', 1, '/mnt/library/Books/Tolkien/the_hobbit.epub'),
+                (2, 'The Two Towers', 1, 'Tolkien/the_two_towers.epub'),
+                (3, 'Notes', 1, '/mnt/library/Books-old/notes.pdf');").unwrap();
+        run_migrations(&mut connection).unwrap();
+        let paths = (1..=3).map(|document_id| get_document_by_id(&mut connection, &document_id).unwrap().path_).collect::<Vec<_>>();
+        assert_eq!(vec!["Tolkien/the_hobbit.epub".to_string(), "Tolkien/the_two_towers.epub".to_string(), "/mnt/library/Books-old/notes.pdf".to_string()], paths);
+        assert_eq!(1, get_changes_by_user(&mut connection, &"migration 12".to_string()).unwrap().len());
+        delete_database(test_db_path).unwrap();
+    }
+}
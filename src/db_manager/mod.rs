@@ -1,4 +1,20 @@
 pub(crate) mod entities;
+pub(crate) mod migrations;
+pub(crate) mod error;
+pub(crate) mod pool;
+pub(crate) mod import;
+pub(crate) mod document_query;
+pub(crate) mod search;
+pub(crate) mod partial_date;
+pub(crate) mod fingerprint;
+
+pub use error::DbError;
+pub use pool::{create_pool, run_blocking, DbPool};
+pub use import::{import_document, DocumentImport};
+pub use document_query::{get_documents_matching, DocumentCursor, DocumentQuery, DocumentSort, MatchMode, SortDirection};
+pub use search::search_documents;
+pub use partial_date::PartialDate;
+pub use fingerprint::{compute_fingerprint, format_modified_at, get_documents_by_hash, set_document_fingerprint, FileFingerprint};
 
 pub use entities::document::*;
 pub use entities::category::*;
@@ -9,109 +25,49 @@ pub use entities::series::*;
 pub use entities::tag::*;
 // pub use entities::author_series::*;
 pub use entities::document_tag::*;
+pub use entities::document_contributor::*;
+pub use entities::document_category::*;
+pub use entities::document_identifier::*;
+pub use entities::change_history::*;
 
 use std::{fs, path::Path};
-use diesel::{connection::SimpleConnection, Connection, SqliteConnection};
-
-const DB_CREATION_QUERY : &str =
-    "CREATE TABLE IF NOT EXISTS author (
-        id INTEGER PRIMARY KEY AUTOINCREMENT,
-        name TEXT NOT NULL
-    );
-
-    CREATE TABLE IF NOT EXISTS series (
-        id INTEGER PRIMARY KEY AUTOINCREMENT,
-        author INTEGER,
-        name TEXT NOT NULL,
-        FOREIGN KEY (author) REFERENCES author(id)
-    );
-
-    CREATE TABLE IF NOT EXISTS tag (
-        id INTEGER PRIMARY KEY AUTOINCREMENT,
-        name TEXT NOT NULL
-    );
-
-    CREATE TABLE IF NOT EXISTS genre (
-        id INTEGER PRIMARY KEY AUTOINCREMENT,
-        name TEXT NOT NULL
-    );
-
-    CREATE TABLE IF NOT EXISTS category (
-        id INTEGER PRIMARY KEY AUTOINCREMENT,
-        name TEXT NOT NULL,
-        path TEXT NOT NULL    
-    );
-
-    CREATE TABLE IF NOT EXISTS document (
-        id INTEGER PRIMARY KEY AUTOINCREMENT,
-        name TEXT NOT NULL,
-        category INTEGER,
-        author INTEGER,
-        series INTEGER,
-        date TEXT,
-        path TEXT NOT NULL,
-    FOREIGN KEY (category) REFERENCES category(id),
-    FOREIGN KEY (author) REFERENCES author(id),
-    FOREIGN KEY (series) REFERENCES series(id)
-    );
-        
-    CREATE TABLE IF NOT EXISTS document_category (
-        document INTEGER,
-        category INTEGER,
-        PRIMARY KEY (document, category),
-        FOREIGN KEY (document) REFERENCES document(id),
-        FOREIGN KEY (category) REFERENCES category(id)
-    );
-
-    CREATE TABLE IF NOT EXISTS document_genre (
-        document INTEGER,
-        genre INTEGER,
-        PRIMARY KEY (document, genre),
-        FOREIGN KEY (document) REFERENCES document(id),
-        FOREIGN KEY (genre) REFERENCES genre(id)
-    );
-
-    CREATE TABLE IF NOT EXISTS document_tag (
-        document INTEGER,
-        tag INTEGER,
-        PRIMARY KEY (document, tag),
-        FOREIGN KEY (document) REFERENCES document(id),
-        FOREIGN KEY (tag) REFERENCES tag(id)
-    );
-
-    CREATE TABLE IF NOT EXISTS users (
-        id INTEGER PRIMARY KEY AUTOINCREMENT,
-        username TEXT NOT NULL,
-        email TEXT DEFAULT NULL,
-        password TEXT NOT NULL
-    );
-
-    ";
-
-fn establish_connection(db_url : &str) -> Result<SqliteConnection, String> {
-    return SqliteConnection::establish(&db_url)
-        .map_err(|_| format!("Error connecting to {}", db_url));
+use diesel::{Connection, SqliteConnection};
+use migrations::run_migrations;
+use pool::configure_connection;
+
+fn establish_connection(db_url : &str) -> Result<SqliteConnection, DbError> {
+    let mut connection = SqliteConnection::establish(&db_url)
+        .map_err(|e| DbError::Io(format!("Error connecting to {} : {}", db_url, e)))?;
+    configure_connection(&mut connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not configure connection to {}", db_url), e))?;
+    return Ok(connection);
 }
 
-pub fn create_database(db_path : &Path) -> Result<(), String> {
+/// Creates the database if needed and brings its schema up to date.
+pub fn create_database(db_path : &Path) -> Result<(), DbError> {
     return match establish_connection(db_path.to_str().unwrap()) {
-        Err(e) => Err(format!("An error occured while creating database {} : {}", db_path.display(), e)),
+        Err(e) => Err(DbError::Io(format!("An error occured while creating database {} : {}", db_path.display(), e))),
         Ok(mut connection) => {
-            connection.batch_execute(DB_CREATION_QUERY)
-                .map_err(|e| format!("An error occured while initiallizing database {} : {}", db_path.display(), e))?;
+            run_migrations(&mut connection)?;
             return Ok(());
         }
     }
 }
 
-pub fn get_connection(db_path : &Path) -> Result<SqliteConnection, String> {
+pub fn get_connection(db_path : &Path) -> Result<SqliteConnection, DbError> {
     return establish_connection(db_path.to_str().unwrap())
-        .map_err(|e| format!("Could not access to databse {} : {}", db_path.display(), e));
+        .map_err(|e| DbError::Io(format!("Could not access to databse {} : {}", db_path.display(), e)));
 }
 
-pub fn delete_database(db_path: &Path) -> Result<(), String> {
+pub fn delete_database(db_path: &Path) -> Result<(), DbError> {
+    for suffix in ["-wal", "-shm"] {
+        let journal_path = format!("{}{}", db_path.display(), suffix);
+        if Path::new(&journal_path).exists() {
+            fs::remove_file(&journal_path).map_err(|e| DbError::Io(format!("An error occurred while deleting the database journal {}: {}", journal_path, e)))?;
+        }
+    }
     if db_path.exists() {
-        fs::remove_file(db_path).map_err(|e| format!("An error occurred while deleting the database {}: {}",db_path.display(), e))
+        fs::remove_file(db_path).map_err(|e| DbError::Io(format!("An error occurred while deleting the database {}: {}",db_path.display(), e)))
     } else {
         Ok(())
     }
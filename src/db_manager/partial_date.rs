@@ -0,0 +1,129 @@
+use std::{fmt, str::FromStr};
+use chrono::{Datelike, NaiveDate};
+use diesel::{backend::Backend, deserialize::{self, FromSql, FromSqlRow}, expression::AsExpression, serialize::{self, IsNull, Output, ToSql}, sql_types::Text, sqlite::Sqlite};
+
+/// Publication date known to the year, the month or the day. Stored as "1954", "1954-06" or "1954-06-29",
+/// which sort in chronological order as text.
+/// Only built through its checked constructors, so the period it covers is always a valid one.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Text)]
+pub struct PartialDate {
+    first_day_ : NaiveDate,
+    precision_ : DatePrecision
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+enum DatePrecision {
+    Year,
+    Month,
+    Day
+}
+
+impl PartialDate {
+    pub fn year(year : i32) -> Option<PartialDate> {
+        return NaiveDate::from_ymd_opt(year, 1, 1).map(|first_day| PartialDate { first_day_ : first_day, precision_ : DatePrecision::Year });
+    }
+
+    pub fn year_month(year : i32, month : u32) -> Option<PartialDate> {
+        return NaiveDate::from_ymd_opt(year, month, 1).map(|first_day| PartialDate { first_day_ : first_day, precision_ : DatePrecision::Month });
+    }
+
+    pub fn first_day(&self) -> NaiveDate {
+        return self.first_day_;
+    }
+
+    pub fn last_day(&self) -> NaiveDate {
+        let (year, month) = (self.first_day_.year(), self.first_day_.month());
+        return match self.precision_ {
+            DatePrecision::Year => NaiveDate::from_ymd_opt(year, 12, 31),
+            DatePrecision::Month => [31, 30, 29, 28].into_iter().find_map(|day| NaiveDate::from_ymd_opt(year, month, day)),
+            DatePrecision::Day => None
+        }.unwrap_or(self.first_day_);
+    }
+}
+
+impl From<NaiveDate> for PartialDate {
+    fn from(day : NaiveDate) -> PartialDate {
+        PartialDate { first_day_ : day, precision_ : DatePrecision::Day }
+    }
+}
+
+impl fmt::Display for PartialDate {
+    fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result {
+        let day = self.first_day_;
+        match self.precision_ {
+            DatePrecision::Year => write!(f, "{:04}", day.year()),
+            DatePrecision::Month => write!(f, "{:04}-{:02}", day.year(), day.month()),
+            DatePrecision::Day => write!(f, "{:04}-{:02}-{:02}", day.year(), day.month(), day.day())
+        }
+    }
+}
+
+impl FromStr for PartialDate {
+    type Err = String;
+
+    fn from_str(value : &str) -> Result<PartialDate, String> {
+        let parts : Vec<&str> = value.split('-').collect();
+        let invalid = || format!("Invalid date {}, expected YYYY, YYYY-MM or YYYY-MM-DD", value);
+        if parts.iter().zip([4, 2, 2]).any(|(part, length)| part.len() != length || !part.chars().all(|c| c.is_ascii_digit())) {
+            return Err(invalid());
+        }
+        let year = parts[0].parse::<i32>().map_err(|_| invalid())?;
+        return match parts.len() {
+            1 => PartialDate::year(year).ok_or_else(invalid),
+            2 => {
+                let month = parts[1].parse::<u32>().map_err(|_| invalid())?;
+                PartialDate::year_month(year, month).ok_or_else(invalid)
+            },
+            3 => NaiveDate::parse_from_str(value, "%Y-%m-%d").map(PartialDate::from).map_err(|_| invalid()),
+            _ => Err(invalid())
+        };
+    }
+}
+
+impl ToSql<Text, Sqlite> for PartialDate {
+    fn to_sql<'b>(&'b self, out : &mut Output<'b, '_, Sqlite>) -> serialize::Result {
+        out.set_value(self.to_string());
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Text, Sqlite> for PartialDate {
+    fn from_sql(bytes : <Sqlite as Backend>::RawValue<'_>) -> deserialize::Result<PartialDate> {
+        let value = <String as FromSql<Text, Sqlite>>::from_sql(bytes)?;
+        return value.parse::<PartialDate>().map_err(|e| e.into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::PartialDate;
+
+    #[test]
+    fn partial_dates_should_round_trip_through_text() {
+        for (text, partial_date) in [("1954", PartialDate::year(1954).unwrap()), ("1954-06", PartialDate::year_month(1954, 6).unwrap()), ("1954-06-29", PartialDate::from(NaiveDate::from_ymd_opt(1954, 6, 29).unwrap()))] {
+            assert_eq!(Ok(partial_date), text.parse::<PartialDate>());
+            assert_eq!(text, partial_date.to_string());
+        }
+        for invalid in ["54", "1954-13", "1954-6", "1954-02-30", "June 1954", "1954-06-29T00:00:00", ""] {
+            assert!(invalid.parse::<PartialDate>().is_err(), "{} should not parse", invalid);
+        }
+    }
+
+    #[test]
+    fn partial_dates_should_cover_their_whole_period() {
+        assert_eq!((NaiveDate::from_ymd_opt(1954, 1, 1).unwrap(), NaiveDate::from_ymd_opt(1954, 12, 31).unwrap()), (PartialDate::year(1954).unwrap().first_day(), PartialDate::year(1954).unwrap().last_day()));
+        assert_eq!(NaiveDate::from_ymd_opt(1956, 2, 29).unwrap(), PartialDate::year_month(1956, 2).unwrap().last_day());
+        assert_eq!(NaiveDate::from_ymd_opt(1954, 12, 31).unwrap(), PartialDate::year_month(1954, 12).unwrap().last_day());
+        assert_eq!(NaiveDate::from_ymd_opt(1954, 4, 30).unwrap(), PartialDate::year_month(1954, 4).unwrap().last_day());
+    }
+
+    #[test]
+    fn partial_dates_should_only_be_built_from_valid_periods() {
+        assert_eq!(None, PartialDate::year_month(2021, 13));
+        assert_eq!(None, PartialDate::year_month(2021, 0));
+        assert_eq!(None, PartialDate::year(i32::MAX));
+    }
+}
@@ -0,0 +1,81 @@
+use std::path::Path;
+use diesel::{connection::SimpleConnection, r2d2::{self, ConnectionManager, CustomizeConnection, Pool}, SqliteConnection};
+use crate::db_manager::DbError;
+
+pub type DbPool = Pool<ConnectionManager<SqliteConnection>>;
+
+const POOL_SIZE : u32 = 8;
+const BUSY_TIMEOUT_MS : u32 = 5000;
+
+/// Pragmas applied to every connection, pooled or not.
+pub(crate) fn configure_connection(connection : &mut SqliteConnection) -> Result<(), diesel::result::Error> {
+    connection.batch_execute(&format!("PRAGMA journal_mode = WAL; PRAGMA busy_timeout = {}; PRAGMA foreign_keys = ON;", BUSY_TIMEOUT_MS))
+}
+
+#[derive(Debug)]
+struct ConnectionOptions;
+
+impl CustomizeConnection<SqliteConnection, r2d2::Error> for ConnectionOptions {
+    fn on_acquire(&self, connection : &mut SqliteConnection) -> Result<(), r2d2::Error> {
+        configure_connection(connection).map_err(r2d2::Error::QueryError)
+    }
+}
+
+pub fn create_pool(db_path : &Path) -> Result<DbPool, DbError> {
+    let manager = ConnectionManager::<SqliteConnection>::new(db_path.to_str().unwrap());
+    return Pool::builder()
+        .max_size(POOL_SIZE)
+        .connection_customizer(Box::new(ConnectionOptions))
+        .build(manager)
+        .map_err(|e| DbError::Io(format!("Could not create connection pool for database {} : {}", db_path.display(), e)));
+}
+
+/// Runs blocking database work on a pooled connection, away from the async runtime threads.
+pub async fn run_blocking<T, F>(pool : &DbPool, work : F) -> Result<T, DbError>
+where
+    F : FnOnce(&mut SqliteConnection) -> Result<T, DbError> + Send + 'static,
+    T : Send + 'static
+{
+    let pool = pool.clone();
+    return tokio::task::spawn_blocking(move || {
+        let mut connection = pool.get()
+            .map_err(|e| DbError::Io(format!("Could not get a database connection from the pool : {}", e)))?;
+        work(&mut connection)
+    }).await
+    .map_err(|e| DbError::Io(format!("Database task did not complete : {}", e)))?;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use diesel::{dsl::sql, select, sql_types::Text, RunQueryDsl};
+
+    use crate::db_manager::{create_database, delete_database, entities::{add_author, get_authors}};
+
+    use super::{create_pool, run_blocking};
+
+    #[test]
+    fn pooled_connections_should_use_wal_journal() {
+        let test_db_path = Path::new("./pooled_connections_should_use_wal_journal.db");
+        create_database(test_db_path).unwrap();
+        let pool = create_pool(test_db_path).unwrap();
+        let mut connection = pool.get().unwrap();
+        let journal_mode = select(sql::<Text>("(SELECT journal_mode FROM pragma_journal_mode())")).get_result::<String>(&mut connection).unwrap();
+        assert_eq!("wal", journal_mode);
+        drop(connection);
+        drop(pool);
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn running_blocking_work_should_give_its_result() {
+        let test_db_path = Path::new("./running_blocking_work_should_give_its_result.db");
+        create_database(test_db_path).unwrap();
+        let pool = create_pool(test_db_path).unwrap();
+        let jrr_tolkien = run_blocking(&pool, |connection| add_author(connection, &"J.R.R Tolkien".to_string())).await.unwrap();
+        let authors = run_blocking(&pool, |connection| get_authors(connection)).await.unwrap();
+        assert_eq!(vec![jrr_tolkien], authors);
+        drop(pool);
+        delete_database(test_db_path).unwrap();
+    }
+}
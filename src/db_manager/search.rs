@@ -0,0 +1,90 @@
+use diesel::{sql_query, sql_types::{BigInt, Integer, Text}, ExpressionMethods, QueryDsl, QueryableByName, RunQueryDsl, SqliteConnection};
+use crate::db_manager::{entities::{schema::document::dsl::*, Document}, DbError};
+
+/// Weights of the document_search columns : name, authors, series, tags, genres.
+const SEARCH_RANKING : &str = "bm25(document_search, 10.0, 5.0, 3.0, 1.0, 1.0)";
+
+#[derive(QueryableByName)]
+struct SearchHit {
+    #[diesel(sql_type = Integer)]
+    document_id : i32
+}
+
+/// Turns user input into an FTS5 query where every word is a prefix that must match,
+/// so quotes or operators typed by the user can't break the query.
+fn to_match_query(text : &str) -> String {
+    return text.split_whitespace()
+        .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect::<Vec<String>>()
+        .join(" ");
+}
+
+/// Gives the documents matching the text in their name, authors, series, tags or genres, best matches first.
+pub fn search_documents(connection : &mut SqliteConnection, text : &String, max_results : &i64) -> Result<Vec<Document>, DbError> {
+    let match_query = to_match_query(text);
+    if match_query.is_empty() {
+        return Ok(Vec::new());
+    }
+    let hits = sql_query(format!("SELECT rowid AS document_id FROM document_search WHERE document_search MATCH ? AND rowid IN (SELECT id FROM document WHERE deleted_at IS NULL) ORDER BY {} LIMIT ?", SEARCH_RANKING))
+        .bind::<Text, _>(&match_query)
+        .bind::<BigInt, _>(max_results)
+        .load::<SearchHit>(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not search documents for {}", text), e))?;
+    let hit_ids : Vec<i32> = hits.iter().map(|hit| hit.document_id).collect();
+    let mut found_documents = document.filter(id.eq_any(&hit_ids)).load::<Document>(connection)
+        .map_err(|e| DbError::from_diesel(format!("Could not get documents found for {}", text), e))?;
+    found_documents.sort_by_key(|found| hit_ids.iter().position(|hit_id| *hit_id == found.id_));
+    return Ok(found_documents);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use chrono::NaiveDate;
+
+    use crate::db_manager::{create_database, delete_database, entities::{add_author, add_category, add_document, add_series, add_tag, link_tag_to_document, remove_document, rename_author, unlink_tag_to_document}, get_connection};
+
+    use super::search_documents;
+
+    #[test]
+    fn searching_documents_should_rank_name_matches_first() {
+        let test_db_path = Path::new("./searching_documents_should_rank_name_matches_first.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let books = add_category(&mut connection, &"Books".to_string(), &"~/Documents/Books".to_string()).unwrap();
+        let jrr_tolkien = add_author(&mut connection, &"J.R.R Tolkien".to_string()).unwrap();
+        let the_lord_of_the_rings = add_series(&mut connection, &"The Lord Of The Rings".to_string(), &jrr_tolkien.id_).unwrap();
+        let the_two_towers = add_document(&mut connection, &"The Two Towers".to_string(), &books.id_, &Some(jrr_tolkien.id_), &Some(the_lord_of_the_rings.id_), &Some(NaiveDate::from_ymd_opt(1954, 11, 11).unwrap().into()), &"Tolkien/the_two_towers.epub".to_string()).unwrap();
+        let the_hobbit = add_document(&mut connection, &"The Hobbit".to_string(), &books.id_, &Some(jrr_tolkien.id_), &None, &Some(NaiveDate::from_ymd_opt(1937, 9, 21).unwrap().into()), &"Tolkien/the_hobbit.epub".to_string()).unwrap();
+        let hobbit_fans = add_tag(&mut connection, &"hobbit fans".to_string()).unwrap();
+        link_tag_to_document(&mut connection, &the_two_towers.id_, &hobbit_fans.id_).unwrap();
+        assert_eq!(vec![the_hobbit.clone(), the_two_towers.clone()], search_documents(&mut connection, &"hobbit".to_string(), &10).unwrap());
+        assert_eq!(vec![the_two_towers.clone()], search_documents(&mut connection, &"lord tow".to_string(), &10).unwrap());
+        assert_eq!(2, search_documents(&mut connection, &"tolkien".to_string(), &10).unwrap().len());
+        assert_eq!(1, search_documents(&mut connection, &"tolkien".to_string(), &1).unwrap().len());
+        assert!(search_documents(&mut connection, &"\"hobbit OR *".to_string(), &10).is_ok());
+        assert!(search_documents(&mut connection, &"   ".to_string(), &10).unwrap().is_empty());
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn search_index_should_follow_changes() {
+        let test_db_path = Path::new("./search_index_should_follow_changes.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let mangas = add_category(&mut connection, &"Mangas".to_string(), &"~/Documents/Mangas".to_string()).unwrap();
+        let oda = add_author(&mut connection, &"Eiichiro Oda".to_string()).unwrap();
+        let one_piece_ch_1001 = add_document(&mut connection, &"Chapter 1001".to_string(), &mangas.id_, &Some(oda.id_), &None, &Some(NaiveDate::from_ymd_opt(2021, 1, 18).unwrap().into()), &"Oda/One piece/chapter-1001.cbz".to_string()).unwrap();
+        let wano = add_tag(&mut connection, &"Wano".to_string()).unwrap();
+        link_tag_to_document(&mut connection, &one_piece_ch_1001.id_, &wano.id_).unwrap();
+        assert_eq!(vec![one_piece_ch_1001.clone()], search_documents(&mut connection, &"wano".to_string(), &10).unwrap());
+        unlink_tag_to_document(&mut connection, &one_piece_ch_1001.id_, &wano.id_).unwrap();
+        assert!(search_documents(&mut connection, &"wano".to_string(), &10).unwrap().is_empty());
+        rename_author(&mut connection, &oda.id_, &"尾田栄一郎".to_string()).unwrap();
+        assert!(search_documents(&mut connection, &"eiichiro".to_string(), &10).unwrap().is_empty());
+        assert_eq!(vec![one_piece_ch_1001.clone()], search_documents(&mut connection, &"尾田栄一郎".to_string(), &10).unwrap());
+        remove_document(&mut connection, &one_piece_ch_1001.id_).unwrap();
+        assert!(search_documents(&mut connection, &"chapter".to_string(), &10).unwrap().is_empty());
+        delete_database(test_db_path).unwrap();
+    }
+}
@@ -0,0 +1,208 @@
+use std::{collections::HashMap, fs::File, io::Read, path::Path};
+use quick_xml::{events::Event, Reader};
+use sevenz_rust::{Password, SevenZReader};
+use zip::ZipArchive;
+
+use crate::db_manager::{ContributorRole, PartialDate};
+
+use super::ExtractedMetadata;
+
+/// Credits of ComicInfo.xml and the role they give, each one is a comma separated list of names.
+const CREDITS : [(&str, ContributorRole); 4] = [("Writer", ContributorRole::Author), 
+                                                ("Penciller", ContributorRole::Illustrator), 
+                                                ("Translator", ContributorRole::Translator), 
+                                                ("Editor", ContributorRole::Editor)];
+
+fn is_comic_info(entry_name : &str) -> bool {
+    return entry_name.rsplit('/').next().is_some_and(|file_name| file_name.eq_ignore_ascii_case("ComicInfo.xml"));
+}
+
+/// Signature every RAR archive, version 4 or 5, starts with.
+const RAR_SIGNATURE : &[u8] = b"Rar!\x1a\x07";
+
+/// Tells whether the file is a RAR archive, whatever its extension says.
+pub(crate) fn is_rar_archive(file_path : &Path) -> bool {
+    let mut signature = [0u8; 6];
+    return File::open(file_path).and_then(|mut file| file.read_exact(&mut signature)).is_ok() && signature == RAR_SIGNATURE;
+}
+
+pub(crate) fn unsupported_rar_archive(file_path : &Path) -> String {
+    return format!("Could not read {} : RAR archives are not supported, only CBR files that are zip archives", file_path.display());
+}
+
+/// Reads ComicInfo.xml from a CBZ, CB7 or CBR archive, archives without one give nothing.
+/// RAR is not supported : a CBR is only read when it actually is a zip archive, which is common.
+pub fn read_comic_info_metadata(file_path : &Path) -> Result<ExtractedMetadata, String> {
+    if is_rar_archive(file_path) {
+        return Err(unsupported_rar_archive(file_path));
+    }
+    let is_seven_zip = file_path.extension().is_some_and(|e| e.eq_ignore_ascii_case("cb7"));
+    let comic_info = match is_seven_zip {
+        true => read_seven_zip_comic_info(file_path)?,
+        false => read_zip_comic_info(file_path)?
+    };
+    return match comic_info {
+        Some(comic_info) => parse_comic_info(&comic_info).map_err(|e| format!("Could not read ComicInfo.xml of {} : {}", file_path.display(), e)),
+        None => Ok(ExtractedMetadata::default())
+    };
+}
+
+fn read_zip_comic_info(file_path : &Path) -> Result<Option<String>, String> {
+    let file = File::open(file_path)
+        .map_err(|e| format!("Could not open archive {} : {}", file_path.display(), e))?;
+    let mut archive = ZipArchive::new(file)
+        .map_err(|e| format!("Could not read archive {} : {}", file_path.display(), e))?;
+    let Some(entry_name) = archive.file_names().find(|entry_name| is_comic_info(entry_name)).map(str::to_string) else {
+        return Ok(None);
+    };
+    let mut comic_info = String::new();
+    archive.by_name(&entry_name).and_then(|mut entry| Ok(entry.read_to_string(&mut comic_info)?))
+        .map_err(|e| format!("Could not read {} in archive {} : {}", entry_name, file_path.display(), e))?;
+    return Ok(Some(comic_info));
+}
+
+fn read_seven_zip_comic_info(file_path : &Path) -> Result<Option<String>, String> {
+    let mut archive = SevenZReader::open(file_path, Password::empty())
+        .map_err(|e| format!("Could not read archive {} : {}", file_path.display(), e))?;
+    let mut comic_info : Option<String> = None;
+    archive.for_each_entries(|entry, entry_reader| {
+        if entry.is_directory() || !is_comic_info(entry.name()) {
+            return Ok(true);
+        }
+        let mut content = String::new();
+        entry_reader.read_to_string(&mut content)?;
+        comic_info = Some(content);
+        Ok(false)
+    }).map_err(|e| format!("Could not read archive {} : {}", file_path.display(), e))?;
+    return Ok(comic_info);
+}
+
+fn split_list(list : &str) -> Vec<String> {
+    return list.split(',').map(|item| item.trim().to_string()).filter(|item| !item.is_empty()).collect();
+}
+
+/// Gives the date as precise as ComicInfo knows it, Year then Month then Day.
+fn comic_date(fields : &HashMap<String, String>) -> Option<PartialDate> {
+    let part = |field_name : &str| fields.get(field_name).and_then(|field| field.parse::<u32>().ok());
+    let year = fields.get("Year").and_then(|field| field.parse::<i32>().ok()).filter(|year| *year > 0)?;
+    let date_text = match (part("Month"), part("Day")) {
+        (Some(month), Some(day)) => format!("{:04}-{:02}-{:02}", year, month, day),
+        (Some(month), None) => format!("{:04}-{:02}", year, month),
+        _ => format!("{:04}", year)
+    };
+    // a wrong day or month still leaves the year
+    return date_text.parse::<PartialDate>().ok().or_else(|| PartialDate::year(year));
+}
+
+fn parse_comic_info(comic_info : &str) -> Result<ExtractedMetadata, String> {
+    let mut reader = Reader::from_str(comic_info);
+    let mut fields : HashMap<String, String> = HashMap::new();
+    let mut text = String::new();
+    loop {
+        match reader.read_event().map_err(|e| e.to_string())? {
+            Event::Start(_) => text.clear(),
+            Event::Text(content) => text.push_str(&content.unescape().map_err(|e| e.to_string())?),
+            Event::End(element) => {
+                let field = text.trim();
+                if !field.is_empty() {
+                    fields.insert(String::from_utf8_lossy(element.local_name().as_ref()).to_string(), field.to_string());
+                }
+                text.clear();
+            },
+            Event::Eof => break,
+            _ => ()
+        }
+    }
+    let list = |field_name : &str| fields.get(field_name).map(|field| split_list(field)).unwrap_or_default();
+    let contributors = CREDITS.iter()
+        .flat_map(|(credit, role)| list(credit).into_iter().map(move |contributor_name| (contributor_name, *role)))
+        .collect();
+    let right_to_left = match fields.get("Manga").map(String::as_str) {
+        Some("YesAndRightToLeft") => Some(true),
+        Some("Yes") | Some("No") => Some(false),
+        _ => None
+    };
+    return Ok(ExtractedMetadata {
+        title_ : fields.get("Title").cloned(),
+        contributors_ : contributors,
+        date_ : comic_date(&fields),
+        language_ : fields.get("LanguageISO").cloned(),
+        description_ : fields.get("Summary").cloned(),
+        publisher_ : fields.get("Publisher").cloned(),
+        identifiers_ : fields.get("GTIN").map(|gtin| vec![("gtin".to_string(), gtin.clone())]).unwrap_or_default(),
+        tags_ : list("Tags"),
+        genres_ : list("Genre"),
+        series_ : fields.get("Series").cloned(),
+        series_index_ : fields.get("Number").and_then(|number| number.parse::<f64>().ok()),
+        page_count_ : fields.get("PageCount").and_then(|page_count| page_count.parse::<i32>().ok()),
+        right_to_left_ : right_to_left
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::{remove_file, File}, io::Write, path::Path};
+    use sevenz_rust::{SevenZArchiveEntry, SevenZWriter};
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    use crate::{db_manager::{ContributorRole, PartialDate}, document_scanner::ExtractedMetadata};
+
+    use super::read_comic_info_metadata;
+
+    const COMIC_INFO : &str = r#"<?xml version="1.0"?>
+        <ComicInfo xmlns:xsd="http://www.w3.org/2001/XMLSchema">
+            <Title>The Man Who Became King</Title>
+            <Series>One Piece</Series>
+            <Number>1001</Number>
+            <Writer>Eiichiro Oda</Writer>
+            <Penciller>Eiichiro Oda, Hiroyuki Kato</Penciller>
+            <Genre>Shonen, Adventure</Genre>
+            <Tags>Wano, Onigashima</Tags>
+            <Year>2021</Year>
+            <Month>1</Month>
+            <Manga>YesAndRightToLeft</Manga>
+            <PageCount>17</PageCount>
+        </ComicInfo>"#;
+
+    #[test]
+    fn reading_cbz_should_give_comic_info_metadata() {
+        let cbz_path = Path::new("./reading_cbz_should_give_comic_info_metadata.cbz");
+        let mut cbz = ZipWriter::new(File::create(cbz_path).unwrap());
+        cbz.start_file("001.jpg", SimpleFileOptions::default()).unwrap();
+        cbz.start_file("ComicInfo.xml", SimpleFileOptions::default()).unwrap();
+        cbz.write_all(COMIC_INFO.as_bytes()).unwrap();
+        cbz.finish().unwrap();
+        let extracted = read_comic_info_metadata(cbz_path).unwrap();
+        assert_eq!(Some("The Man Who Became King".to_string()), extracted.title_);
+        assert_eq!((Some("One Piece".to_string()), Some(1001.0)), (extracted.series_, extracted.series_index_));
+        assert_eq!(vec![("Eiichiro Oda".to_string(), ContributorRole::Author), ("Eiichiro Oda".to_string(), ContributorRole::Illustrator), ("Hiroyuki Kato".to_string(), ContributorRole::Illustrator)], extracted.contributors_);
+        assert_eq!(vec!["Shonen".to_string(), "Adventure".to_string()], extracted.genres_);
+        assert_eq!(vec!["Wano".to_string(), "Onigashima".to_string()], extracted.tags_);
+        assert_eq!(Some(PartialDate::year_month(2021, 1).unwrap()), extracted.date_);
+        assert_eq!((Some(17), Some(true)), (extracted.page_count_, extracted.right_to_left_));
+        remove_file(cbz_path).unwrap();
+    }
+
+    #[test]
+    fn reading_cb7_should_give_comic_info_metadata() {
+        let cb7_path = Path::new("./reading_cb7_should_give_comic_info_metadata.cb7");
+        let mut cb7 = SevenZWriter::create(cb7_path).unwrap();
+        let mut entry = SevenZArchiveEntry::new();
+        entry.name = "One Piece/ComicInfo.xml".to_string();
+        cb7.push_archive_entry(entry, Some(COMIC_INFO.as_bytes())).unwrap();
+        cb7.finish().unwrap();
+        let extracted = read_comic_info_metadata(cb7_path).unwrap();
+        assert_eq!((Some("One Piece".to_string()), Some(1001.0)), (extracted.series_, extracted.series_index_));
+        remove_file(cb7_path).unwrap();
+    }
+
+    #[test]
+    fn reading_rar_archive_should_fail_as_unsupported() {
+        let cbr_path = Path::new("./reading_rar_archive_should_fail_as_unsupported.cbr");
+        File::create(cbr_path).unwrap().write_all(b"Rar!\x1a\x07\x01\x00").unwrap();
+        assert!(read_comic_info_metadata(cbr_path).unwrap_err().contains("RAR archives are not supported"));
+        File::create(cbr_path).unwrap().write_all(b"neither zip nor rar").unwrap();
+        assert!(read_comic_info_metadata(cbr_path).is_err());
+        remove_file(cbr_path).unwrap();
+    }
+}
@@ -0,0 +1,324 @@
+use std::{fmt, fs::{self, File}, io::Read, path::{Path, PathBuf}, process, str::FromStr, sync::atomic::{AtomicUsize, Ordering}};
+use diesel::SqliteConnection;
+use id3::{frame::PictureType, Tag};
+use image::{DynamicImage, ImageFormat};
+use sevenz_rust::{Password, SevenZReader};
+use zip::ZipArchive;
+
+use crate::db_manager::{get_document_by_id, get_documents_from_category, resolve_document_path, Category, DbError, Document};
+
+use super::{comic_info::{is_rar_archive, unsupported_rar_archive}, epub::read_epub_cover};
+
+const IMAGE_EXTENSIONS : [&str; 5] = ["jpg", "jpeg", "png", "gif", "webp"];
+
+/// Numbers the temporary thumbnail files, so concurrent writers of the same thumbnail don't share one.
+static TEMPORARY_FILES : AtomicUsize = AtomicUsize::new(0);
+
+/// Sizes thumbnails are generated in, each one fits a box with the 2:3 ratio of a book cover.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum ThumbnailSize {
+    Small,
+    Medium,
+    Large
+}
+
+impl ThumbnailSize {
+    pub const ALL : [ThumbnailSize; 3] = [ThumbnailSize::Small, ThumbnailSize::Medium, ThumbnailSize::Large];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ThumbnailSize::Small => "small",
+            ThumbnailSize::Medium => "medium",
+            ThumbnailSize::Large => "large"
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        match self {
+            ThumbnailSize::Small => 160,
+            ThumbnailSize::Medium => 320,
+            ThumbnailSize::Large => 640
+        }
+    }
+}
+
+impl fmt::Display for ThumbnailSize {
+    fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for ThumbnailSize {
+    type Err = String;
+
+    fn from_str(value : &str) -> Result<ThumbnailSize, String> {
+        match value {
+            "small" => Ok(ThumbnailSize::Small),
+            "medium" => Ok(ThumbnailSize::Medium),
+            "large" => Ok(ThumbnailSize::Large),
+            _ => Err(format!("Unknown thumbnail size {}", value))
+        }
+    }
+}
+
+fn is_image(entry_name : &str) -> bool {
+    return Path::new(entry_name).extension().and_then(|e| e.to_str()).is_some_and(|e| IMAGE_EXTENSIONS.contains(&e.to_lowercase().as_str()));
+}
+
+/// Extracts the cover art of a file : the cover image of an EPUB, the first page of a comic archive
+/// or the picture embedded in the ID3 tag of an audio file. Files without cover give nothing.
+pub fn extract_cover(file_path : &Path) -> Result<Option<Vec<u8>>, String> {
+    return match file_path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase()).as_deref() {
+        Some("epub") => read_epub_cover(file_path),
+        Some("cbz") | Some("cbr") => read_zip_first_page(file_path),
+        Some("cb7") => read_seven_zip_first_page(file_path),
+        Some("mp3") => read_id3_picture(file_path),
+        _ => Ok(None)
+    };
+}
+
+/// Pages are taken in the order of their names, which is the reading order of most archives.
+/// RAR is not supported, a CBR is only read when it actually is a zip archive.
+fn read_zip_first_page(file_path : &Path) -> Result<Option<Vec<u8>>, String> {
+    if is_rar_archive(file_path) {
+        return Err(unsupported_rar_archive(file_path));
+    }
+    let file = File::open(file_path)
+        .map_err(|e| format!("Could not open archive {} : {}", file_path.display(), e))?;
+    let mut archive = ZipArchive::new(file)
+        .map_err(|e| format!("Could not read archive {} : {}", file_path.display(), e))?;
+    let Some(first_page) = archive.file_names().filter(|entry_name| is_image(entry_name)).min().map(str::to_string) else {
+        return Ok(None);
+    };
+    let mut cover = Vec::new();
+    archive.by_name(&first_page).and_then(|mut entry| Ok(entry.read_to_end(&mut cover)?))
+        .map_err(|e| format!("Could not read {} in archive {} : {}", first_page, file_path.display(), e))?;
+    return Ok(Some(cover));
+}
+
+fn read_seven_zip_first_page(file_path : &Path) -> Result<Option<Vec<u8>>, String> {
+    let mut archive = SevenZReader::open(file_path, Password::empty())
+        .map_err(|e| format!("Could not read archive {} : {}", file_path.display(), e))?;
+    let Some(first_page) = archive.archive().files.iter()
+        .filter(|entry| !entry.is_directory() && is_image(entry.name()))
+        .map(|entry| entry.name().to_string())
+        .min() else {
+        return Ok(None);
+    };
+    let mut cover : Option<Vec<u8>> = None;
+    archive.for_each_entries(|entry, entry_reader| {
+        if entry.name() != first_page {
+            return Ok(true);
+        }
+        let mut content = Vec::new();
+        entry_reader.read_to_end(&mut content)?;
+        cover = Some(content);
+        Ok(false)
+    }).map_err(|e| format!("Could not read archive {} : {}", file_path.display(), e))?;
+    return Ok(cover);
+}
+
+/// The front cover picture is preferred, otherwise the first picture of the tag is used.
+fn read_id3_picture(file_path : &Path) -> Result<Option<Vec<u8>>, String> {
+    let tag = match Tag::read_from_path(file_path) {
+        Ok(tag) => tag,
+        Err(e) if matches!(e.kind, id3::ErrorKind::NoTag) => return Ok(None),
+        Err(e) => return Err(format!("Could not read ID3 tag of {} : {}", file_path.display(), e))
+    };
+    let picture = tag.pictures().find(|picture| picture.picture_type == PictureType::CoverFront)
+        .or_else(|| tag.pictures().next());
+    return Ok(picture.map(|picture| picture.data.clone()));
+}
+
+/// Directory holding the thumbnails of the document, they are named after the content hash
+/// so a modified file doesn't keep the cover of its previous content.
+fn document_cache_directory(cache_directory : &Path, cached_document : &Document) -> PathBuf {
+    return cache_directory.join(cached_document.id_.to_string());
+}
+
+fn cache_key(cached_document : &Document) -> String {
+    return cached_document.content_hash_.clone().unwrap_or_else(|| "unhashed".to_string());
+}
+
+pub fn thumbnail_path(cache_directory : &Path, cached_document : &Document, size : &ThumbnailSize) -> PathBuf {
+    return document_cache_directory(cache_directory, cached_document).join(format!("{}-{}.jpg", cache_key(cached_document), size));
+}
+
+/// Marks the document as having no cover, so its file isn't read again until its content changes.
+fn no_cover_path(cache_directory : &Path, cached_document : &Document) -> PathBuf {
+    return document_cache_directory(cache_directory, cached_document).join(format!("{}.none", cache_key(cached_document)));
+}
+
+/// Extracts the cover of the document file and writes its thumbnails in every size.
+/// A cover that can't be decoded is treated like a missing one, so the file isn't read again until
+/// its content changes. A file that can't be read is left unmarked, to be read again on the next request.
+/// Gives whether a cover was cached.
+pub fn cache_cover(cache_directory : &Path, cached_document : &Document, file_path : &Path) -> Result<bool, String> {
+    let document_directory = document_cache_directory(cache_directory, cached_document);
+    fs::create_dir_all(&document_directory)
+        .map_err(|e| format!("Could not create cover cache {} : {}", document_directory.display(), e))?;
+    let cover_image = extract_cover(file_path)?.and_then(|cover| image::load_from_memory(&cover).ok());
+    let Some(cover_image) = cover_image else {
+        let no_cover = no_cover_path(cache_directory, cached_document);
+        File::create(&no_cover).map_err(|e| format!("Could not write {} : {}", no_cover.display(), e))?;
+        remove_previous_content(&document_directory, &cache_key(cached_document));
+        return Ok(false);
+    };
+    for size in ThumbnailSize::ALL {
+        let thumbnail_file = thumbnail_path(cache_directory, cached_document, &size);
+        // written aside then renamed, so a concurrent request never reads a partial thumbnail
+        let temporary_file = thumbnail_file.with_extension(format!("{}-{}.tmp", process::id(), TEMPORARY_FILES.fetch_add(1, Ordering::Relaxed)));
+        // JPEG has no alpha channel
+        DynamicImage::ImageRgb8(cover_image.thumbnail(size.width(), size.width() * 3 / 2).to_rgb8())
+            .save_with_format(&temporary_file, ImageFormat::Jpeg)
+            .and_then(|_| Ok(fs::rename(&temporary_file, &thumbnail_file)?))
+            .map_err(|e| format!("Could not write thumbnail {} : {}", thumbnail_file.display(), e))?;
+    }
+    remove_previous_content(&document_directory, &cache_key(cached_document));
+    return Ok(true);
+}
+
+/// Removes the thumbnails and marker of the previous content of the document file.
+/// Stale files only take space, so failing to remove them is ignored.
+fn remove_previous_content(document_directory : &Path, current_key : &str) {
+    let Ok(entries) = fs::read_dir(document_directory) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        if !entry.file_name().to_string_lossy().starts_with(current_key) {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
+
+/// Gives the thumbnail of the document cover, extracting it on first request.
+/// Documents without cover give nothing.
+pub fn get_cover_thumbnail(connection : &mut SqliteConnection, cache_directory : &Path, document_id : &i32, size : &ThumbnailSize) -> Result<Option<PathBuf>, DbError> {
+    let cached_document = get_document_by_id(connection, document_id)?;
+    let thumbnail_file = thumbnail_path(cache_directory, &cached_document, size);
+    if thumbnail_file.is_file() {
+        return Ok(Some(thumbnail_file));
+    }
+    if no_cover_path(cache_directory, &cached_document).is_file() {
+        return Ok(None);
+    }
+    let file_path = resolve_document_path(connection, document_id)?;
+    let has_cover = cache_cover(cache_directory, &cached_document, &file_path)
+        .map_err(|e| DbError::Io(format!("Could not get cover of document {} : {}", document_id, e)))?;
+    return match has_cover {
+        true => Ok(Some(thumbnail_file)),
+        false => Ok(None)
+    };
+}
+
+/// Generates the missing thumbnails of the documents, whose files are under the category root.
+/// Files that can't be read are skipped. Gives the number of documents whose cover was cached.
+pub fn cache_document_covers(category : &Category, cache_directory : &Path, documents : &[Document]) -> usize {
+    let mut cached_covers = 0;
+    for cached_document in documents {
+        let has_thumbnails = ThumbnailSize::ALL.iter().all(|size| thumbnail_path(cache_directory, cached_document, size).is_file());
+        if has_thumbnails || no_cover_path(cache_directory, cached_document).is_file() {
+            continue;
+        }
+        let file_path = category.root_path().join(&cached_document.path_);
+        if cache_cover(cache_directory, cached_document, &file_path).unwrap_or(false) {
+            cached_covers += 1;
+        }
+    }
+    return cached_covers;
+}
+
+/// Generates the missing thumbnails of the documents stored under the category root.
+pub fn cache_category_covers(connection : &mut SqliteConnection, category : &Category, cache_directory : &Path) -> Result<usize, String> {
+    let category_documents = get_documents_from_category(connection, &category.id_).map_err(|e| e.to_string())?;
+    let stored_documents : Vec<Document> = category_documents.into_iter().filter(|d| d.category_ == category.id_).collect();
+    return Ok(cache_document_covers(category, cache_directory, &stored_documents));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::{copy, create_dir_all, remove_dir_all, File}, io::{Cursor, Write}, path::Path};
+    use id3::{frame::{Picture, PictureType}, Tag, TagLike, Version};
+    use image::{ImageFormat, RgbImage};
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    use crate::db_manager::{add_category, create_database, delete_database, get_connection, import_document, set_document_fingerprint, DbError, DocumentImport, FileFingerprint};
+
+    use super::{cache_category_covers, extract_cover, get_cover_thumbnail, ThumbnailSize};
+
+    fn png_image(width : u32, height : u32) -> Vec<u8> {
+        let mut png = Cursor::new(Vec::new());
+        RgbImage::new(width, height).write_to(&mut png, ImageFormat::Png).unwrap();
+        return png.into_inner();
+    }
+
+    #[test]
+    fn extracting_cover_should_give_first_page_or_embedded_picture() {
+        let test_directory = Path::new("./extracting_cover_should_give_first_page_or_embedded_picture");
+        create_dir_all(test_directory).unwrap();
+        let cbz_path = test_directory.join("chapter-1001.cbz");
+        let mut cbz = ZipWriter::new(File::create(&cbz_path).unwrap());
+        for (entry_name, content) in [("ComicInfo.xml", "<ComicInfo/>"), ("page-002.png", "second"), ("page-001.png", "first")] {
+            cbz.start_file(entry_name, SimpleFileOptions::default()).unwrap();
+            cbz.write_all(content.as_bytes()).unwrap();
+        }
+        cbz.finish().unwrap();
+        assert_eq!(Some(b"first".to_vec()), extract_cover(&cbz_path).unwrap());
+        let mp3_path = test_directory.join("chapter-1.mp3");
+        File::create(&mp3_path).unwrap();
+        assert_eq!(None, extract_cover(&mp3_path).unwrap());
+        let mut tag = Tag::new();
+        tag.add_frame(Picture { mime_type : "image/png".to_string(), picture_type : PictureType::Other, description : String::new(), data : b"other".to_vec() });
+        tag.add_frame(Picture { mime_type : "image/png".to_string(), picture_type : PictureType::CoverFront, description : String::new(), data : b"front".to_vec() });
+        tag.write_to_path(&mp3_path, Version::Id3v24).unwrap();
+        assert_eq!(Some(b"front".to_vec()), extract_cover(&mp3_path).unwrap());
+        assert_eq!(None, extract_cover(&test_directory.join("paper.pdf")).unwrap());
+        remove_dir_all(test_directory).unwrap();
+    }
+
+    #[test]
+    fn cover_thumbnails_should_be_cached_in_every_size() {
+        let test_db_path = Path::new("./cover_thumbnails_should_be_cached_in_every_size.db");
+        let test_directory = Path::new("./cover_thumbnails_should_be_cached_in_every_size");
+        let cache_directory = test_directory.join("covers");
+        create_dir_all(test_directory.join("Oda")).unwrap();
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let mut cbz = ZipWriter::new(File::create(test_directory.join("Oda/chapter-1001.cbz")).unwrap());
+        cbz.start_file("001.png", SimpleFileOptions::default()).unwrap();
+        cbz.write_all(&png_image(800, 1200)).unwrap();
+        cbz.finish().unwrap();
+        let mut cbz = ZipWriter::new(File::create(test_directory.join("Oda/chapter-1002.cbz")).unwrap());
+        cbz.start_file("ComicInfo.xml", SimpleFileOptions::default()).unwrap();
+        cbz.write_all(b"<ComicInfo/>").unwrap();
+        cbz.finish().unwrap();
+        let mut cbz = ZipWriter::new(File::create(test_directory.join("Oda/chapter-1003.cbz")).unwrap());
+        cbz.start_file("001.png", SimpleFileOptions::default()).unwrap();
+        cbz.write_all(b"not a png").unwrap();
+        cbz.finish().unwrap();
+        let mangas = add_category(&mut connection, &"Mangas".to_string(), &test_directory.to_string_lossy().to_string()).unwrap();
+        let import = |chapter : &str| DocumentImport::new(chapter.to_string(), mangas.id_, None, Vec::new(), None, None, None, None, format!("Oda/{}.cbz", chapter), Vec::new(), Vec::new(), Vec::new(), Default::default());
+        let chapter_1001 = import_document(&mut connection, &import("chapter-1001")).unwrap();
+        let chapter_1002 = import_document(&mut connection, &import("chapter-1002")).unwrap();
+        let chapter_1003 = import_document(&mut connection, &import("chapter-1003")).unwrap();
+        let chapter_1004 = import_document(&mut connection, &import("chapter-1004")).unwrap();
+        assert_eq!(1, cache_category_covers(&mut connection, &mangas, &cache_directory).unwrap());
+        let small_thumbnail = get_cover_thumbnail(&mut connection, &cache_directory, &chapter_1001.id_, &ThumbnailSize::Small).unwrap().unwrap();
+        assert!(small_thumbnail.starts_with(cache_directory.join(chapter_1001.id_.to_string())));
+        assert_eq!((160, 240), image::image_dimensions(&small_thumbnail).unwrap());
+        let large_thumbnail = get_cover_thumbnail(&mut connection, &cache_directory, &chapter_1001.id_, &ThumbnailSize::Large).unwrap().unwrap();
+        assert_eq!((640, 960), image::image_dimensions(&large_thumbnail).unwrap());
+        assert_eq!(None, get_cover_thumbnail(&mut connection, &cache_directory, &chapter_1002.id_, &ThumbnailSize::Small).unwrap());
+        assert_eq!(None, get_cover_thumbnail(&mut connection, &cache_directory, &chapter_1003.id_, &ThumbnailSize::Small).unwrap());
+        assert!(get_cover_thumbnail(&mut connection, &cache_directory, &chapter_1004.id_, &ThumbnailSize::Small).is_err());
+        assert!(matches!(get_cover_thumbnail(&mut connection, &cache_directory, &(chapter_1004.id_ + 1), &ThumbnailSize::Small), Err(DbError::NotFound(_))));
+        assert_eq!(0, cache_category_covers(&mut connection, &mangas, &cache_directory).unwrap());
+        copy(test_directory.join("Oda/chapter-1001.cbz"), test_directory.join("Oda/chapter-1004.cbz")).unwrap();
+        assert!(get_cover_thumbnail(&mut connection, &cache_directory, &chapter_1004.id_, &ThumbnailSize::Small).unwrap().is_some());
+        set_document_fingerprint(&mut connection, &chapter_1001.id_, &FileFingerprint::new("rehashed".to_string(), 0, String::new())).unwrap();
+        let rehashed_thumbnail = get_cover_thumbnail(&mut connection, &cache_directory, &chapter_1001.id_, &ThumbnailSize::Small).unwrap().unwrap();
+        assert_eq!((true, false, false), (rehashed_thumbnail.is_file(), small_thumbnail.exists(), large_thumbnail.exists()));
+        remove_dir_all(test_directory).unwrap();
+        delete_database(test_db_path).unwrap();
+    }
+}
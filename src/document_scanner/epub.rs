@@ -0,0 +1,281 @@
+use std::{collections::HashMap, fs::File, io::Read, path::Path};
+use quick_xml::{events::{BytesStart, Event}, Reader};
+use zip::ZipArchive;
+
+use crate::db_manager::ContributorRole;
+
+use super::{parse_partial_date, ExtractedMetadata};
+
+/// Reads the package metadata of an EPUB : the container file points at the OPF package,
+/// whose metadata element holds Dublin Core fields and Calibre series.
+pub fn read_epub_metadata(file_path : &Path) -> Result<ExtractedMetadata, String> {
+    let (_, _, package) = open_epub_package(file_path)?;
+    return parse_package(&package).map_err(|e| format!("Could not read package of EPUB {} : {}", file_path.display(), e));
+}
+
+/// Reads the cover image of an EPUB : the manifest item with the EPUB 3 cover-image property,
+/// or the one named by the EPUB 2 cover meta element. EPUBs without cover give nothing.
+pub fn read_epub_cover(file_path : &Path) -> Result<Option<Vec<u8>>, String> {
+    let (mut archive, package_path, package) = open_epub_package(file_path)?;
+    let Some(cover_href) = find_cover_href(&package) else {
+        return Ok(None);
+    };
+    // manifest references are relative to the package file
+    let cover_path = package_path.rsplit_once('/').map(|(package_directory, _)| format!("{}/{}", package_directory, cover_href)).unwrap_or(cover_href);
+    let mut cover = Vec::new();
+    archive.by_name(&normalize_entry_name(&cover_path)).and_then(|mut entry| Ok(entry.read_to_end(&mut cover)?))
+        .map_err(|e| format!("Could not read cover {} of EPUB {} : {}", cover_path, file_path.display(), e))?;
+    return Ok(Some(cover));
+}
+
+/// Opens the EPUB and reads the OPF package its container points at.
+/// Gives the archive, the path of the package inside it and the package text.
+fn open_epub_package(file_path : &Path) -> Result<(ZipArchive<File>, String, String), String> {
+    let file = File::open(file_path)
+        .map_err(|e| format!("Could not open EPUB {} : {}", file_path.display(), e))?;
+    let mut archive = ZipArchive::new(file)
+        .map_err(|e| format!("Could not read EPUB {} : {}", file_path.display(), e))?;
+    let container = read_archive_text(&mut archive, "META-INF/container.xml")?;
+    let package_path = find_package_path(&container)
+        .ok_or_else(|| format!("EPUB {} has no package file in its container", file_path.display()))?;
+    let package = read_archive_text(&mut archive, &package_path)?;
+    return Ok((archive, package_path, package));
+}
+
+fn find_cover_href(package : &str) -> Option<String> {
+    let mut reader = Reader::from_str(package);
+    let mut cover_id : Option<String> = None;
+    // manifest items as id, href and properties
+    let mut items : Vec<(Option<String>, String, Option<String>)> = Vec::new();
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(element)) | Ok(Event::Empty(element)) => match element.local_name().as_ref() {
+                b"meta" if attribute(&element, b"name").as_deref() == Some("cover") => cover_id = attribute(&element, b"content"),
+                b"item" => if let Some(item_href) = attribute(&element, b"href") {
+                    items.push((attribute(&element, b"id"), item_href, attribute(&element, b"properties")));
+                },
+                _ => ()
+            },
+            Ok(Event::Eof) | Err(_) => break,
+            _ => ()
+        }
+    }
+    let cover_image = items.iter().find(|(_, _, item_properties)| item_properties.as_ref().is_some_and(|item_properties| item_properties.split_whitespace().any(|property| property == "cover-image")));
+    let cover_item = cover_image.or_else(|| items.iter().find(|(item_id, _, _)| item_id.is_some() && *item_id == cover_id));
+    return cover_item.map(|(_, item_href, _)| item_href.clone());
+}
+
+/// Resolves the "." and ".." parts of an archive entry name.
+fn normalize_entry_name(entry_name : &str) -> String {
+    let mut parts : Vec<&str> = Vec::new();
+    for part in entry_name.split('/') {
+        match part {
+            "" | "." => (),
+            ".." => { parts.pop(); },
+            _ => parts.push(part)
+        }
+    }
+    return parts.join("/");
+}
+
+fn read_archive_text(archive : &mut ZipArchive<File>, entry_name : &str) -> Result<String, String> {
+    let mut entry = archive.by_name(entry_name)
+        .map_err(|e| format!("Could not find {} in archive : {}", entry_name, e))?;
+    let mut text = String::new();
+    entry.read_to_string(&mut text)
+        .map_err(|e| format!("Could not read {} in archive : {}", entry_name, e))?;
+    return Ok(text);
+}
+
+fn attribute(element : &BytesStart, attribute_name : &[u8]) -> Option<String> {
+    return element.attributes().flatten()
+        .find(|found| found.key.local_name().as_ref() == attribute_name)
+        .and_then(|found| found.unescape_value().ok())
+        .map(|found_value| found_value.trim().to_string());
+}
+
+fn find_package_path(container : &str) -> Option<String> {
+    let mut reader = Reader::from_str(container);
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(element)) | Ok(Event::Empty(element)) if element.local_name().as_ref() == b"rootfile" => return attribute(&element, b"full-path"),
+            Ok(Event::Eof) | Err(_) => return None,
+            _ => ()
+        }
+    }
+}
+
+/// MARC relator codes used by the OPF role attribute, creators without role are authors.
+fn contributor_role(relator : &Option<String>) -> Option<ContributorRole> {
+    return match relator.as_deref() {
+        None | Some("aut") => Some(ContributorRole::Author),
+        Some("ill") | Some("art") => Some(ContributorRole::Illustrator),
+        Some("trl") => Some(ContributorRole::Translator),
+        Some("edt") => Some(ContributorRole::Editor),
+        Some("nrt") => Some(ContributorRole::Narrator),
+        Some(_) => None
+    };
+}
+
+/// Scheme of an identifier, from its attribute or from its URN prefix.
+fn identifier(element : &BytesStart, text : &str) -> (String, String) {
+    if let Some(identifier_scheme) = attribute(element, b"scheme") {
+        return (identifier_scheme.to_lowercase(), text.to_string());
+    }
+    for identifier_scheme in ["isbn", "uuid"] {
+        let prefixes = [format!("urn:{}:", identifier_scheme), format!("{}:", identifier_scheme)];
+        if let Some(identifier_value) = prefixes.iter().find_map(|prefix| text.to_lowercase().starts_with(prefix.as_str()).then(|| &text[prefix.len()..])) {
+            return (identifier_scheme.to_string(), identifier_value.to_string());
+        }
+    }
+    return ("unknown".to_string(), text.to_string());
+}
+
+fn parse_package(package : &str) -> Result<ExtractedMetadata, String> {
+    let mut reader = Reader::from_str(package);
+    let mut extracted = ExtractedMetadata::default();
+    // EPUB 3 gives creator roles in meta elements refining the creator id
+    let mut creators : Vec<(String, Option<String>, Option<String>)> = Vec::new();
+    let mut refined_roles : HashMap<String, String> = HashMap::new();
+    let mut open_element : Option<BytesStart<'static>> = None;
+    let mut text = String::new();
+    loop {
+        let (element, element_text) = match reader.read_event().map_err(|e| e.to_string())? {
+            Event::Start(element) => {
+                open_element = Some(element.into_owned());
+                text.clear();
+                continue;
+            },
+            Event::Text(content) => {
+                text.push_str(&content.unescape().map_err(|e| e.to_string())?);
+                continue;
+            },
+            Event::CData(content) => {
+                text.push_str(&String::from_utf8_lossy(&content));
+                continue;
+            },
+            Event::Empty(element) => (element.into_owned(), String::new()),
+            Event::End(_) => match open_element.take() {
+                Some(element) => (element, text.trim().to_string()),
+                None => continue
+            },
+            Event::Eof => break,
+            _ => continue
+        };
+        match element.local_name().as_ref() {
+            b"title" if extracted.title_.is_none() && !element_text.is_empty() => extracted.title_ = Some(element_text),
+            b"creator" if !element_text.is_empty() => creators.push((element_text, attribute(&element, b"id"), attribute(&element, b"role"))),
+            b"contributor" if !element_text.is_empty() && attribute(&element, b"role").is_some() => creators.push((element_text, attribute(&element, b"id"), attribute(&element, b"role"))),
+            b"date" if extracted.date_.is_none() && attribute(&element, b"event").is_none_or(|event| event == "publication") => extracted.date_ = parse_partial_date(&element_text),
+            b"language" if extracted.language_.is_none() && !element_text.is_empty() => extracted.language_ = Some(element_text),
+            b"description" if extracted.description_.is_none() && !element_text.is_empty() => extracted.description_ = Some(element_text),
+            b"publisher" if extracted.publisher_.is_none() && !element_text.is_empty() => extracted.publisher_ = Some(element_text),
+            b"subject" if !element_text.is_empty() => extracted.tags_.push(element_text),
+            b"identifier" if !element_text.is_empty() => extracted.identifiers_.push(identifier(&element, &element_text)),
+            b"meta" => match (attribute(&element, b"name").as_deref(), attribute(&element, b"property").as_deref()) {
+                (Some("calibre:series"), _) => extracted.series_ = attribute(&element, b"content").filter(|series_name| !series_name.is_empty()),
+                (Some("calibre:series_index"), _) => extracted.series_index_ = attribute(&element, b"content").and_then(|index| index.parse::<f64>().ok()),
+                (_, Some("role")) => if let Some(refined) = attribute(&element, b"refines") {
+                    refined_roles.insert(refined.trim_start_matches('#').to_string(), element_text);
+                },
+                _ => ()
+            },
+            _ => ()
+        }
+    }
+    for (creator_name, creator_id, creator_role) in creators {
+        let relator = creator_role.or_else(|| creator_id.and_then(|refined| refined_roles.get(&refined).cloned()));
+        if let Some(role) = contributor_role(&relator) {
+            extracted.contributors_.push((creator_name, role));
+        }
+    }
+    return Ok(extracted);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::{remove_file, File}, io::Write, path::Path};
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    use crate::db_manager::{ContributorRole, PartialDate};
+
+    use super::{read_epub_cover, read_epub_metadata};
+
+    const CONTAINER : &str = r#"<?xml version="1.0"?>
+        <container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+            <rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles>
+        </container>"#;
+
+    const PACKAGE : &str = r##"<?xml version="1.0" encoding="UTF-8"?>
+        <package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="uid">
+            <metadata xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:opf="http://www.idpf.org/2007/opf">
+                <dc:title>The Two Towers</dc:title>
+                <dc:creator opf:role="aut">J.R.R Tolkien</dc:creator>
+                <dc:creator id="illustrator">Alan Lee</dc:creator>
+                <meta refines="#illustrator" property="role" scheme="marc:relators">ill</meta>
+                <dc:date opf:event="modification">2011-01-01</dc:date>
+                <dc:date>1954-11</dc:date>
+                <dc:language>en</dc:language>
+                <dc:identifier id="uid">urn:uuid:1b7a3e52-4f1c-4a86-9f6e-6b2e1f3c2d10</dc:identifier>
+                <dc:identifier opf:scheme="ISBN">9780261102361</dc:identifier>
+                <dc:description><![CDATA[<p>Frodo &amp; the Company</p>]]></dc:description>
+                <dc:publisher>Allen &amp; Unwin</dc:publisher>
+                <dc:subject>Fantasy</dc:subject>
+                <dc:subject>Classics</dc:subject>
+                <meta name="calibre:series" content="The Lord Of The Rings"/>
+                <meta name="calibre:series_index" content="2.0"/>
+            </metadata>
+            <manifest><item id="chapter-1" href="chapter-1.xhtml" media-type="application/xhtml+xml"/></manifest>
+        </package>"##;
+
+    #[test]
+    fn reading_epub_should_give_package_metadata() {
+        let epub_path = Path::new("./reading_epub_should_give_package_metadata.epub");
+        let mut epub = ZipWriter::new(File::create(epub_path).unwrap());
+        for (entry_name, content) in [("mimetype", "application/epub+zip"), ("META-INF/container.xml", CONTAINER), ("OEBPS/content.opf", PACKAGE)] {
+            epub.start_file(entry_name, SimpleFileOptions::default()).unwrap();
+            epub.write_all(content.as_bytes()).unwrap();
+        }
+        epub.finish().unwrap();
+        let extracted = read_epub_metadata(epub_path).unwrap();
+        assert_eq!(Some("The Two Towers".to_string()), extracted.title_);
+        assert_eq!(vec![("J.R.R Tolkien".to_string(), ContributorRole::Author), ("Alan Lee".to_string(), ContributorRole::Illustrator)], extracted.contributors_);
+        assert_eq!(Some(PartialDate::year_month(1954, 11).unwrap()), extracted.date_);
+        assert_eq!(Some("en".to_string()), extracted.language_);
+        assert_eq!(vec![("uuid".to_string(), "1b7a3e52-4f1c-4a86-9f6e-6b2e1f3c2d10".to_string()), ("isbn".to_string(), "9780261102361".to_string())], extracted.identifiers_);
+        assert_eq!(Some("<p>Frodo &amp; the Company</p>".to_string()), extracted.description_);
+        assert_eq!(Some("Allen & Unwin".to_string()), extracted.publisher_);
+        assert_eq!(vec!["Fantasy".to_string(), "Classics".to_string()], extracted.tags_);
+        assert_eq!((Some("The Lord Of The Rings".to_string()), Some(2.0)), (extracted.series_, extracted.series_index_));
+        remove_file(epub_path).unwrap();
+        assert!(read_epub_metadata(epub_path).is_err());
+    }
+
+    #[test]
+    fn reading_epub_should_give_its_cover_image() {
+        let epub_path = Path::new("./reading_epub_should_give_its_cover_image.epub");
+        let package = r#"<package xmlns="http://www.idpf.org/2007/opf" version="2.0">
+                <metadata><meta name="cover" content="cover-jpg"/></metadata>
+                <manifest>
+                    <item id="chapter-1" href="text/chapter-1.xhtml" media-type="application/xhtml+xml"/>
+                    <item id="cover-jpg" href="../images/cover.jpg" media-type="image/jpeg"/>
+                </manifest>
+            </package>"#;
+        let mut epub = ZipWriter::new(File::create(epub_path).unwrap());
+        for (entry_name, content) in [("META-INF/container.xml", CONTAINER), ("OEBPS/content.opf", package), ("images/cover.jpg", "cover")] {
+            epub.start_file(entry_name, SimpleFileOptions::default()).unwrap();
+            epub.write_all(content.as_bytes()).unwrap();
+        }
+        epub.finish().unwrap();
+        assert_eq!(Some(b"cover".to_vec()), read_epub_cover(epub_path).unwrap());
+        remove_file(epub_path).unwrap();
+        let mut epub = ZipWriter::new(File::create(epub_path).unwrap());
+        for (entry_name, content) in [("META-INF/container.xml", CONTAINER), ("OEBPS/content.opf", PACKAGE)] {
+            epub.start_file(entry_name, SimpleFileOptions::default()).unwrap();
+            epub.write_all(content.as_bytes()).unwrap();
+        }
+        epub.finish().unwrap();
+        assert_eq!(None, read_epub_cover(epub_path).unwrap());
+        remove_file(epub_path).unwrap();
+    }
+}
@@ -1,17 +1,181 @@
 use std::{fs::read_dir, path::{Path, PathBuf}};
 
-use crate::db_manager::{get_connection, get_documents_from_category, Category};
+use diesel::SqliteConnection;
+use comic_info::{is_rar_archive, unsupported_rar_archive};
+use cover::cache_document_covers;
+use path_template::PathTemplate;
 
+use crate::db_manager::{format_modified_at, get_documents_stored_in_category, import_document, Category, ContributorRole, Document, DocumentImport, DocumentMetadata, PartialDate};
 
-pub fn scan_for_new_files(category : &Category) ->Result<Vec<&Path>, String> {
-    let connection = get_connection(db_path)
-    let documents_from_category = get_documents_from_category(connection, category_id)
+pub(crate) mod path_template;
+pub(crate) mod epub;
+pub(crate) mod comic_info;
+pub(crate) mod pdf;
+pub(crate) mod cover;
+
+/// Extensions of the files a category scan looks for.
+const DOCUMENT_EXTENSIONS : [&str; 6] = ["epub", "pdf", "cbz", "cb7", "cbr", "mp3"];
+
+/// Differences between the category root on disk and its documents in the database.
+/// New file paths are relative to the category root, like document paths.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct ScanReport {
+    pub new_files_ : Vec<PathBuf>,
+    pub missing_documents_ : Vec<Document>,
+    /// Documents whose file size or modification time changed since they were fingerprinted
+    pub modified_documents_ : Vec<Document>
+}
+
+/// Outcome of importing the new files of a category, a file that fails is reported without stopping the others.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct ImportReport {
+    pub imported_documents_ : Vec<Document>,
+    /// Path relative to the category root and the reason it could not be imported
+    pub failed_files_ : Vec<(PathBuf, String)>
+}
+
+/// What a document file says about itself, formats and files vary so every field may be missing.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct ExtractedMetadata {
+    pub title_ : Option<String>,
+    pub contributors_ : Vec<(String, ContributorRole)>,
+    pub date_ : Option<PartialDate>,
+    pub language_ : Option<String>,
+    pub description_ : Option<String>,
+    pub publisher_ : Option<String>,
+    /// Scheme and value of each identifier, like ("isbn", "9780261103344")
+    pub identifiers_ : Vec<(String, String)>,
+    pub tags_ : Vec<String>,
+    pub genres_ : Vec<String>,
+    pub series_ : Option<String>,
+    pub series_index_ : Option<f64>,
+    pub page_count_ : Option<i32>,
+    pub right_to_left_ : Option<bool>
+}
+
+/// Reads the date at the start of an ISO date or date time, as precise as it is given.
+fn parse_partial_date(text : &str) -> Option<PartialDate> {
+    return text.split('T').next().and_then(|date_text| date_text.trim().parse::<PartialDate>().ok());
+}
+
+/// Reads the metadata embedded in the file, formats without support give nothing.
+pub fn extract_metadata(file_path : &Path) -> Result<ExtractedMetadata, String> {
+    return match file_path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase()).as_deref() {
+        Some("epub") => epub::read_epub_metadata(file_path),
+        Some("cbz") | Some("cb7") | Some("cbr") => comic_info::read_comic_info_metadata(file_path),
+        Some("pdf") => pdf::read_pdf_metadata(file_path),
+        _ => Ok(ExtractedMetadata::default())
+    };
+}
+
+/// Walks the category root and compares it with the documents stored in the category.
+/// Documents only linked to the category live under another root and are left out,
+/// the files of trashed documents are not new.
+pub fn scan_for_new_files(connection : &mut SqliteConnection, category : &Category) -> Result<ScanReport, String> {
+    let root_path = category.root_path();
+    let mut found_files : Vec<PathBuf> = get_files_with_extensions(&root_path, DOCUMENT_EXTENSIONS.to_vec())?
+        .into_iter()
+        .filter_map(|file_path| file_path.strip_prefix(&root_path).ok().map(Path::to_path_buf))
+        .collect();
+    found_files.sort();
+    let stored_documents = get_documents_stored_in_category(connection, &category.id_)
+        .map_err(|e| format!("Could not get documents of category {} : {}", category.name_, e))?;
+    let mut report = ScanReport::default();
+    for category_document in stored_documents {
+        let document_path = PathBuf::from(&category_document.path_);
+        // the file of a trashed document is known, but the document is neither missing nor modified
+        let is_trashed = category_document.deleted_at_.is_some();
+        match found_files.iter().position(|found_file| *found_file == document_path) {
+            None if is_trashed => (),
+            None => report.missing_documents_.push(category_document),
+            Some(found_index) => {
+                found_files.remove(found_index);
+                if !is_trashed && is_modified(&root_path.join(&document_path), &category_document)? {
+                    report.modified_documents_.push(category_document);
+                }
+            }
+        }
+    }
+    report.new_files_ = found_files;
+    return Ok(report);
+}
+
+/// Imports the new files of the category, reading author, series, index and title from their path
+/// with the category path template. Files not following the template are left out.
+/// Metadata embedded in the file is preferred to the path, without any title the file name is used.
+/// The covers of the imported documents are extracted into the cover cache.
+pub fn import_new_files(connection : &mut SqliteConnection, category : &Category, cover_cache_directory : &Path) -> Result<ImportReport, String> {
+    let template = match &category.path_template_ {
+        Some(category_template) => category_template.parse::<PathTemplate>()?,
+        None => return Err(format!("Category {} has no path template to import files with", category.name_))
+    };
+    let mut report = ImportReport::default();
+    for new_file in scan_for_new_files(connection, category)?.new_files_ {
+        let Some(template_match) = template.match_path(&new_file) else {
+            continue;
+        };
+        let file_path = category.root_path().join(&new_file);
+        // a real RAR archive can't be read, it is reported rather than imported without its metadata
+        if is_rar_archive(&file_path) {
+            report.failed_files_.push((new_file, unsupported_rar_archive(&file_path)));
+            continue;
+        }
+        // a damaged file is still imported, from what its path tells
+        let extracted = extract_metadata(&file_path).unwrap_or_default();
+        let title = extracted.title_.or(template_match.title_)
+            .unwrap_or_else(|| new_file.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default());
+        let author = extracted.contributors_.iter()
+            .find(|(_, role)| *role == ContributorRole::Author)
+            .map(|(contributor_name, _)| contributor_name.clone())
+            .or(template_match.author_);
+        let (series, series_index) = match extracted.series_ {
+            Some(extracted_series) => (Some(extracted_series), extracted.series_index_.or(template_match.index_)),
+            None => (template_match.series_, template_match.index_.or(extracted.series_index_))
+        };
+        let metadata = DocumentMetadata { description_ : extracted.description_, 
+                                        language_ : extracted.language_, 
+                                        publisher_ : extracted.publisher_, 
+                                        page_count_ : extracted.page_count_, 
+                                        right_to_left_ : extracted.right_to_left_, 
+                                        ..Default::default() };
+        let document_import = DocumentImport::new(title, 
+                                                category.id_, 
+                                                author, 
+                                                extracted.contributors_, 
+                                                series, 
+                                                series_index, 
+                                                None, 
+                                                extracted.date_, 
+                                                new_file.to_string_lossy().to_string(), 
+                                                extracted.tags_, 
+                                                extracted.genres_, 
+                                                extracted.identifiers_, 
+                                                metadata);
+        match import_document(connection, &document_import) {
+            Ok(imported_document) => report.imported_documents_.push(imported_document),
+            Err(e) => report.failed_files_.push((new_file, e.to_string()))
+        }
+    }
+    cache_document_covers(category, cover_cache_directory, &report.imported_documents_);
+    return Ok(report);
+}
+
+/// Compares the file with the fingerprint of the document, documents never fingerprinted can't be modified.
+fn is_modified(file_path : &Path, scanned_document : &Document) -> Result<bool, String> {
+    let (Some(known_size), Some(known_modified_at)) = (&scanned_document.file_size_, &scanned_document.modified_at_) else {
+        return Ok(false);
+    };
+    let file_metadata = file_path.metadata()
+        .map_err(|e| format!("An error occured while reading {} : {}", file_path.display(), e))?;
+    let modified_at = file_metadata.modified()
+        .map_err(|e| format!("An error occured while reading {} : {}", file_path.display(), e))?;
+    return Ok(file_metadata.len() as i64 != *known_size || format_modified_at(modified_at) != *known_modified_at);
 }
 
 fn get_files_with_extensions(path: &Path, extensions: Vec<&str>) -> Result<Vec<PathBuf>, String> {
     Ok(get_all_files(path)?
         .into_iter()
-        .filter(|p| p.extension().map_or(false, |e| e.to_str().map_or(false, |e| extensions.contains(&e))))
+        .filter(|p| p.extension().and_then(|e| e.to_str()).is_some_and(|e| extensions.iter().any(|extension| e.eq_ignore_ascii_case(extension))))
         .collect::<Vec<PathBuf>>())
 }
 
@@ -35,4 +199,170 @@ fn get_all_files(path : &Path) -> Result<Vec<PathBuf>, String> {
         }
         return Ok(files);
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::{create_dir_all, remove_dir_all, write, File}, io::{Cursor, Write}, path::{Path, PathBuf}};
+    use diesel::connection::SimpleConnection;
+    use image::{ImageFormat, RgbImage};
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    use crate::db_manager::{add_category, add_document, compute_fingerprint, create_database, delete_database, get_authors, get_connection, get_document_contributors, get_document_identifiers, get_genres, get_series_by_name, get_tags, ContributorRole, PartialDate, link_category_to_document, remove_document, set_category_path_template, set_document_fingerprint};
+
+    use super::{cover::{thumbnail_path, ThumbnailSize}, import_new_files, scan_for_new_files, ImportReport, ScanReport};
+
+    #[test]
+    fn scanning_category_should_report_new_missing_and_modified_files() {
+        let test_db_path = Path::new("./scanning_category_should_report_new_missing_and_modified_files.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let books_root = Path::new("./scanning_category_should_report_new_missing_and_modified_files");
+        create_dir_all(books_root.join("Tolkien")).unwrap();
+        write(books_root.join("Tolkien/the_hobbit.epub"), "In a hole in the ground there lived a hobbit.").unwrap();
+        write(books_root.join("Tolkien/the_two_towers.epub"), "Aragorn sped on up the hill.").unwrap();
+        write(books_root.join("Tolkien/the_silmarillion.epub"), "There was Eru, the One.").unwrap();
+        write(books_root.join("Tolkien/unfinished_tales.PDF"), "Of Tuor and his coming to Gondolin.").unwrap();
+        write(books_root.join("Tolkien/notes.txt"), "not a document").unwrap();
+        let books = add_category(&mut connection, &"Books".to_string(), &books_root.to_string_lossy().to_string()).unwrap();
+        let favourites = add_category(&mut connection, &"Favourites".to_string(), &"~/Documents/Favourites".to_string()).unwrap();
+        let the_hobbit = add_document(&mut connection, &"The Hobbit".to_string(), &books.id_, &None, &None, &None, &"Tolkien/the_hobbit.epub".to_string()).unwrap();
+        let the_two_towers = add_document(&mut connection, &"The Two Towers".to_string(), &books.id_, &None, &None, &None, &"Tolkien/the_two_towers.epub".to_string()).unwrap();
+        let the_return_of_the_king = add_document(&mut connection, &"The Return Of The King".to_string(), &books.id_, &None, &None, &None, &"Tolkien/the_return_of_the_king.epub".to_string()).unwrap();
+        let a_game_of_thrones = add_document(&mut connection, &"A Game Of Thrones".to_string(), &favourites.id_, &None, &None, &None, &"Martin/A_game_of_thrones.epub".to_string()).unwrap();
+        link_category_to_document(&mut connection, &a_game_of_thrones.id_, &books.id_).unwrap();
+        set_document_fingerprint(&mut connection, &the_hobbit.id_, &compute_fingerprint(&books_root.join("Tolkien/the_hobbit.epub")).unwrap()).unwrap();
+        let the_two_towers = set_document_fingerprint(&mut connection, &the_two_towers.id_, &compute_fingerprint(&books_root.join("Tolkien/the_two_towers.epub")).unwrap()).unwrap();
+        write(books_root.join("Tolkien/the_two_towers.epub"), "Aragorn sped on up the hill, to the top.").unwrap();
+        let report = scan_for_new_files(&mut connection, &books).unwrap();
+        assert_eq!(ScanReport { new_files_ : vec![PathBuf::from("Tolkien/the_silmarillion.epub"), PathBuf::from("Tolkien/unfinished_tales.PDF")], 
+                                missing_documents_ : vec![the_return_of_the_king], 
+                                modified_documents_ : vec![the_two_towers] }, report);
+        remove_dir_all(books_root).unwrap();
+        assert!(scan_for_new_files(&mut connection, &books).is_err());
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn scanning_category_should_not_report_files_of_trashed_documents() {
+        let test_db_path = Path::new("./scanning_category_should_not_report_files_of_trashed_documents.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let books_root = Path::new("./scanning_category_should_not_report_files_of_trashed_documents");
+        create_dir_all(books_root.join("Tolkien")).unwrap();
+        write(books_root.join("Tolkien/the_hobbit.epub"), "In a hole in the ground there lived a hobbit.").unwrap();
+        let books = add_category(&mut connection, &"Books".to_string(), &books_root.to_string_lossy().to_string()).unwrap();
+        let the_hobbit = add_document(&mut connection, &"The Hobbit".to_string(), &books.id_, &None, &None, &None, &"Tolkien/the_hobbit.epub".to_string()).unwrap();
+        let the_return_of_the_king = add_document(&mut connection, &"The Return Of The King".to_string(), &books.id_, &None, &None, &None, &"Tolkien/the_return_of_the_king.epub".to_string()).unwrap();
+        remove_document(&mut connection, &the_hobbit.id_).unwrap();
+        remove_document(&mut connection, &the_return_of_the_king.id_).unwrap();
+        let books = set_category_path_template(&mut connection, &books.id_, &Some("{author}/{title}.{ext}".to_string())).unwrap();
+        assert_eq!(ScanReport::default(), scan_for_new_files(&mut connection, &books).unwrap());
+        assert_eq!(ImportReport::default(), import_new_files(&mut connection, &books, &books_root.join("covers")).unwrap());
+        remove_dir_all(books_root).unwrap();
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn importing_new_files_should_follow_category_path_template() {
+        let test_db_path = Path::new("./importing_new_files_should_follow_category_path_template.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let mangas_root = Path::new("./importing_new_files_should_follow_category_path_template");
+        create_dir_all(mangas_root.join("Oda/One piece")).unwrap();
+        write(mangas_root.join("Oda/One piece/chapter-1001.cbz"), "Chapter 1001").unwrap();
+        write(mangas_root.join("Oda/One piece/chapter-1002.cbz"), "Chapter 1002").unwrap();
+        write(mangas_root.join("Oda/artbook.cbz"), "Color Walk").unwrap();
+        let mangas = add_category(&mut connection, &"Mangas".to_string(), &mangas_root.to_string_lossy().to_string()).unwrap();
+        assert!(import_new_files(&mut connection, &mangas, &mangas_root.join("covers")).is_err());
+        let mangas = set_category_path_template(&mut connection, &mangas.id_, &Some("{author}/{series}/chapter-{index}.{ext}".to_string())).unwrap();
+        let imported_documents = import_new_files(&mut connection, &mangas, &mangas_root.join("covers")).unwrap().imported_documents_;
+        let oda = get_authors(&mut connection).unwrap().pop().unwrap();
+        let one_piece = get_series_by_name(&mut connection, &"One piece".to_string()).unwrap();
+        assert_eq!("Oda", oda.name_);
+        assert_eq!(vec![("chapter-1001".to_string(), Some(oda.id_), Some(one_piece.id_), Some(1001.0)), ("chapter-1002".to_string(), Some(oda.id_), Some(one_piece.id_), Some(1002.0))], 
+                    imported_documents.iter().map(|imported| (imported.name_.clone(), imported.author_, imported.series_, imported.series_index_)).collect::<Vec<_>>());
+        assert_eq!(vec![PathBuf::from("Oda/artbook.cbz")], scan_for_new_files(&mut connection, &mangas).unwrap().new_files_);
+        assert_eq!(ImportReport::default(), import_new_files(&mut connection, &mangas, &mangas_root.join("covers")).unwrap());
+        remove_dir_all(mangas_root).unwrap();
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn importing_epub_should_use_its_metadata() {
+        let test_db_path = Path::new("./importing_epub_should_use_its_metadata.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let books_root = Path::new("./importing_epub_should_use_its_metadata");
+        create_dir_all(books_root.join("Tolkien")).unwrap();
+        let mut epub = ZipWriter::new(File::create(books_root.join("Tolkien/the_hobbit.epub")).unwrap());
+        epub.start_file("META-INF/container.xml", SimpleFileOptions::default()).unwrap();
+        epub.write_all(br#"<container><rootfiles><rootfile full-path="content.opf"/></rootfiles></container>"#).unwrap();
+        epub.start_file("content.opf", SimpleFileOptions::default()).unwrap();
+        epub.write_all(br#"<package><metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+            <dc:title>The Hobbit</dc:title><dc:creator>J.R.R Tolkien</dc:creator><dc:date>1937-09-21</dc:date>
+            <dc:identifier>urn:isbn:9780261103344</dc:identifier><dc:subject>Fantasy</dc:subject>
+        </metadata></package>"#).unwrap();
+        epub.finish().unwrap();
+        let books = add_category(&mut connection, &"Books".to_string(), &books_root.to_string_lossy().to_string()).unwrap();
+        let books = set_category_path_template(&mut connection, &books.id_, &Some("{author}/{title}.{ext}".to_string())).unwrap();
+        let the_hobbit = import_new_files(&mut connection, &books, &books_root.join("covers")).unwrap().imported_documents_.pop().unwrap();
+        let tolkien = get_authors(&mut connection).unwrap().pop().unwrap();
+        assert_eq!(("The Hobbit".to_string(), Some(tolkien.id_), "J.R.R Tolkien".to_string()), (the_hobbit.name_, the_hobbit.author_, tolkien.name_.clone()));
+        assert_eq!(Some(PartialDate::from(chrono::NaiveDate::from_ymd_opt(1937, 9, 21).unwrap())), the_hobbit.date_);
+        assert_eq!(vec!["Fantasy".to_string()], get_tags(&mut connection).unwrap().into_iter().map(|tag| tag.name_).collect::<Vec<String>>());
+        assert_eq!(vec![("isbn".to_string(), "9780261103344".to_string())], get_document_identifiers(&mut connection, &the_hobbit.id_).unwrap().into_iter().map(|identifier| (identifier.scheme_, identifier.value_)).collect::<Vec<_>>());
+        let contributors = get_document_contributors(&mut connection, &the_hobbit.id_).unwrap();
+        assert_eq!(vec![ContributorRole::Author], contributors.iter().map(|contributor| contributor.role_).collect::<Vec<_>>());
+        remove_dir_all(books_root).unwrap();
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn importing_cbz_should_use_its_comic_info() {
+        let test_db_path = Path::new("./importing_cbz_should_use_its_comic_info.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let mangas_root = Path::new("./importing_cbz_should_use_its_comic_info");
+        create_dir_all(mangas_root).unwrap();
+        let mut cbz = ZipWriter::new(File::create(mangas_root.join("one_piece_1001.cbz")).unwrap());
+        cbz.start_file("ComicInfo.xml", SimpleFileOptions::default()).unwrap();
+        cbz.write_all(b"<ComicInfo><Series>One Piece</Series><Number>1001</Number><Writer>Eiichiro Oda</Writer><Genre>Shonen</Genre><Manga>YesAndRightToLeft</Manga></ComicInfo>").unwrap();
+        cbz.start_file("001.png", SimpleFileOptions::default()).unwrap();
+        let mut page = Cursor::new(Vec::new());
+        RgbImage::new(100, 150).write_to(&mut page, ImageFormat::Png).unwrap();
+        cbz.write_all(page.get_ref()).unwrap();
+        cbz.finish().unwrap();
+        let mangas = add_category(&mut connection, &"Mangas".to_string(), &mangas_root.to_string_lossy().to_string()).unwrap();
+        let mangas = set_category_path_template(&mut connection, &mangas.id_, &Some("{title}.{ext}".to_string())).unwrap();
+        let chapter_1001 = import_new_files(&mut connection, &mangas, &mangas_root.join("covers")).unwrap().imported_documents_.pop().unwrap();
+        assert!(thumbnail_path(&mangas_root.join("covers"), &chapter_1001, &ThumbnailSize::Medium).is_file());
+        let one_piece = get_series_by_name(&mut connection, &"One Piece".to_string()).unwrap();
+        assert_eq!(("one_piece_1001".to_string(), Some(one_piece.id_), Some(1001.0), Some(true)), (chapter_1001.name_, chapter_1001.series_, chapter_1001.series_index_, chapter_1001.right_to_left_));
+        assert_eq!(vec!["Shonen".to_string()], get_genres(&mut connection).unwrap().into_iter().map(|genre| genre.name_).collect::<Vec<String>>());
+        remove_dir_all(mangas_root).unwrap();
+        delete_database(test_db_path).unwrap();
+    }
+
+    #[test]
+    fn failed_file_should_not_stop_importing_the_others() {
+        let test_db_path = Path::new("./failed_file_should_not_stop_importing_the_others.db");
+        create_database(test_db_path).unwrap();
+        let mut connection = get_connection(test_db_path).unwrap();
+        let mangas_root = Path::new("./failed_file_should_not_stop_importing_the_others");
+        create_dir_all(mangas_root.join("Oda/One piece")).unwrap();
+        for chapter in ["chapter-1001", "chapter-1002", "chapter-1003"] {
+            write(mangas_root.join(format!("Oda/One piece/{}.cbz", chapter)), chapter).unwrap();
+        }
+        write(mangas_root.join("Oda/One piece/chapter-1004.cbr"), b"Rar!\x1a\x07\x01\x00").unwrap();
+        connection.batch_execute("CREATE TRIGGER refuse_chapter_1002 BEFORE INSERT ON document WHEN NEW.name = 'chapter-1002' BEGIN SELECT RAISE(ABORT, 'refused'); END;").unwrap();
+        let mangas = add_category(&mut connection, &"Mangas".to_string(), &mangas_root.to_string_lossy().to_string()).unwrap();
+        let mangas = set_category_path_template(&mut connection, &mangas.id_, &Some("{author}/{series}/{title}.{ext}".to_string())).unwrap();
+        let report = import_new_files(&mut connection, &mangas, &mangas_root.join("covers")).unwrap();
+        assert_eq!(vec!["chapter-1001".to_string(), "chapter-1003".to_string()], report.imported_documents_.into_iter().map(|imported| imported.name_).collect::<Vec<String>>());
+        assert_eq!(vec![PathBuf::from("Oda/One piece/chapter-1002.cbz"), PathBuf::from("Oda/One piece/chapter-1004.cbr")], report.failed_files_.iter().map(|(failed_file, _)| failed_file.clone()).collect::<Vec<PathBuf>>());
+        assert!(report.failed_files_[1].1.contains("RAR archives are not supported"));
+        remove_dir_all(mangas_root).unwrap();
+        delete_database(test_db_path).unwrap();
+    }
+}
@@ -0,0 +1,151 @@
+use std::{path::Path, str::FromStr};
+
+/// Part of a document file path a template placeholder stands for.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum TemplateField {
+    Author,
+    Series,
+    Index,
+    Title,
+    Extension
+}
+
+impl FromStr for TemplateField {
+    type Err = String;
+
+    fn from_str(value : &str) -> Result<TemplateField, String> {
+        match value {
+            "author" => Ok(TemplateField::Author),
+            "series" => Ok(TemplateField::Series),
+            "index" => Ok(TemplateField::Index),
+            "title" => Ok(TemplateField::Title),
+            "ext" => Ok(TemplateField::Extension),
+            _ => Err(format!("Unknown path template field {{{}}}", value))
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+enum TemplateToken {
+    Text(String),
+    Field(TemplateField)
+}
+
+/// Layout of the document files under a category root, like "{author}/{series}/chapter-{index}.{ext}".
+/// Fields never span a "/" and take as much text as they can, {index} only takes numbers.
+#[derive(PartialEq, Debug, Clone)]
+pub struct PathTemplate {
+    tokens_ : Vec<TemplateToken>
+}
+
+/// What a file path tells about its document, fields missing from the template stay None.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct TemplateMatch {
+    pub author_ : Option<String>,
+    pub series_ : Option<String>,
+    pub index_ : Option<f64>,
+    pub title_ : Option<String>
+}
+
+impl FromStr for PathTemplate {
+    type Err = String;
+
+    fn from_str(template : &str) -> Result<PathTemplate, String> {
+        let mut tokens : Vec<TemplateToken> = Vec::new();
+        let mut rest = template;
+        while let Some(brace) = rest.find(['{', '}']) {
+            if brace > 0 {
+                tokens.push(TemplateToken::Text(rest[..brace].to_string()));
+            }
+            if rest[brace..].starts_with('}') {
+                return Err(format!("Unexpected }} in path template {}", template));
+            }
+            let closing_brace = brace + rest[brace..].find('}')
+                .ok_or_else(|| format!("Unclosed {{ in path template {}", template))?;
+            let field = rest[brace + 1..closing_brace].parse::<TemplateField>()?;
+            if tokens.contains(&TemplateToken::Field(field)) {
+                return Err(format!("Field {} appears twice in path template {}", &rest[brace..=closing_brace], template));
+            }
+            tokens.push(TemplateToken::Field(field));
+            rest = &rest[closing_brace + 1..];
+        }
+        if !rest.is_empty() {
+            tokens.push(TemplateToken::Text(rest.to_string()));
+        }
+        return Ok(PathTemplate { tokens_ : tokens });
+    }
+}
+
+impl PathTemplate {
+    /// Reads the fields from a path relative to the category root, None if the path doesn't follow the template.
+    pub fn match_path(&self, relative_path : &Path) -> Option<TemplateMatch> {
+        let mut captures : Vec<(TemplateField, String)> = Vec::new();
+        if !match_tokens(&self.tokens_, relative_path.to_str()?, &mut captures) {
+            return None;
+        }
+        let mut template_match = TemplateMatch::default();
+        for (field, value) in captures {
+            match field {
+                TemplateField::Author => template_match.author_ = Some(value),
+                TemplateField::Series => template_match.series_ = Some(value),
+                TemplateField::Index => template_match.index_ = value.parse::<f64>().ok(),
+                TemplateField::Title => template_match.title_ = Some(value),
+                TemplateField::Extension => ()
+            }
+        }
+        return Some(template_match);
+    }
+}
+
+fn is_index(value : &str) -> bool {
+    return value.chars().all(|c| c.is_ascii_digit() || c == '.') && value.parse::<f64>().is_ok();
+}
+
+/// Backtracks over the possible ends of each field, longest first.
+fn match_tokens(tokens : &[TemplateToken], text : &str, captures : &mut Vec<(TemplateField, String)>) -> bool {
+    return match tokens.split_first() {
+        None => text.is_empty(),
+        Some((TemplateToken::Text(literal), rest)) => text.strip_prefix(literal.as_str())
+            .is_some_and(|remaining| match_tokens(rest, remaining, captures)),
+        Some((TemplateToken::Field(field), rest)) => {
+            let folder_end = text.find('/').unwrap_or(text.len());
+            for end in (1..=folder_end).rev().filter(|end| text.is_char_boundary(*end)) {
+                let value = &text[..end];
+                if *field == TemplateField::Index && !is_index(value) {
+                    continue;
+                }
+                captures.push((*field, value.to_string()));
+                if match_tokens(rest, &text[end..], captures) {
+                    return true;
+                }
+                captures.pop();
+            }
+            false
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{PathTemplate, TemplateMatch};
+
+    #[test]
+    fn path_template_should_read_fields_from_paths() {
+        let chapters = "{author}/{series}/chapter-{index}.{ext}".parse::<PathTemplate>().unwrap();
+        assert_eq!(Some(TemplateMatch { author_ : Some("Oda".to_string()), series_ : Some("One piece".to_string()), index_ : Some(1001.0), title_ : None }), chapters.match_path(Path::new("Oda/One piece/chapter-1001.cbz")));
+        assert_eq!(Some(10.5), chapters.match_path(Path::new("Oda/One piece/chapter-10.5.cbz")).unwrap().index_);
+        assert_eq!(None, chapters.match_path(Path::new("Oda/One piece/extra/chapter-1001.cbz")));
+        assert_eq!(None, chapters.match_path(Path::new("Oda/One piece/chapter-one.cbz")));
+        let books = "{author}/{title}.{ext}".parse::<PathTemplate>().unwrap();
+        assert_eq!(Some("The Fellowship Of The Ring. Part 1".to_string()), books.match_path(Path::new("Tolkien/The Fellowship Of The Ring. Part 1.epub")).unwrap().title_);
+    }
+
+    #[test]
+    fn invalid_path_templates_should_be_refused() {
+        for invalid in ["{author}/{serie}.{ext}", "{author/{title}.{ext}", "{author}}/{title}", "{title}/{title}.{ext}"] {
+            assert!(invalid.parse::<PathTemplate>().is_err(), "{} should not parse", invalid);
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use std::path::Path;
+use lopdf::{decode_text_string, Dictionary, Document as PdfDocument};
+use quick_xml::{events::Event, Reader};
+
+use crate::db_manager::{ContributorRole, PartialDate};
+
+use super::{parse_partial_date, ExtractedMetadata};
+
+/// Reads the Info dictionary and the XMP packet of a PDF, XMP values win since writers keep them more up to date.
+pub fn read_pdf_metadata(file_path : &Path) -> Result<ExtractedMetadata, String> {
+    let pdf = PdfDocument::load(file_path)
+        .map_err(|e| format!("Could not read PDF {} : {}", file_path.display(), e))?;
+    let mut extracted = match info_dictionary(&pdf) {
+        Some(info) => read_info(info),
+        None => ExtractedMetadata::default()
+    };
+    // a malformed XMP packet leaves the Info values in place
+    if let Some(xmp_metadata) = xmp_packet(&pdf).and_then(|xmp| parse_xmp(&xmp).ok()) {
+        extracted.title_ = xmp_metadata.title_.or(extracted.title_);
+        if !xmp_metadata.contributors_.is_empty() {
+            extracted.contributors_ = xmp_metadata.contributors_;
+        }
+        extracted.description_ = xmp_metadata.description_.or(extracted.description_);
+        extracted.date_ = xmp_metadata.date_.or(extracted.date_);
+        extracted.language_ = xmp_metadata.language_.or(extracted.language_);
+        extracted.identifiers_ = xmp_metadata.identifiers_;
+        for keyword in xmp_metadata.tags_ {
+            if !extracted.tags_.contains(&keyword) {
+                extracted.tags_.push(keyword);
+            }
+        }
+    }
+    extracted.page_count_ = i32::try_from(pdf.get_pages().len()).ok();
+    return Ok(extracted);
+}
+
+fn info_dictionary(pdf : &PdfDocument) -> Option<&Dictionary> {
+    let info = pdf.trailer.get(b"Info").ok()?;
+    return pdf.dereference(info).ok()?.1.as_dict().ok();
+}
+
+fn xmp_packet(pdf : &PdfDocument) -> Option<String> {
+    let metadata = pdf.catalog().ok()?.get(b"Metadata").ok()?;
+    let stream = pdf.dereference(metadata).ok()?.1.as_stream().ok()?;
+    let content = stream.decompressed_content().unwrap_or_else(|_| stream.content.clone());
+    return Some(String::from_utf8_lossy(&content).to_string());
+}
+
+fn split_keywords(keywords : &str) -> Vec<String> {
+    return keywords.split([',', ';']).map(|keyword| keyword.trim().to_string()).filter(|keyword| !keyword.is_empty()).collect();
+}
+
+/// Reads a PDF date like "D:20210118120000+01'00'", as precise as it is given.
+fn parse_pdf_date(pdf_date : &str) -> Option<PartialDate> {
+    let digits : String = pdf_date.trim_start_matches("D:").chars().take_while(char::is_ascii_digit).take(8).collect();
+    let date_text = match digits.len() {
+        8 => format!("{}-{}-{}", &digits[0..4], &digits[4..6], &digits[6..8]),
+        6 => format!("{}-{}", &digits[0..4], &digits[4..6]),
+        4 => digits,
+        _ => return None
+    };
+    return date_text.parse::<PartialDate>().ok();
+}
+
+fn read_info(info : &Dictionary) -> ExtractedMetadata {
+    let text = |key : &[u8]| info.get(key).ok()
+        .and_then(|value| decode_text_string(value).ok())
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty());
+    // authors are often "A; B", commas can't split them since they also separate last and first names
+    let contributors = text(b"Author")
+        .map(|authors| authors.split(';').map(|author_name| (author_name.trim().to_string(), ContributorRole::Author)).filter(|(author_name, _)| !author_name.is_empty()).collect())
+        .unwrap_or_default();
+    return ExtractedMetadata {
+        title_ : text(b"Title"),
+        contributors_ : contributors,
+        description_ : text(b"Subject"),
+        tags_ : text(b"Keywords").map(|keywords| split_keywords(&keywords)).unwrap_or_default(),
+        date_ : text(b"CreationDate").and_then(|creation_date| parse_pdf_date(&creation_date)),
+        ..Default::default()
+    };
+}
+
+fn read_xmp_property(extracted : &mut ExtractedMetadata, property : &str, value : String) {
+    match property {
+        "dc:title" if extracted.title_.is_none() => extracted.title_ = Some(value),
+        "dc:creator" => extracted.contributors_.push((value, ContributorRole::Author)),
+        "dc:description" if extracted.description_.is_none() => extracted.description_ = Some(value),
+        "dc:subject" => extracted.tags_.push(value),
+        "pdf:Keywords" => extracted.tags_.extend(split_keywords(&value)),
+        "dc:language" if extracted.language_.is_none() => extracted.language_ = Some(value),
+        "xmp:CreateDate" => extracted.date_ = parse_partial_date(&value),
+        "prism:doi" => extracted.identifiers_.push(("doi".to_string(), value)),
+        _ => ()
+    }
+}
+
+/// Reads the Dublin Core, XMP and PDF properties of the packet. They are either attributes of rdf:Description
+/// or elements, whose list values are rdf:li elements.
+fn parse_xmp(xmp : &str) -> Result<ExtractedMetadata, String> {
+    let mut reader = Reader::from_str(xmp);
+    let mut extracted = ExtractedMetadata::default();
+    let mut open_elements : Vec<String> = Vec::new();
+    let mut text = String::new();
+    loop {
+        let (element, is_empty) = match reader.read_event().map_err(|e| e.to_string())? {
+            Event::Start(element) => (element, false),
+            Event::Empty(element) => (element, true),
+            Event::Text(content) => {
+                text.push_str(&content.unescape().map_err(|e| e.to_string())?);
+                continue;
+            },
+            Event::End(_) => {
+                let element_name = open_elements.pop().unwrap_or_default();
+                let value = text.trim().to_string();
+                text.clear();
+                let property = match element_name.as_str() {
+                    "rdf:li" => open_elements.iter().rev().find(|open_element| !open_element.starts_with("rdf:")).cloned().unwrap_or_default(),
+                    _ => element_name
+                };
+                if !value.is_empty() {
+                    read_xmp_property(&mut extracted, &property, value);
+                }
+                continue;
+            },
+            Event::Eof => break,
+            _ => continue
+        };
+        let element_name = String::from_utf8_lossy(element.name().as_ref()).to_string();
+        if element_name == "rdf:Description" {
+            for property in element.attributes().flatten() {
+                let value = property.unescape_value().map_err(|e| e.to_string())?.trim().to_string();
+                if !value.is_empty() {
+                    read_xmp_property(&mut extracted, &String::from_utf8_lossy(property.key.as_ref()), value);
+                }
+            }
+        }
+        if !is_empty {
+            open_elements.push(element_name);
+            text.clear();
+        }
+    }
+    return Ok(extracted);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::remove_file, path::Path};
+    use lopdf::{dictionary, Document as PdfDocument, Object, Stream};
+
+    use crate::db_manager::{ContributorRole, PartialDate};
+
+    use super::read_pdf_metadata;
+
+    const XMP : &str = r#"<?xpacket begin="" id="W5M0MpCehiHzreSzNTczkc9d"?>
+        <x:xmpmeta xmlns:x="adobe:ns:meta/">
+            <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+                <rdf:Description rdf:about="" xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:pdf="http://ns.adobe.com/pdf/1.3/" pdf:Keywords="transformers; attention">
+                    <dc:title><rdf:Alt><rdf:li xml:lang="x-default">Attention Is All You Need</rdf:li></rdf:Alt></dc:title>
+                    <dc:creator><rdf:Seq><rdf:li>Ashish Vaswani</rdf:li><rdf:li>Noam Shazeer</rdf:li></rdf:Seq></dc:creator>
+                    <prism:doi xmlns:prism="http://prismstandard.org/namespaces/basic/2.0/">10.48550/arXiv.1706.03762</prism:doi>
+                </rdf:Description>
+            </rdf:RDF>
+        </x:xmpmeta>
+        <?xpacket end="w"?>"#;
+
+    fn write_pdf(pdf_path : &Path, xmp : Option<&str>) {
+        let mut pdf = PdfDocument::with_version("1.5");
+        let pages_id = pdf.new_object_id();
+        let page_id = pdf.add_object(dictionary! { "Type" => "Page", "Parent" => pages_id, "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()] });
+        pdf.objects.insert(pages_id, Object::Dictionary(dictionary! { "Type" => "Pages", "Kids" => vec![page_id.into()], "Count" => 1 }));
+        let mut catalog = dictionary! { "Type" => "Catalog", "Pages" => pages_id };
+        if let Some(xmp) = xmp {
+            let metadata_id = pdf.add_object(Stream::new(dictionary! { "Type" => "Metadata", "Subtype" => "XML" }, xmp.as_bytes().to_vec()));
+            catalog.set("Metadata", metadata_id);
+        }
+        let catalog_id = pdf.add_object(catalog);
+        let info_id = pdf.add_object(dictionary! { "Title" => Object::string_literal("download(3)"), 
+                                                    "Author" => Object::string_literal("A. Vaswani; N. Shazeer"), 
+                                                    "Keywords" => Object::string_literal("attention, translation"), 
+                                                    "CreationDate" => Object::string_literal("D:20170612173000Z") });
+        pdf.trailer.set("Root", catalog_id);
+        pdf.trailer.set("Info", info_id);
+        pdf.save(pdf_path).unwrap();
+    }
+
+    #[test]
+    fn reading_pdf_should_give_info_dictionary_metadata() {
+        let pdf_path = Path::new("./reading_pdf_should_give_info_dictionary_metadata.pdf");
+        write_pdf(pdf_path, None);
+        let extracted = read_pdf_metadata(pdf_path).unwrap();
+        assert_eq!(Some("download(3)".to_string()), extracted.title_);
+        assert_eq!(vec![("A. Vaswani".to_string(), ContributorRole::Author), ("N. Shazeer".to_string(), ContributorRole::Author)], extracted.contributors_);
+        assert_eq!(vec!["attention".to_string(), "translation".to_string()], extracted.tags_);
+        assert_eq!((Some(PartialDate::from(chrono::NaiveDate::from_ymd_opt(2017, 6, 12).unwrap())), Some(1)), (extracted.date_, extracted.page_count_));
+        remove_file(pdf_path).unwrap();
+    }
+
+    #[test]
+    fn reading_pdf_should_prefer_xmp_metadata() {
+        let pdf_path = Path::new("./reading_pdf_should_prefer_xmp_metadata.pdf");
+        write_pdf(pdf_path, Some(XMP));
+        let extracted = read_pdf_metadata(pdf_path).unwrap();
+        assert_eq!(Some("Attention Is All You Need".to_string()), extracted.title_);
+        assert_eq!(vec![("Ashish Vaswani".to_string(), ContributorRole::Author), ("Noam Shazeer".to_string(), ContributorRole::Author)], extracted.contributors_);
+        assert_eq!(vec!["attention".to_string(), "translation".to_string(), "transformers".to_string()], extracted.tags_);
+        assert_eq!(vec![("doi".to_string(), "10.48550/arXiv.1706.03762".to_string())], extracted.identifiers_);
+        remove_file(pdf_path).unwrap();
+        assert!(read_pdf_metadata(pdf_path).is_err());
+    }
+
+    #[test]
+    fn reading_pdf_with_malformed_xmp_should_keep_info_dictionary_metadata() {
+        let pdf_path = Path::new("./reading_pdf_with_malformed_xmp_should_keep_info_dictionary_metadata.pdf");
+        write_pdf(pdf_path, Some("<rdf:RDF><rdf:Description><dc:title>Broken</dc:title></rdf:RDF>"));
+        let extracted = read_pdf_metadata(pdf_path).unwrap();
+        assert_eq!((Some("download(3)".to_string()), 2), (extracted.title_, extracted.contributors_.len()));
+        remove_file(pdf_path).unwrap();
+    }
+}
@@ -2,7 +2,7 @@ use std::{path::Path, process::exit};
 use std::env;
 use axum::{routing::get, routing::post, Router, response::IntoResponse, Extension};
 use axum::extract::FromRequestParts;
-use db_manager::create_database;
+use db_manager::{create_database, create_pool, get_connection, purge_trash};
 use controllers::*;
 use serde::{Deserialize, Serialize};
 use time::Duration;
@@ -26,23 +26,48 @@ async fn main() {
 
     let db_path_str = env::var("DATABASE_PATH").unwrap_or_else(|_| "./data.db".to_string());
     let db_path = Path::new(&db_path_str);
-    if !db_path.exists() {
-        match create_database(db_path) {
-            Ok(_) => (),
-            Err(error) => {
-                eprintln!("{}", error);
-                exit(-1);
+    match create_database(db_path) {
+        Ok(_) => (),
+        Err(error) => {
+            eprintln!("{}", error);
+            exit(-1);
+        }
+    }
+
+    // the trash is left untouched when the retention is not a number of days
+    let trash_retention_days = match env::var("TRASH_RETENTION_DAYS") {
+        Err(_) => Some(30),
+        Ok(days) => match days.parse::<i64>() {
+            Ok(retention_days) if retention_days >= 0 => Some(retention_days),
+            _ => {
+                eprintln!("TRASH_RETENTION_DAYS should be a number of days, 0 or more, got {} : trash is not purged", days);
+                None
             }
         }
+    };
+    if let Some(retention_days) = trash_retention_days {
+        match get_connection(db_path).and_then(|mut connection| purge_trash(&mut connection, &chrono::Duration::days(retention_days))) {
+            Ok(_) => (),
+            Err(error) => eprintln!("{}", error)
+        }
     }
 
+    let pool = match create_pool(db_path) {
+        Ok(pool) => pool,
+        Err(error) => {
+            eprintln!("{}", error);
+            exit(-1);
+        }
+    };
+
     let app = Router::new()
         .route("/", get( hello_world ))
         .route("/register", post( register ))
         .route("/login", post( login ))
             .layer(session_layer.clone())
             .layer(Extension(session_layer.clone())
-        );
+        )
+        .with_state(pool);
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
     axum::serve(listener, app).await.unwrap();
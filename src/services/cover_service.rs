@@ -0,0 +1,14 @@
+use std::{fs, path::PathBuf};
+use crate::db_manager::{run_blocking, DbError, DbPool};
+use crate::document_scanner::cover::{get_cover_thumbnail, ThumbnailSize};
+
+/// Gives the JPEG thumbnail of the document cover, extracted and cached on first request.
+pub async fn get_document_cover(pool: &DbPool, cache_directory: PathBuf, document_id: i32, size: ThumbnailSize) -> Result<Option<Vec<u8>>, DbError> {
+    run_blocking(pool, move |connection| {
+        match get_cover_thumbnail(connection, &cache_directory, &document_id, &size)? {
+            Some(thumbnail_path) => fs::read(&thumbnail_path).map(Some)
+                .map_err(|e| DbError::Io(format!("Could not read thumbnail {} : {}", thumbnail_path.display(), e))),
+            None => Ok(None)
+        }
+    }).await
+}
@@ -1,3 +1,5 @@
 mod user_service;
+mod cover_service;
 
-pub use user_service::*;
\ No newline at end of file
+pub use user_service::*;
+pub use cover_service::*;
\ No newline at end of file
@@ -1,55 +1,14 @@
-use std::env;
-use std::path::Path;
 use crate::db_manager::entities::user::{User, get_users, add_user, get_user_by_username};
-use crate::db_manager::get_connection;
+use crate::db_manager::{run_blocking, DbError, DbPool};
 
-pub fn get_all_users() -> Result<Vec<User>, String> {
-    let db_path_str = env::var("DATABASE_PATH").unwrap_or_else(|_| "./data.db".to_string());
-    let db_path = Path::new(&db_path_str);
-
-    let connection = get_connection(db_path);
-
-    if connection.is_err() {
-        return Err(connection.err().unwrap());
-    }
-
-    get_users(&mut connection?)
+pub async fn get_all_users(pool: &DbPool) -> Result<Vec<User>, DbError> {
+    run_blocking(pool, |connection| get_users(connection)).await
 }
 
-pub fn get_by_username(username: String) -> Result<Vec<User>, String> {
-    let db_path_str = env::var("DATABASE_PATH").unwrap_or_else(|_| "./data.db".to_string());
-    let db_path = Path::new(&db_path_str);
-
-    let connection = get_connection(db_path);
-
-    if connection.is_err() {
-        return Err(connection.err().unwrap());
-    }
-
-    let user = get_user_by_username(&mut connection?, &username);
-
-    if user.is_ok() {
-        return Ok(user?);
-    }
-
-    Err("User not found".to_string())
+pub async fn get_by_username(pool: &DbPool, username: String) -> Result<Vec<User>, DbError> {
+    run_blocking(pool, move |connection| get_user_by_username(connection, &username)).await
 }
 
-pub fn create_user(new_user: User) -> Result<User, String> {
-    let db_path_str = env::var("DATABASE_PATH").unwrap_or_else(|_| "./data.db".to_string());
-    let db_path = Path::new(&db_path_str);
-
-    let connection = get_connection(db_path);
-
-    if connection.is_err() {
-        return Err(connection.err().unwrap());
-    }
-
-    let user = add_user(&mut connection?, new_user);
-
-    if user.is_ok() {
-        return Ok(user?);
-    }
-
-    Err("Failed to create user".to_string())
+pub async fn create_user(pool: &DbPool, new_user: User) -> Result<User, DbError> {
+    run_blocking(pool, move |connection| add_user(connection, new_user)).await
 }
\ No newline at end of file